@@ -3,6 +3,8 @@
 
 use std::fs::remove_file;
 use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 pub use anyhow::Result;
@@ -48,15 +50,193 @@ pub enum Event<'a> {
 
 type DownloadCallback<'a> = &'a dyn Fn(Event<'_>) -> Result<()>;
 
+/// Validators of a previously downloaded copy of a resource, which make a
+/// request conditional on the resource having changed since.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    /// The `ETag` header of the response.
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the response.
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// The outcome of a download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The resource was downloaded, and the server sent these validators for it.
+    Downloaded(Validators),
+    /// The resource hasn't changed since the validators of the request were
+    /// issued, so nothing was downloaded.
+    NotModified,
+}
+
+/// Bandwidth and timeout limits applied to a single download.
+///
+/// Every backend honours all of these limits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DownloadLimits {
+    /// Maximum transfer rate in bytes per second of all the downloads in
+    /// progress together, unlimited if `None`.
+    pub max_rate: Option<u64>,
+    /// Maximum time allowed for establishing the connection.
+    pub connect_timeout: Duration,
+    /// Abort the transfer when it is too slow for too long.
+    pub low_speed: Option<LowSpeedLimit>,
+    /// Maximum time allowed for the whole transfer, unlimited if `None` or
+    /// zero. Stalled transfers are caught by `low_speed` instead, and reqwest
+    /// also gives up when nothing is received for
+    /// [`Self::DEFAULT_READ_TIMEOUT`].
+    pub timeout: Option<Duration>,
+}
+
+impl DownloadLimits {
+    pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+    pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+}
+
+impl Default for DownloadLimits {
+    fn default() -> Self {
+        Self {
+            max_rate: None,
+            connect_timeout: Self::DEFAULT_CONNECT_TIMEOUT,
+            low_speed: Some(LowSpeedLimit::default()),
+            timeout: None,
+        }
+    }
+}
+
+/// A transfer averaging less than `bytes_per_sec` over `time` is aborted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LowSpeedLimit {
+    pub bytes_per_sec: u32,
+    pub time: Duration,
+}
+
+impl Default for LowSpeedLimit {
+    /// Consider a transfer that hasn't made progress in 30s as stalled.
+    fn default() -> Self {
+        Self {
+            bytes_per_sec: 1,
+            time: Duration::from_secs(30),
+        }
+    }
+}
+
 async fn download_with_backend(
     backend: Backend,
     url: &Url,
     resume_from: u64,
+    cached: &Validators,
+    limits: &DownloadLimits,
     callback: DownloadCallback<'_>,
-) -> Result<()> {
+) -> Result<Outcome> {
+    match backend {
+        Backend::Curl => curl_download(url, resume_from, cached, limits, callback).await,
+        Backend::Reqwest(tls) => {
+            reqwest_be::download(url, resume_from, cached, limits, callback, tls).await
+        }
+    }
+}
+
+/// When the downloads in progress may next receive data without going over
+/// the rate limit between them.
+static RATE_LIMIT_SCHEDULE: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Records `len` more bytes received under `limits`, returning how long to
+/// pause before reading again so that all downloads together stay under the
+/// rate limit.
+fn rate_limit_pause(len: usize, limits: &DownloadLimits) -> Duration {
+    let Some(max_rate) = limits.max_rate.filter(|&rate| rate > 0) else {
+        return Duration::ZERO;
+    };
+    let now = Instant::now();
+    let mut next = RATE_LIMIT_SCHEDULE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let start = next.filter(|&next| next > now).unwrap_or(now);
+    let end = start + Duration::from_secs_f64(len as f64 / max_rate as f64);
+    *next = Some(end);
+    end - now
+}
+
+/// An [`Event`] sent from the thread running a libcurl transfer.
+enum CurlEvent {
+    ResumingPartialDownload,
+    DownloadContentLengthReceived(u64),
+    DownloadDataReceived(Vec<u8>),
+}
+
+/// Runs a libcurl transfer, which blocks, on a thread of its own, passing its
+/// events back to `callback` on the caller's.
+///
+/// An error from `callback` aborts the transfer.
+async fn curl_download(
+    url: &Url,
+    resume_from: u64,
+    cached: &Validators,
+    limits: &DownloadLimits,
+    callback: DownloadCallback<'_>,
+) -> Result<Outcome> {
+    // Bounded, so that a slow callback slows the transfer down rather than
+    // letting it pile up in memory.
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let (url, cached, limits) = (url.clone(), cached.clone(), *limits);
+    let transfer = tokio::task::spawn_blocking(move || {
+        curl::download(&url, resume_from, &cached, &limits, &|event| {
+            let event = match event {
+                Event::ResumingPartialDownload => CurlEvent::ResumingPartialDownload,
+                Event::DownloadContentLengthReceived(len) => {
+                    CurlEvent::DownloadContentLengthReceived(len)
+                }
+                Event::DownloadDataReceived(data) => CurlEvent::DownloadDataReceived(data.to_vec()),
+            };
+            tx.blocking_send(event)
+                .map_err(|_| anyhow::anyhow!("download aborted"))
+        })
+    });
+
+    while let Some(event) = rx.recv().await {
+        callback(match &event {
+            CurlEvent::ResumingPartialDownload => Event::ResumingPartialDownload,
+            CurlEvent::DownloadContentLengthReceived(len) => {
+                Event::DownloadContentLengthReceived(*len)
+            }
+            CurlEvent::DownloadDataReceived(data) => Event::DownloadDataReceived(data),
+        })?;
+    }
+    transfer.await?
+}
+
+/// Returns the size of the resource at `url` without downloading it, if the
+/// server reports one.
+pub async fn content_length(
+    backend: Backend,
+    url: &Url,
+    limits: &DownloadLimits,
+) -> Result<Option<u64>> {
+    // The file scheme is mostly for use by tests to mock the dist server
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| DownloadError::Message(format!("bogus file url: '{url}'")))?;
+        return match std::fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => Ok(Some(metadata.len())),
+            _ => Err(DownloadError::FileNotFound.into()),
+        };
+    }
+
     match backend {
-        Backend::Curl => curl::download(url, resume_from, callback),
-        Backend::Reqwest(tls) => reqwest_be::download(url, resume_from, callback, tls).await,
+        Backend::Curl => {
+            let (url, limits) = (url.clone(), *limits);
+            tokio::task::spawn_blocking(move || curl::content_length(&url, &limits)).await?
+        }
+        Backend::Reqwest(tls) => reqwest_be::content_length(url, limits, tls).await,
     }
 }
 
@@ -65,12 +245,57 @@ pub async fn download_to_path_with_backend(
     url: &Url,
     path: &Path,
     resume_from_partial: bool,
+    limits: &DownloadLimits,
     callback: Option<DownloadCallback<'_>>,
 ) -> Result<()> {
-    let Err(err) =
-        download_to_path_with_backend_(backend, url, path, resume_from_partial, callback).await
-    else {
-        return Ok(());
+    download_to_path(
+        backend,
+        url,
+        path,
+        resume_from_partial,
+        &Validators::default(),
+        limits,
+        callback,
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Downloads `url` to `path`, unless it hasn't changed since the `cached`
+/// validators were received for it, in which case `path` is left empty.
+pub async fn download_to_path_if_modified(
+    backend: Backend,
+    url: &Url,
+    path: &Path,
+    cached: &Validators,
+    limits: &DownloadLimits,
+    callback: Option<DownloadCallback<'_>>,
+) -> Result<Outcome> {
+    download_to_path(backend, url, path, false, cached, limits, callback).await
+}
+
+async fn download_to_path(
+    backend: Backend,
+    url: &Url,
+    path: &Path,
+    resume_from_partial: bool,
+    cached: &Validators,
+    limits: &DownloadLimits,
+    callback: Option<DownloadCallback<'_>>,
+) -> Result<Outcome> {
+    let err = match download_to_path_with_backend_(
+        backend,
+        url,
+        path,
+        resume_from_partial,
+        cached,
+        limits,
+        callback,
+    )
+    .await
+    {
+        Ok(outcome) => return Ok(outcome),
+        Err(err) => err,
     };
 
     // TODO: We currently clear up the cached download on any error, should we restrict it to a subset?
@@ -88,8 +313,10 @@ pub async fn download_to_path_with_backend_(
     url: &Url,
     path: &Path,
     resume_from_partial: bool,
+    cached: &Validators,
+    limits: &DownloadLimits,
     callback: Option<DownloadCallback<'_>>,
-) -> Result<()> {
+) -> Result<Outcome> {
     use std::cell::RefCell;
     use std::fs::OpenOptions;
     use std::io::{Read, Seek, SeekFrom, Write};
@@ -148,7 +375,7 @@ pub async fn download_to_path_with_backend_(
     let file = RefCell::new(file);
 
     // TODO: the sync callback will stall the async runtime if IO calls block, which is OS dependent. Rearrange.
-    download_with_backend(backend, url, resume_from, &|event| {
+    let outcome = download_with_backend(backend, url, resume_from, cached, limits, &|event| {
         if let Event::DownloadDataReceived(data) = event {
             file.borrow_mut()
                 .write_all(data)
@@ -165,7 +392,7 @@ pub async fn download_to_path_with_backend_(
         .sync_data()
         .context("unable to sync download to disk")?;
 
-    Ok::<(), anyhow::Error>(())
+    Ok(outcome)
 }
 
 #[cfg(all(not(feature = "reqwest-backend"), not(feature = "curl-backend")))]
@@ -180,16 +407,18 @@ pub mod curl {
     use std::time::Duration;
 
     use anyhow::{Context, Result};
-    use curl::easy::Easy;
+    use curl::easy::{Easy, List};
     use url::Url;
 
-    use super::{DownloadError, Event};
+    use super::{DownloadError, DownloadLimits, Event, Outcome, Validators};
 
     pub fn download(
         url: &Url,
         resume_from: u64,
+        cached: &Validators,
+        limits: &DownloadLimits,
         callback: &dyn Fn(Event<'_>) -> Result<()>,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
         // Fetch either a cached libcurl handle (which will preserve open
         // connections) or create a new one if it isn't listed.
         //
@@ -211,9 +440,35 @@ pub mod curl {
                 let _ = handle.resume_from(0);
             }
 
-            // Take at most 30s to connect
-            handle.connect_timeout(Duration::new(30, 0))?;
+            handle.connect_timeout(limits.connect_timeout)?;
+
+            // The handle is reused across downloads, so every limit has to be
+            // reset explicitly: zero means "no limit" for all of these. The
+            // rate limit is shared with other downloads, so it is enforced by
+            // pausing in the write callback instead.
+            handle.max_recv_speed(0)?;
+            match limits.low_speed {
+                Some(low_speed) => {
+                    handle.low_speed_limit(low_speed.bytes_per_sec)?;
+                    handle.low_speed_time(low_speed.time)?;
+                }
+                None => {
+                    handle.low_speed_limit(0)?;
+                    handle.low_speed_time(Duration::ZERO)?;
+                }
+            }
+            handle.timeout(limits.timeout.unwrap_or(Duration::ZERO))?;
 
+            let mut headers = List::new();
+            if let Some(etag) = &cached.etag {
+                headers.append(&format!("If-None-Match: {etag}"))?;
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                headers.append(&format!("If-Modified-Since: {last_modified}"))?;
+            }
+            handle.http_headers(headers)?;
+
+            let validators = RefCell::new(Validators::default());
             {
                 let cberr = RefCell::new(None);
                 let mut transfer = handle.transfer();
@@ -223,7 +478,14 @@ pub mod curl {
                 // to disk.
                 transfer.write_function(|data| {
                     match callback(Event::DownloadDataReceived(data)) {
-                        Ok(()) => Ok(data.len()),
+                        Ok(()) => {
+                            // This runs on a thread of its own, see `curl_download`
+                            let pause = super::rate_limit_pause(data.len(), limits);
+                            if !pause.is_zero() {
+                                std::thread::sleep(pause);
+                            }
+                            Ok(data.len())
+                        }
                         Err(e) => {
                             *cberr.borrow_mut() = Some(e);
                             Ok(0)
@@ -235,6 +497,19 @@ pub mod curl {
                 // comes so we know how much we're downloading.
                 transfer.header_function(|header| {
                     if let Ok(data) = str::from_utf8(header) {
+                        if let Some((name, value)) = data.split_once(':') {
+                            let value = Some(value.trim().to_owned());
+                            match name.to_ascii_lowercase().as_str() {
+                                "etag" => validators.borrow_mut().etag = value,
+                                "last-modified" => validators.borrow_mut().last_modified = value,
+                                _ => {}
+                            }
+                        } else if data.starts_with("HTTP/") {
+                            // Only keep the headers of the final response
+                            // when following redirects.
+                            *validators.borrow_mut() = Validators::default();
+                        }
+
                         let prefix = "content-length: ";
                         if data.to_ascii_lowercase().starts_with(prefix) {
                             if let Ok(s) = data[prefix.len()..].trim().parse::<u64>() {
@@ -274,15 +549,33 @@ pub mod curl {
             // If we didn't get a 20x or 0 ("OK" for files) then return an error
             let code = handle.response_code()?;
             match code {
-                0 | 200..=299 => {}
-                _ => {
-                    return Err(DownloadError::HttpStatus(code).into());
-                }
-            };
-
-            Ok(())
+                0 | 200..=299 => Ok(Outcome::Downloaded(validators.into_inner())),
+                304 if !cached.is_empty() => Ok(Outcome::NotModified),
+                _ => Err(DownloadError::HttpStatus(code).into()),
+            }
         })
     }
+
+    pub fn content_length(url: &Url, limits: &DownloadLimits) -> Result<Option<u64>> {
+        let mut handle = Easy::new();
+        handle.url(url.as_ref())?;
+        handle.follow_location(true)?;
+        handle.useragent(super::CURL_USER_AGENT)?;
+        handle.nobody(true)?;
+        handle.connect_timeout(limits.connect_timeout)?;
+        if let Some(timeout) = limits.timeout {
+            handle.timeout(timeout)?;
+        }
+        handle.perform().context("error during request")?;
+
+        let code = handle.response_code()?;
+        if !matches!(code, 0 | 200..=299) {
+            return Err(DownloadError::HttpStatus(code).into());
+        }
+        // libcurl reports -1 when the length is unknown.
+        let len = handle.content_length_download()?;
+        Ok((len >= 0.0).then_some(len as u64))
+    }
 }
 
 #[cfg(feature = "reqwest-backend")]
@@ -293,37 +586,44 @@ pub mod reqwest_be {
     ))]
     compile_error!("Must select a reqwest TLS backend");
 
+    use std::future::Future;
     use std::io;
     #[cfg(feature = "reqwest-rustls-tls")]
     use std::sync::Arc;
     #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
     use std::sync::LazyLock;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     use anyhow::{anyhow, Context, Result};
-    use reqwest::{header, Client, ClientBuilder, Proxy, Response};
+    use reqwest::{header, Client, ClientBuilder, Proxy, RequestBuilder, Response, StatusCode};
     #[cfg(feature = "reqwest-rustls-tls")]
     use rustls::crypto::aws_lc_rs;
     use tokio_stream::StreamExt;
     use url::Url;
 
-    use super::{DownloadError, Event, TlsBackend};
+    use super::{DownloadError, DownloadLimits, Event, Outcome, TlsBackend, Validators};
 
     pub async fn download(
         url: &Url,
         resume_from: u64,
+        cached: &Validators,
+        limits: &DownloadLimits,
         callback: &dyn Fn(Event<'_>) -> Result<()>,
         tls: TlsBackend,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
         // Short-circuit reqwest for the "file:" URL scheme
         if download_from_file_url(url, resume_from, callback)? {
-            return Ok(());
+            return Ok(Outcome::Downloaded(Validators::default()));
         }
 
-        let res = request(url, resume_from, tls)
-            .await
+        let res = within_low_speed_limit(limits, request(url, resume_from, cached, limits, tls))
+            .await?
             .context("failed to make network request")?;
 
+        if res.status() == StatusCode::NOT_MODIFIED && !cached.is_empty() {
+            return Ok(Outcome::NotModified);
+        }
+
         if !res.status().is_success() {
             let code: u16 = res.status().into();
             return Err(anyhow!(DownloadError::HttpStatus(u32::from(code))));
@@ -334,12 +634,114 @@ pub mod reqwest_be {
             callback(Event::DownloadContentLengthReceived(len))?;
         }
 
+        let header_value = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let validators = Validators {
+            etag: header_value(header::ETAG),
+            last_modified: header_value(header::LAST_MODIFIED),
+        };
+
+        let mut pacer = Pacer::new(limits);
         let mut stream = res.bytes_stream();
-        while let Some(item) = stream.next().await {
+        while let Some(item) = within_low_speed_limit(limits, stream.next()).await? {
             let bytes = item?;
             callback(Event::DownloadDataReceived(&bytes))?;
+            let pause = pacer.received(bytes.len())?;
+            if !pause.is_zero() {
+                tokio::time::sleep(pause).await;
+            }
+        }
+        Ok(Outcome::Downloaded(validators))
+    }
+
+    pub async fn content_length(
+        url: &Url,
+        limits: &DownloadLimits,
+        tls: TlsBackend,
+    ) -> Result<Option<u64>> {
+        let req = with_timeout(
+            client(tls, limits.connect_timeout)?.head(url.as_str()),
+            limits,
+        );
+        let res = within_low_speed_limit(limits, req.send())
+            .await?
+            .context("failed to make network request")?;
+
+        if !res.status().is_success() {
+            let code: u16 = res.status().into();
+            return Err(anyhow!(DownloadError::HttpStatus(u32::from(code))));
+        }
+
+        // The response to a `HEAD` request has no body, so its length can
+        // only be read from the header.
+        Ok(res
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok()))
+    }
+
+    /// Fails with [`DownloadError::LowSpeed`] if `fut` doesn't complete within
+    /// the low speed time: receiving nothing at all is the slowest transfer.
+    async fn within_low_speed_limit<F: Future>(
+        limits: &DownloadLimits,
+        fut: F,
+    ) -> Result<F::Output, DownloadError> {
+        match limits.low_speed {
+            Some(low_speed) => tokio::time::timeout(low_speed.time, fut)
+                .await
+                .map_err(|_| DownloadError::LowSpeed {
+                    limit: low_speed.bytes_per_sec,
+                    secs: low_speed.time.as_secs(),
+                }),
+            None => Ok(fut.await),
+        }
+    }
+
+    /// Enforces the rate and low speed limits of a transfer, which reqwest
+    /// has no native support for.
+    struct Pacer {
+        limits: DownloadLimits,
+        window_start: Instant,
+        window_received: u64,
+    }
+
+    impl Pacer {
+        fn new(limits: &DownloadLimits) -> Self {
+            Self {
+                limits: *limits,
+                window_start: Instant::now(),
+                window_received: 0,
+            }
+        }
+
+        /// Records `len` more received bytes, returning how long to pause
+        /// before reading again so as to stay under the rate limit.
+        fn received(&mut self, len: usize) -> Result<Duration, DownloadError> {
+            let now = Instant::now();
+            self.window_received += len as u64;
+
+            if let Some(low_speed) = self.limits.low_speed {
+                let elapsed = now.saturating_duration_since(self.window_start);
+                if elapsed >= low_speed.time {
+                    let rate = self.window_received as f64 / elapsed.as_secs_f64();
+                    if rate < f64::from(low_speed.bytes_per_sec) {
+                        return Err(DownloadError::LowSpeed {
+                            limit: low_speed.bytes_per_sec,
+                            secs: low_speed.time.as_secs(),
+                        });
+                    }
+                    self.window_start = now;
+                    self.window_received = 0;
+                }
+            }
+
+            Ok(super::rate_limit_pause(len, &self.limits))
         }
-        Ok(())
     }
 
     fn client_generic() -> ClientBuilder {
@@ -349,80 +751,114 @@ pub mod reqwest_be {
             // See <https://github.com/hyperium/hyper/issues/2312> for more details.
             .pool_max_idle_per_host(0)
             .gzip(false)
+            .read_timeout(DownloadLimits::DEFAULT_READ_TIMEOUT)
             .proxy(Proxy::custom(env_proxy))
-            .timeout(Duration::from_secs(30))
     }
 
     #[cfg(feature = "reqwest-rustls-tls")]
-    static CLIENT_RUSTLS_TLS: LazyLock<Client> = LazyLock::new(|| {
-        let catcher = || {
-            client_generic()
-                .use_preconfigured_tls(
-                    rustls_platform_verifier::tls_config_with_provider(Arc::new(
-                        aws_lc_rs::default_provider(),
-                    ))
-                    .expect("failed to initialize pre-configured rustls backend"),
-                )
-                .user_agent(super::REQWEST_RUSTLS_TLS_USER_AGENT)
-                .build()
-        };
+    fn client_rustls_tls(connect_timeout: Duration) -> Result<Client, reqwest::Error> {
+        client_generic()
+            .use_preconfigured_tls(
+                rustls_platform_verifier::tls_config_with_provider(Arc::new(
+                    aws_lc_rs::default_provider(),
+                ))
+                .expect("failed to initialize pre-configured rustls backend"),
+            )
+            .user_agent(super::REQWEST_RUSTLS_TLS_USER_AGENT)
+            .connect_timeout(connect_timeout)
+            .build()
+    }
 
+    #[cfg(feature = "reqwest-rustls-tls")]
+    static CLIENT_RUSTLS_TLS: LazyLock<Client> = LazyLock::new(|| {
         // woah, an unwrap?!
         // It's OK. This is the same as what is happening in curl.
         //
         // The curl::Easy::new() internally assert!s that the initialized
         // Easy is not null. Inside reqwest, the errors here would be from
         // the TLS library returning a null pointer as well.
-        catcher().unwrap()
+        client_rustls_tls(DownloadLimits::DEFAULT_CONNECT_TIMEOUT).unwrap()
     });
 
     #[cfg(feature = "reqwest-native-tls")]
-    static CLIENT_DEFAULT_TLS: LazyLock<Client> = LazyLock::new(|| {
-        let catcher = || {
-            client_generic()
-                .user_agent(super::REQWEST_DEFAULT_TLS_USER_AGENT)
-                .build()
-        };
+    fn client_default_tls(connect_timeout: Duration) -> Result<Client, reqwest::Error> {
+        client_generic()
+            .user_agent(super::REQWEST_DEFAULT_TLS_USER_AGENT)
+            .connect_timeout(connect_timeout)
+            .build()
+    }
 
+    #[cfg(feature = "reqwest-native-tls")]
+    static CLIENT_DEFAULT_TLS: LazyLock<Client> = LazyLock::new(|| {
         // woah, an unwrap?!
         // It's OK. This is the same as what is happening in curl.
         //
         // The curl::Easy::new() internally assert!s that the initialized
         // Easy is not null. Inside reqwest, the errors here would be from
         // the TLS library returning a null pointer as well.
-        catcher().unwrap()
+        client_default_tls(DownloadLimits::DEFAULT_CONNECT_TIMEOUT).unwrap()
     });
 
     fn env_proxy(url: &Url) -> Option<Url> {
         env_proxy::for_url(url).to_url()
     }
 
-    async fn request(
-        url: &Url,
-        resume_from: u64,
-        backend: TlsBackend,
-    ) -> Result<Response, DownloadError> {
-        let client: &Client = match backend {
+    /// Returns a client honouring `connect_timeout`.
+    ///
+    /// The connect timeout can only be set on the client itself, so the shared
+    /// clients are only used for the default timeout.
+    fn client(backend: TlsBackend, connect_timeout: Duration) -> Result<Client, DownloadError> {
+        let shared = connect_timeout == DownloadLimits::DEFAULT_CONNECT_TIMEOUT;
+        Ok(match backend {
+            #[cfg(feature = "reqwest-rustls-tls")]
+            TlsBackend::Rustls if shared => CLIENT_RUSTLS_TLS.clone(),
             #[cfg(feature = "reqwest-rustls-tls")]
-            TlsBackend::Rustls => &CLIENT_RUSTLS_TLS,
+            TlsBackend::Rustls => client_rustls_tls(connect_timeout)?,
             #[cfg(not(feature = "reqwest-rustls-tls"))]
             TlsBackend::Rustls => {
                 return Err(DownloadError::BackendUnavailable("reqwest rustls"));
             }
             #[cfg(feature = "reqwest-native-tls")]
-            TlsBackend::NativeTls => &CLIENT_DEFAULT_TLS,
+            TlsBackend::NativeTls if shared => CLIENT_DEFAULT_TLS.clone(),
+            #[cfg(feature = "reqwest-native-tls")]
+            TlsBackend::NativeTls => client_default_tls(connect_timeout)?,
             #[cfg(not(feature = "reqwest-native-tls"))]
             TlsBackend::NativeTls => {
                 return Err(DownloadError::BackendUnavailable("reqwest default TLS"));
             }
-        };
+        })
+    }
+
+    async fn request(
+        url: &Url,
+        resume_from: u64,
+        cached: &Validators,
+        limits: &DownloadLimits,
+        backend: TlsBackend,
+    ) -> Result<Response, DownloadError> {
+        let client = client(backend, limits.connect_timeout)?;
         let mut req = client.get(url.as_str());
 
         if resume_from != 0 {
             req = req.header(header::RANGE, format!("bytes={resume_from}-"));
         }
 
-        Ok(req.send().await?)
+        if let Some(etag) = &cached.etag {
+            req = req.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            req = req.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+
+        Ok(with_timeout(req, limits).send().await?)
+    }
+
+    /// Applies the total timeout of `limits` to `req`, if any.
+    fn with_timeout(req: RequestBuilder, limits: &DownloadLimits) -> RequestBuilder {
+        match limits.timeout {
+            Some(timeout) if !timeout.is_zero() => req.timeout(timeout),
+            _ => req,
+        }
     }
 
     fn download_from_file_url(
@@ -462,6 +898,42 @@ pub mod reqwest_be {
             Ok(false)
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn rate_limited_downloads_have_no_total_timeout() {
+            let timeout = |limits: &DownloadLimits| {
+                let req = Client::new().get("https://static.rust-lang.org/rustc.tar.xz");
+                with_timeout(req, limits)
+                    .build()
+                    .unwrap()
+                    .timeout()
+                    .copied()
+            };
+
+            // At 2 MiB/s, a 100 MiB archive takes 50s by design.
+            let limits = DownloadLimits {
+                max_rate: Some(2 * 1024 * 1024),
+                ..DownloadLimits::default()
+            };
+            assert_eq!(timeout(&limits), None);
+
+            let limits = DownloadLimits {
+                timeout: Some(Duration::from_secs(600)),
+                ..limits
+            };
+            assert_eq!(timeout(&limits), Some(Duration::from_secs(600)));
+
+            let limits = DownloadLimits {
+                timeout: Some(Duration::ZERO),
+                ..limits
+            };
+            assert_eq!(timeout(&limits), None);
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -472,6 +944,8 @@ pub enum DownloadError {
     FileNotFound,
     #[error("download backend '{0}' unavailable")]
     BackendUnavailable(&'static str),
+    #[error("transfer speed stayed below {limit} B/s for {secs} seconds")]
+    LowSpeed { limit: u32, secs: u64 },
     #[error("{0}")]
     Message(String),
     #[error(transparent)]
@@ -489,13 +963,19 @@ pub mod curl {
     use anyhow::{anyhow, Result};
     use url::Url;
 
-    use super::{DownloadError, Event};
+    use super::{DownloadError, DownloadLimits, Event, Outcome, Validators};
 
     pub fn download(
         _url: &Url,
         _resume_from: u64,
+        _cached: &Validators,
+        _limits: &DownloadLimits,
         _callback: &dyn Fn(Event<'_>) -> Result<()>,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
+        Err(anyhow!(DownloadError::BackendUnavailable("curl")))
+    }
+
+    pub fn content_length(_url: &Url, _limits: &DownloadLimits) -> Result<Option<u64>> {
         Err(anyhow!(DownloadError::BackendUnavailable("curl")))
     }
 }
@@ -505,14 +985,24 @@ pub mod reqwest_be {
     use anyhow::{anyhow, Result};
     use url::Url;
 
-    use super::{DownloadError, Event, TlsBackend};
+    use super::{DownloadError, DownloadLimits, Event, Outcome, TlsBackend, Validators};
 
     pub async fn download(
         _url: &Url,
         _resume_from: u64,
+        _cached: &Validators,
+        _limits: &DownloadLimits,
         _callback: &dyn Fn(Event<'_>) -> Result<()>,
         _tls: TlsBackend,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
+        Err(anyhow!(DownloadError::BackendUnavailable("reqwest")))
+    }
+
+    pub async fn content_length(
+        _url: &Url,
+        _limits: &DownloadLimits,
+        _tls: TlsBackend,
+    ) -> Result<Option<u64>> {
         Err(anyhow!(DownloadError::BackendUnavailable("reqwest")))
     }
 }
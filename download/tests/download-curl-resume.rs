@@ -2,13 +2,14 @@
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use url::Url;
 
 use download::*;
 
 mod support;
-use crate::support::{serve_file, tmp_dir, write_file};
+use crate::support::{serve_file, serve_nothing, tmp_dir, write_file, ETAG, LAST_MODIFIED};
 
 #[tokio::test]
 async fn partially_downloaded_file_gets_resumed_from_byte_offset() {
@@ -20,9 +21,16 @@ async fn partially_downloaded_file_gets_resumed_from_byte_offset() {
     write_file(&target_path, "123");
 
     let from_url = Url::from_file_path(&from_path).unwrap();
-    download_to_path_with_backend(Backend::Curl, &from_url, &target_path, true, None)
-        .await
-        .expect("Test download failed");
+    download_to_path_with_backend(
+        Backend::Curl,
+        &from_url,
+        &target_path,
+        true,
+        &DownloadLimits::default(),
+        None,
+    )
+    .await
+    .expect("Test download failed");
 
     assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
 }
@@ -46,6 +54,7 @@ async fn callback_gets_all_data_as_if_the_download_happened_all_at_once() {
         &from_url,
         &target_path,
         true,
+        &DownloadLimits::default(),
         Some(&|msg| {
             match msg {
                 Event::ResumingPartialDownload => {
@@ -76,3 +85,144 @@ async fn callback_gets_all_data_as_if_the_download_happened_all_at_once() {
     assert_eq!(observed_bytes, vec![b'1', b'2', b'3', b'4', b'5']);
     assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
 }
+
+#[tokio::test]
+async fn download_is_throttled_to_max_rate() {
+    let tmpdir = tmp_dir();
+    let target_path = tmpdir.path().join("downloaded");
+
+    let addr = serve_file(vec![b'x'; 32 * 1024]);
+    let from_url = format!("http://{addr}").parse().unwrap();
+
+    let limits = DownloadLimits {
+        max_rate: Some(16 * 1024),
+        ..DownloadLimits::default()
+    };
+    let start = Instant::now();
+    download_to_path_with_backend(Backend::Curl, &from_url, &target_path, false, &limits, None)
+        .await
+        .expect("Test download failed");
+
+    assert!(start.elapsed() >= Duration::from_millis(1500));
+    assert_eq!(std::fs::read(&target_path).unwrap().len(), 32 * 1024);
+}
+
+#[tokio::test]
+async fn concurrent_downloads_share_max_rate() {
+    let tmpdir = tmp_dir();
+    let addr = serve_file(vec![b'x'; 16 * 1024]);
+    let from_url = format!("http://{addr}").parse().unwrap();
+
+    let limits = DownloadLimits {
+        max_rate: Some(16 * 1024),
+        ..DownloadLimits::default()
+    };
+    let (first_path, second_path) = (tmpdir.path().join("first"), tmpdir.path().join("second"));
+    let download =
+        |path| download_to_path_with_backend(Backend::Curl, &from_url, path, false, &limits, None);
+    let start = Instant::now();
+    let (first, second) = tokio::join!(download(&first_path), download(&second_path));
+    first.expect("Test download failed");
+    second.expect("Test download failed");
+
+    // Each alone would take a second.
+    assert!(start.elapsed() >= Duration::from_millis(1500));
+}
+
+#[tokio::test]
+async fn unresponsive_server_hits_total_timeout() {
+    let tmpdir = tmp_dir();
+    let target_path = tmpdir.path().join("downloaded");
+
+    let addr = serve_nothing();
+    let from_url = format!("http://{addr}").parse().unwrap();
+
+    let limits = DownloadLimits {
+        low_speed: None,
+        timeout: Some(Duration::from_secs(1)),
+        ..DownloadLimits::default()
+    };
+    let start = Instant::now();
+    download_to_path_with_backend(Backend::Curl, &from_url, &target_path, false, &limits, None)
+        .await
+        .expect_err("Test download should time out");
+
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn unresponsive_server_hits_low_speed_limit() {
+    let tmpdir = tmp_dir();
+    let target_path = tmpdir.path().join("downloaded");
+
+    let addr = serve_nothing();
+    let from_url = format!("http://{addr}").parse().unwrap();
+
+    let limits = DownloadLimits {
+        low_speed: Some(LowSpeedLimit {
+            bytes_per_sec: 1,
+            time: Duration::from_secs(1),
+        }),
+        ..DownloadLimits::default()
+    };
+    let start = Instant::now();
+    download_to_path_with_backend(Backend::Curl, &from_url, &target_path, false, &limits, None)
+        .await
+        .expect_err("Test download should be aborted");
+
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn conditional_download_skips_unmodified_file() {
+    let tmpdir = tmp_dir();
+    let target_path = tmpdir.path().join("downloaded");
+
+    let addr = serve_file(b"12345".to_vec());
+    let from_url = format!("http://{addr}").parse().unwrap();
+
+    let limits = DownloadLimits::default();
+    let download = |cached| {
+        download_to_path_if_modified(
+            Backend::Curl,
+            &from_url,
+            &target_path,
+            cached,
+            &limits,
+            None,
+        )
+    };
+
+    let unconditional = Validators::default();
+    let outcome = download(&unconditional)
+        .await
+        .expect("Test download failed");
+    let validators = Validators {
+        etag: Some(ETAG.to_owned()),
+        last_modified: Some(LAST_MODIFIED.to_owned()),
+    };
+    assert_eq!(outcome, Outcome::Downloaded(validators.clone()));
+    assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
+
+    let outcome = download(&validators).await.expect("Test download failed");
+    assert_eq!(outcome, Outcome::NotModified);
+
+    let stale = Validators {
+        etag: Some("\"stale\"".to_owned()),
+        last_modified: None,
+    };
+    let outcome = download(&stale).await.expect("Test download failed");
+    assert_eq!(outcome, Outcome::Downloaded(validators));
+    assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
+}
+
+#[tokio::test]
+async fn content_length_is_queried_without_downloading() {
+    let addr = serve_file(b"12345".to_vec());
+    let from_url = format!("http://{addr}").parse().unwrap();
+
+    let len = content_length(Backend::Curl, &from_url, &DownloadLimits::default())
+        .await
+        .expect("Test request failed");
+    assert_eq!(len, Some(5));
+}
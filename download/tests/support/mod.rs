@@ -82,10 +82,29 @@ pub fn serve_file(contents: Vec<u8>) -> SocketAddr {
     addr.unwrap()
 }
 
+/// The `ETag` sent for all contents: they never change once served.
+pub const ETAG: &str = "\"rustup-test\"";
+/// The `Last-Modified` date sent for all contents.
+pub const LAST_MODIFIED: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
 fn serve_contents(
     req: hyper::Request<hyper::body::Incoming>,
     contents: Vec<u8>,
 ) -> hyper::Response<Full<Bytes>> {
+    let headers = req.headers();
+    if headers
+        .get(hyper::header::IF_NONE_MATCH)
+        .is_some_and(|v| v == ETAG)
+        || headers
+            .get(hyper::header::IF_MODIFIED_SINCE)
+            .is_some_and(|v| v == LAST_MODIFIED)
+    {
+        return hyper::Response::builder()
+            .status(hyper::StatusCode::NOT_MODIFIED)
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+    }
+
     let mut range_header = None;
     let (status, body) = if let Some(range) = req.headers().get(hyper::header::RANGE) {
         // extract range "bytes={start}-"
@@ -109,6 +128,8 @@ fn serve_contents(
     let mut res = hyper::Response::builder()
         .status(status)
         .header(hyper::header::CONTENT_LENGTH, body.len())
+        .header(hyper::header::ETAG, ETAG)
+        .header(hyper::header::LAST_MODIFIED, LAST_MODIFIED)
         .body(Full::new(Bytes::from(body)))
         .unwrap();
     if let Some(range) = range_header {
@@ -117,3 +138,19 @@ fn serve_contents(
     }
     res
 }
+
+/// Accepts connections but never responds on them.
+pub fn serve_nothing() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("can not bind");
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut streams = Vec::new();
+        for stream in listener.incoming() {
+            // Keep the connection open so the client is left waiting.
+            streams.push(stream);
+        }
+    });
+
+    addr
+}
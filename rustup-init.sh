@@ -60,6 +60,8 @@ Options:
           Don't update any existing default toolchain after install
       --no-modify-path
           Don't configure the PATH environment variable
+      --config <PATH>
+          Read the installation options from a TOML answer file
   -h, --help
           Print help
   -V, --version
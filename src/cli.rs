@@ -2,12 +2,16 @@
 #[macro_use]
 pub mod log;
 pub mod common;
+mod complete;
+mod doc_search;
+mod doc_server;
 mod download_tracker;
 pub mod errors;
 mod help;
 mod job;
 mod markdown;
 pub mod proxy_mode;
+mod release_notes;
 pub mod rustup_mode;
 pub mod self_update;
 pub mod setup_mode;
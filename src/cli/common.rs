@@ -14,18 +14,27 @@ use anyhow::{anyhow, Context, Result};
 use git_testament::{git_testament, render_testament};
 use tracing::{debug, error, info, trace, warn};
 
+use super::markdown::md;
+use super::release_notes::{self, Version, RELEASE_NOTES_URL};
 use super::self_update;
 use crate::{
     cli::download_tracker::DownloadTracker,
     config::Cfg,
     dist::{
-        manifest::ComponentStatus, notifications as dist_notifications, TargetTriple, ToolchainDesc,
+        manifest::ComponentStatus,
+        manifestation::{PlannedComponent, UpdatePlan},
+        notifications as dist_notifications, RunSupport, TargetTriple, ToolchainDesc,
     },
     install::UpdateStatus,
     notifications::Notification,
     process::{terminalsource, Process},
     toolchain::{DistributableToolchain, LocalToolchainName, Toolchain, ToolchainName},
-    utils::{notifications as util_notifications, notify::NotificationLevel, utils},
+    utils::{
+        notifications as util_notifications,
+        notify::NotificationLevel,
+        units::{Size, Unit, UnitMode},
+        utils,
+    },
 };
 
 pub(crate) const WARN_COMPLETE_PROFILE: &str = "downloading with complete profile isn't recommended unless you are a developer of the rust language";
@@ -266,13 +275,13 @@ fn show_channel_updates(
         .iter()
         .fold(0, |a, &(_, _, width, _, _, _)| cmp::max(a, width));
 
-    for (pkg, banner, width, color, version, previous_version) in data {
+    for (pkg, banner, width, color, version, previous_version) in &data {
         let padding = max_width - width;
         let padding: String = " ".repeat(padding);
         let _ = write!(t.lock(), "  {padding}");
         let _ = t.attr(terminalsource::Attr::Bold);
         if let Some(color) = color {
-            let _ = t.fg(color);
+            let _ = t.fg(*color);
         }
         let _ = write!(t.lock(), "{pkg} {banner}");
         let _ = t.reset();
@@ -284,9 +293,119 @@ fn show_channel_updates(
     }
     let _ = writeln!(t.lock());
 
+    if cfg.get_release_notes()? {
+        for (pkg, _, _, _, version, previous_version) in &data {
+            if let (PackageUpdate::Toolchain(desc), Some(previous_version)) =
+                (pkg, previous_version)
+            {
+                show_release_notes(cfg, desc, previous_version, version);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows the release notes of the versions after `previous_version` up to
+/// `version`, as installed in the toolchain `desc`.
+fn show_release_notes(cfg: &Cfg<'_>, desc: &ToolchainDesc, previous_version: &str, version: &str) {
+    let (Some(from), Some(to)) = (Version::find(previous_version), Version::find(version)) else {
+        return;
+    };
+    if from >= to {
+        return;
+    }
+    let notes = Toolchain::new(cfg, desc.into())
+        .ok()
+        .and_then(|toolchain| release_notes::installed(&toolchain));
+    let Some(notes) = notes else {
+        info!("the release notes of '{desc}' are not installed; see {RELEASE_NOTES_URL}");
+        return;
+    };
+    let sections = release_notes::sections(&notes, Some(from), to);
+    if sections.is_empty() {
+        return;
+    }
+    let mut t = cfg.process.stdout().terminal(cfg.process);
+    md(&mut t, sections);
+    let _ = writeln!(t.lock());
+}
+
+/// Shows the changes an update of `toolchain` would make, `None` meaning
+/// that it is up to date.
+pub(crate) fn show_update_plan(
+    process: &Process,
+    toolchain: &ToolchainDesc,
+    plan: Option<&UpdatePlan>,
+) -> Result<()> {
+    let mut t = process.stdout().terminal(process);
+    let plan = match plan {
+        Some(plan) if !plan.is_empty() => plan,
+        _ => {
+            writeln!(t.lock(), "{toolchain}: no changes")?;
+            return Ok(());
+        }
+    };
+
+    writeln!(t.lock(), "{toolchain}:")?;
+    let actions = [
+        ("add", Some(terminalsource::Color::Green), &plan.add),
+        ("upgrade", Some(terminalsource::Color::Green), &plan.upgrade),
+        ("remove", Some(terminalsource::Color::Red), &plan.remove),
+    ];
+    for (action, color, components) in actions {
+        for component in components {
+            let _ = write!(t.lock(), "  ");
+            let _ = t.attr(terminalsource::Attr::Bold);
+            if let Some(color) = color {
+                let _ = t.fg(color);
+            }
+            let _ = write!(t.lock(), "{action:<7}");
+            let _ = t.reset();
+            writeln!(t.lock(), " {}", describe_planned_component(component))?;
+        }
+    }
+
+    let download_size = match plan.download_size() {
+        Some(size) => format_size(size),
+        None => "unknown".to_owned(),
+    };
+    writeln!(t.lock(), "  total download size: {download_size}")?;
+    let disk_usage_change = match plan.disk_usage_change() {
+        Some(change) if change < 0 => format!("-{}", format_size(change.unsigned_abs())),
+        Some(change) => format!("+{}", format_size(change as u64)),
+        None => "unknown".to_owned(),
+    };
+    writeln!(
+        t.lock(),
+        "  estimated disk usage change: {disk_usage_change}"
+    )?;
     Ok(())
 }
 
+fn describe_planned_component(component: &PlannedComponent) -> String {
+    let mut description = component.name.clone();
+    match (&component.old_version, &component.new_version) {
+        (Some(old), Some(new)) => description += &format!(" {old} -> {new}"),
+        (Some(version), None) | (None, Some(version)) => description += &format!(" {version}"),
+        (None, None) => {}
+    }
+    match component.download_size {
+        Some(0) if component.new_version.is_some() => description += " (already downloaded)",
+        Some(0) => {}
+        Some(size) => description += &format!(" ({} to download)", format_size(size)),
+        None => description += " (download size unknown)",
+    }
+    description
+}
+
+fn format_size(bytes: u64) -> String {
+    Size::new(bytes as usize, Unit::B, UnitMode::Norm)
+        .to_string()
+        .trim_start()
+        .to_owned()
+}
+
 pub(crate) async fn update_all_channels(
     cfg: &Cfg<'_>,
     do_self_update: bool,
@@ -314,7 +433,7 @@ pub(crate) async fn update_all_channels(
     };
 
     if do_self_update {
-        exit_code &= self_update(show_channel_updates, cfg.process).await?;
+        exit_code &= self_update(show_channel_updates, cfg).await?;
     } else {
         show_channel_updates()?;
     }
@@ -359,7 +478,7 @@ pub(crate) fn self_update_permitted(explicit: bool) -> Result<SelfUpdatePermissi
 }
 
 /// Performs all of a self-update: check policy, download, apply and exit.
-pub(crate) async fn self_update<F>(before_restart: F, process: &Process) -> Result<utils::ExitCode>
+pub(crate) async fn self_update<F>(before_restart: F, cfg: &Cfg<'_>) -> Result<utils::ExitCode>
 where
     F: FnOnce() -> Result<()>,
 {
@@ -373,7 +492,9 @@ where
         SelfUpdatePermission::Permit => {}
     }
 
-    let setup_path = self_update::prepare_update(process).await?;
+    let process = cfg.process;
+    let pin = cfg.get_self_update_version()?;
+    let setup_path = self_update::prepare_update(process, None, pin.as_ref()).await?;
 
     before_restart()?;
 
@@ -436,11 +557,17 @@ pub(crate) fn list_toolchains(
             let is_default_toolchain = default_toolchain_name.as_ref() == Some(&toolchain);
             let is_active_toolchain = active_toolchain_name.as_ref() == Some(&toolchain);
 
+            let is_held_toolchain = match &toolchain {
+                ToolchainName::Official(desc) => cfg.is_held(desc)?,
+                ToolchainName::Custom(_) => false,
+            };
+
             print_toolchain(
                 cfg,
                 &toolchain.to_string(),
                 is_default_toolchain,
                 is_active_toolchain,
+                is_held_toolchain,
                 verbose,
                 quiet,
             )
@@ -453,6 +580,7 @@ pub(crate) fn list_toolchains(
         toolchain: &str,
         is_default: bool,
         is_active: bool,
+        is_held: bool,
         verbose: bool,
         quiet: bool,
     ) -> Result<()> {
@@ -472,11 +600,18 @@ pub(crate) fn list_toolchains(
         } else {
             String::new()
         };
-        let status_str = match (is_default, is_active) {
-            (true, true) => " (active, default)",
-            (true, false) => " (default)",
-            (false, true) => " (active)",
-            (false, false) => "",
+        let labels = [
+            (is_active, "active"),
+            (is_default, "default"),
+            (is_held, "held"),
+        ]
+        .into_iter()
+        .filter_map(|(set, label)| set.then_some(label))
+        .collect::<Vec<_>>();
+        let status_str = if labels.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", labels.join(", "))
         };
 
         writeln!(
@@ -641,12 +776,24 @@ pub(crate) fn warn_if_host_is_incompatible(
     host_arch: &TargetTriple,
     target_triple: &TargetTriple,
     force_non_host: bool,
+    process: &Process,
 ) -> Result<()> {
-    if force_non_host || host_arch.can_run(target_triple)? {
+    if force_non_host {
         return Ok(());
     }
+    let reason = match host_arch.run_support(target_triple, process)? {
+        RunSupport::Native => return Ok(()),
+        RunSupport::Emulated { interpreter } => {
+            info!(
+                "toolchain '{toolchain}' will run under emulation by {}",
+                interpreter.display()
+            );
+            return Ok(());
+        }
+        RunSupport::Unsupported { reason } => reason,
+    };
     error!("DEPRECATED: future versions of rustup will require --force-non-host to install a non-host toolchain.");
-    warn!("toolchain '{toolchain}' may not be able to run on this system.");
+    warn!("toolchain '{toolchain}' may not be able to run on this system: {reason}.");
     warn!("If you meant to build software to target that platform, perhaps try `rustup target add {target_triple}` instead?");
     Ok(())
 }
@@ -0,0 +1,419 @@
+//! Completions computed when the shell asks for them.
+//!
+//! The scripts `rustup completions` prints for bash, zsh, fish and
+//! PowerShell pass the words of the command line to the hidden
+//! `rustup __complete` command, which prints the candidates for the word
+//! under the cursor, one per line. Unlike completions generated ahead of
+//! time, these can offer the toolchains, components and targets that are
+//! installed or available.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use clap::{Arg, Command};
+use clap_complete::Shell;
+use itertools::Itertools;
+
+use crate::{
+    config::Cfg,
+    dist::{known, PartialToolchainDesc},
+    toolchain::DistributableToolchain,
+};
+
+/// The channels offered where a toolchain is installed, besides the
+/// toolchains that already are.
+const CHANNELS: &[&str] = &["stable", "beta", "nightly"];
+
+/// The candidates for `words[cword]`, or for a new word if `cword` is past
+/// the end of `words`. `words` starts with the command being completed,
+/// which is either rustup, described by `cmd`, or one of its proxies.
+pub(crate) fn candidates(
+    cfg: &Cfg<'_>,
+    cmd: Command,
+    words: &[String],
+    cword: usize,
+) -> Vec<String> {
+    let current = words.get(cword).map(String::as_str).unwrap_or_default();
+    let before = &words[..cword.min(words.len())];
+    let Some(program) = before.first() else {
+        return Vec::new();
+    };
+    let program = Path::new(program)
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+
+    let candidates = if program == "rustup" {
+        rustup_candidates(cfg, cmd, &before[1..], current)
+    } else if before.len() == 1 && current.starts_with('+') {
+        // Only the first argument of a proxy can name a toolchain.
+        plus_toolchains(cfg)
+    } else {
+        Vec::new()
+    };
+
+    candidates
+        .into_iter()
+        .filter(|c| c.starts_with(current))
+        .unique()
+        .collect()
+}
+
+fn rustup_candidates(
+    cfg: &Cfg<'_>,
+    mut cmd: Command,
+    words: &[String],
+    current: &str,
+) -> Vec<String> {
+    cmd.build();
+    let mut cmd = &cmd;
+    let mut path = Vec::new();
+    let mut toolchain = None;
+    let mut pending = None;
+    let mut positional = 0;
+
+    for word in words {
+        if let Some(arg) = pending.take() {
+            if is_toolchain(arg) {
+                toolchain = Some(word.clone());
+            }
+        } else if let Some(long) = word.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            if let Some(arg) = cmd.get_arguments().find(|a| a.get_long() == Some(name)) {
+                match value {
+                    Some(value) if is_toolchain(arg) => toolchain = Some(value.to_owned()),
+                    None if arg.get_action().takes_values() => pending = Some(arg),
+                    _ => {}
+                }
+            }
+        } else if let Some(shorts) = word.strip_prefix('-').filter(|s| !s.is_empty()) {
+            // A short option takes the next word as its value only if it
+            // isn't followed by the value itself.
+            let mut chars = shorts.chars();
+            if let (Some(short), None) = (chars.next(), chars.next()) {
+                pending = cmd
+                    .get_arguments()
+                    .find(|a| a.get_short() == Some(short))
+                    .filter(|a| a.get_action().takes_values());
+            }
+        } else if let Some(name) = word.strip_prefix('+').filter(|_| path.is_empty()) {
+            toolchain = Some(name.to_owned());
+        } else if let Some(subcmd) = cmd.find_subcommand(word) {
+            cmd = subcmd;
+            path.push(subcmd.get_name());
+            positional = 0;
+        } else {
+            positional += 1;
+        }
+    }
+
+    let cx = Context {
+        cfg,
+        path: &path,
+        toolchain: toolchain.as_deref(),
+        current,
+    };
+    if let Some(arg) = pending {
+        return cx.values(arg);
+    }
+    if let Some((name, _)) = current.strip_prefix("--").and_then(|s| s.split_once('=')) {
+        return match cmd.get_arguments().find(|a| a.get_long() == Some(name)) {
+            Some(arg) => cx
+                .values(arg)
+                .into_iter()
+                .map(|v| format!("--{name}={v}"))
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+    if current.starts_with('-') {
+        return cmd
+            .get_arguments()
+            .filter(|a| !a.is_hide_set())
+            .filter_map(Arg::get_long)
+            .map(|long| format!("--{long}"))
+            .collect();
+    }
+    if path.is_empty() && current.starts_with('+') {
+        return plus_toolchains(cfg);
+    }
+
+    let mut candidates = Vec::new();
+    if positional == 0 {
+        candidates.extend(
+            cmd.get_subcommands()
+                .filter(|c| !c.is_hide_set())
+                .map(|c| c.get_name().to_owned()),
+        );
+    }
+    let positionals = cmd.get_positionals().collect::<Vec<_>>();
+    let arg = positionals.get(positional).or_else(|| {
+        // The last positional argument may take any number of values.
+        positionals
+            .last()
+            .filter(|a| a.get_num_args().is_some_and(|n| n.max_values() > 1))
+    });
+    if let Some(arg) = arg {
+        candidates.extend(cx.values(arg));
+    }
+    candidates
+}
+
+fn is_toolchain(arg: &Arg) -> bool {
+    arg.get_id() == "toolchain"
+}
+
+/// What the values of an argument are completed from.
+struct Context<'a> {
+    cfg: &'a Cfg<'a>,
+    /// The names of the subcommands given so far.
+    path: &'a [&'a str],
+    /// The toolchain given so far, with `+toolchain` or `--toolchain`.
+    toolchain: Option<&'a str>,
+    current: &'a str,
+}
+
+impl Context<'_> {
+    fn values(&self, arg: &Arg) -> Vec<String> {
+        match arg.get_id().as_str() {
+            "toolchain" => self.toolchains(),
+            "component" => self.components(),
+            "target" => self.targets(),
+            "host_triple" => triples(self.current),
+            "profile" | "profile_name" => self.profiles(arg),
+            _ => arg
+                .get_possible_values()
+                .iter()
+                .filter(|v| !v.is_hide_set())
+                .map(|v| v.get_name().to_owned())
+                .collect(),
+        }
+    }
+
+    fn toolchains(&self) -> Vec<String> {
+        match self.path {
+            // A link is given a new name.
+            ["toolchain", "link"] => return Vec::new(),
+            ["toolchain", "install" | "update" | "add"] | ["install" | "update"] => {}
+            _ => return installed_toolchains(self.cfg),
+        }
+        CHANNELS
+            .iter()
+            .map(|&c| c.to_owned())
+            .chain(installed_toolchains(self.cfg))
+            .collect()
+    }
+
+    fn components(&self) -> Vec<String> {
+        let Some(distributable) = self.distributable() else {
+            return Vec::new();
+        };
+        let Ok(components) = distributable.components() else {
+            return Vec::new();
+        };
+        // Components for the toolchain's own target are named without it.
+        let suffix = format!("-{}", distributable.desc().target);
+        let removing = self.path.contains(&"remove");
+        components
+            .into_iter()
+            .filter(|c| match removing {
+                true => c.installed,
+                false => c.available && !c.installed,
+            })
+            .map(|c| match c.name.strip_suffix(&suffix) {
+                Some(name) => name.to_owned(),
+                None => c.name,
+            })
+            .collect()
+    }
+
+    fn targets(&self) -> Vec<String> {
+        let Some(distributable) = self.distributable() else {
+            return Vec::new();
+        };
+        let Ok(components) = distributable.components() else {
+            return Vec::new();
+        };
+        let removing = self.path.contains(&"remove");
+        // The target of a component may be any, installed or not.
+        let any = self.path.contains(&"component") && !removing;
+        components
+            .into_iter()
+            .filter(|c| c.component.short_name_in_manifest() == "rust-std")
+            .filter(|c| match (any, removing) {
+                (true, _) => c.available,
+                (false, true) => c.installed,
+                (false, false) => c.available && !c.installed,
+            })
+            .filter_map(|c| c.component.target)
+            .map(|t| t.to_string())
+            .collect()
+    }
+
+    fn profiles(&self, arg: &Arg) -> Vec<String> {
+        let custom = self
+            .cfg
+            .settings_file
+            .with(|s| Ok(s.profiles.keys().cloned().collect::<Vec<_>>()))
+            .unwrap_or_default();
+        arg.get_possible_values()
+            .iter()
+            .map(|v| v.get_name().to_owned())
+            .chain(custom)
+            .collect()
+    }
+
+    fn distributable(&self) -> Option<DistributableToolchain<'_>> {
+        let toolchain = self
+            .toolchain
+            .map(PartialToolchainDesc::from_str)
+            .transpose()
+            .ok()?;
+        DistributableToolchain::from_partial(toolchain, self.cfg).ok()
+    }
+}
+
+fn installed_toolchains(cfg: &Cfg<'_>) -> Vec<String> {
+    cfg.list_toolchains()
+        .unwrap_or_default()
+        .iter()
+        .map(|t| t.to_string())
+        .collect()
+}
+
+fn plus_toolchains(cfg: &Cfg<'_>) -> Vec<String> {
+    installed_toolchains(cfg)
+        .into_iter()
+        .map(|t| format!("+{t}"))
+        .collect()
+}
+
+/// Target triples, one part at a time: the architectures, then the
+/// operating systems for the architecture given, then the environments.
+fn triples(current: &str) -> Vec<String> {
+    for arch in known::LIST_ARCHS {
+        let Some(rest) = current.strip_prefix(arch).and_then(|r| r.strip_prefix('-')) else {
+            continue;
+        };
+        for os in known::LIST_OSES {
+            if rest.strip_prefix(os).is_some_and(|r| r.starts_with('-')) {
+                return known::LIST_ENVS
+                    .iter()
+                    .map(|env| format!("{arch}-{os}-{env}"))
+                    .collect();
+            }
+        }
+        return known::LIST_OSES
+            .iter()
+            .map(|os| format!("{arch}-{os}"))
+            .collect();
+    }
+    known::LIST_ARCHS
+        .iter()
+        .map(|arch| arch.to_string())
+        .collect()
+}
+
+/// A script that completes `commands` with `rustup __complete`, defining
+/// a function named `function` where the shell needs one.
+pub(crate) fn script(shell: Shell, function: &str, commands: &[&str]) -> Option<String> {
+    let template = match shell {
+        Shell::Bash => BASH_SCRIPT,
+        Shell::Zsh => ZSH_SCRIPT,
+        Shell::Fish => FISH_SCRIPT,
+        Shell::PowerShell => POWERSHELL_SCRIPT,
+        _ => return None,
+    };
+    let commands = match shell {
+        Shell::PowerShell => commands.iter().map(|c| format!("'{c}'")).join(","),
+        _ => commands.join(" "),
+    };
+    Some(
+        template
+            .replace("{function}", function)
+            .replace("{commands}", &commands),
+    )
+}
+
+const BASH_SCRIPT: &str = r#"{function}() {
+    local IFS=$'\n'
+    COMPREPLY=($(rustup __complete --cword "$COMP_CWORD" -- "${COMP_WORDS[@]}" 2>/dev/null))
+}
+
+complete -o default -F {function} {commands}
+"#;
+
+const ZSH_SCRIPT: &str = r#"#compdef {commands}
+
+{function}() {
+    local -a candidates
+    candidates=(${(f)"$(rustup __complete --cword $((CURRENT - 1)) -- "${words[@]}" 2>/dev/null)"})
+    if (( ${#candidates} )); then
+        compadd -a candidates
+    else
+        _files
+    fi
+}
+
+if [ "$funcstack[1]" = "{function}" ]; then
+    {function} "$@"
+else
+    compdef {function} {commands}
+fi
+"#;
+
+const FISH_SCRIPT: &str = r#"function {function}
+    set -l tokens (commandline -opc)
+    rustup __complete --cword (count $tokens) -- $tokens (commandline -ct) 2>/dev/null
+end
+
+for command in {commands}
+    complete -c $command -a '({function})'
+end
+"#;
+
+const POWERSHELL_SCRIPT: &str = r#"Register-ArgumentCompleter -Native -CommandName {commands} -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @($commandAst.CommandElements |
+        Where-Object { $_.Extent.StartOffset -lt $cursorPosition } |
+        ForEach-Object { $_.ToString() })
+    $cword = $words.Count
+    if ($wordToComplete) { $cword -= 1 }
+    rustup __complete --cword $cword '--' @words 2>$null | ForEach-Object {
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }
+}
+"#;
+
+/// Completes `+toolchain` ahead of the completions cargo ships for bash,
+/// which are sourced first.
+pub(crate) const BASH_CARGO_PLUS_TOOLCHAIN: &str = r#"
+_rustup_cargo() {
+    if [[ $COMP_CWORD -eq 1 && ${COMP_WORDS[1]} == +* ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(rustup __complete --cword 1 -- "${COMP_WORDS[@]}" 2>/dev/null))
+    elif declare -F _cargo >/dev/null; then
+        _cargo "$@"
+    fi
+}
+
+complete -F _rustup_cargo cargo
+"#;
+
+/// Completes `+toolchain` ahead of the completions cargo ships for zsh,
+/// which are sourced first.
+pub(crate) const ZSH_CARGO_PLUS_TOOLCHAIN: &str = r#"
+if (( $+functions[_cargo] && ! $+functions[_rustup_cargo] )); then
+    functions[_rustup_cargo]=$functions[_cargo]
+    _cargo() {
+        if (( CURRENT == 2 )) && [[ $PREFIX == +* ]]; then
+            compadd -- ${(f)"$(rustup __complete --cword 1 -- cargo "$PREFIX" 2>/dev/null)"}
+        else
+            _rustup_cargo "$@"
+        fi
+    }
+fi
+"#;
@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// The crates whose API documentation is searched, in the order their
+/// items are preferred when they rank equally. Items of `core` and `alloc`
+/// are re-exported by `std`.
+const CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+/// An item of the API documentation, found from the name of its page.
+pub(crate) struct Item {
+    pub(crate) kind: String,
+    pub(crate) path: String,
+    /// The page of the item, relative to the root of the documentation.
+    pub(crate) file: PathBuf,
+    crate_rank: usize,
+    /// The size of the page, in bytes.
+    size: u64,
+}
+
+impl Item {
+    fn name(&self) -> &str {
+        self.path.rsplit("::").next().unwrap_or(&self.path)
+    }
+
+    /// How well the item matches `query`, which is in lowercase, with 0
+    /// being the best match.
+    fn score(&self, query: &str) -> Option<usize> {
+        let name = self.name().to_lowercase();
+        if name == query {
+            Some(0)
+        } else if name.starts_with(query) {
+            Some(1)
+        } else if name.contains(query) {
+            Some(2)
+        } else if self.path.to_lowercase().contains(query) {
+            Some(3)
+        } else {
+            None
+        }
+    }
+}
+
+/// The items of the documentation in `root` that match `query`, best
+/// matches first.
+pub(crate) fn search(root: &Path, query: &str) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    for (crate_rank, name) in CRATES.iter().enumerate() {
+        let dir = Path::new(name);
+        if root.join(dir).is_dir() {
+            index_dir(root, dir, &[name.to_string()], crate_rank, &mut items)?;
+        }
+    }
+
+    let query = query.trim().to_lowercase();
+    let mut matches = items
+        .into_iter()
+        .filter_map(|item| Some((item.score(&query)?, item)))
+        .filter(|(_, item)| !is_redirect(root, item))
+        .collect::<Vec<_>>();
+    // Shallower items come first, as they're usually the ones re-exported.
+    matches.sort_by(|(a, a_item), (b, b_item)| {
+        let key =
+            |score: &usize, item: &Item| (*score, item.path.matches("::").count(), item.crate_rank);
+        key(a, a_item)
+            .cmp(&key(b, b_item))
+            .then_with(|| a_item.path.cmp(&b_item.path))
+    });
+    Ok(matches.into_iter().map(|(_, item)| item).collect())
+}
+
+fn index_dir(
+    root: &Path,
+    dir: &Path,
+    module: &[String],
+    crate_rank: usize,
+    items: &mut Vec<Item>,
+) -> Result<()> {
+    let full = root.join(dir);
+    if full.join("index.html").exists() {
+        items.push(Item {
+            kind: match module.len() {
+                1 => "crate".to_owned(),
+                _ => "mod".to_owned(),
+            },
+            path: module.join("::"),
+            file: dir.join("index.html"),
+            crate_rank,
+            size: fs::metadata(full.join("index.html"))?.len(),
+        });
+    }
+
+    let entries = fs::read_dir(&full).with_context(|| format!("Failed to read_dir {full:?}"))?;
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        let rel = dir.join(file_name);
+
+        if entry.file_type()?.is_dir() {
+            let mut module = module.to_vec();
+            module.push(file_name.to_owned());
+            index_dir(root, &rel, &module, crate_rank, items)?;
+            continue;
+        }
+
+        // Item pages are named `<kind>.<name>.html`.
+        let Some((kind, name)) = file_name
+            .strip_suffix(".html")
+            .and_then(|stem| stem.split_once('.'))
+        else {
+            continue;
+        };
+        items.push(Item {
+            kind: kind.to_owned(),
+            path: format!("{}::{name}", module.join("::")),
+            file: rel,
+            crate_rank,
+            size: entry.metadata()?.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Whether the page of `item` only redirects to another page, as rustdoc
+/// writes for items that are re-exported or renamed.
+fn is_redirect(root: &Path, item: &Item) -> bool {
+    // Redirects are tiny, so larger pages needn't be read.
+    const MAX_REDIRECT_SIZE: u64 = 2048;
+
+    item.size <= MAX_REDIRECT_SIZE
+        && fs::read_to_string(root.join(&item.file))
+            .is_ok_and(|page| page.contains(r#"<meta http-equiv="refresh""#))
+}
+
+/// The short description rustdoc writes in the page of `item`.
+pub(crate) fn description(root: &Path, item: &Item) -> Option<String> {
+    let page = fs::read_to_string(root.join(&item.file)).ok()?;
+    let start = page.find(r#"<meta name="description" content=""#)?;
+    let content = &page[start..];
+    let content = &content[content.find("content=\"")? + "content=\"".len()..];
+    let content = &content[..content.find('"')?];
+    let description = unescape(content);
+    // Pages without a summary get a generic description.
+    match description.starts_with("API documentation for the Rust") {
+        true => None,
+        false => Some(description),
+    }
+}
+
+fn unescape(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('&') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+            }
+            .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                text.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
@@ -0,0 +1,249 @@
+//! A local HTTP server for the documentation of installed toolchains, run
+//! by `rustup doc --serve`.
+//!
+//! Each toolchain is served below a prefix named after it, such as
+//! `/stable-x86_64-unknown-linux-gnu/std/index.html`, and `/` lists them.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+use crate::utils::http_server::{percent_decode, read_request, write_head, Method, Status};
+
+pub(crate) struct DocServer {
+    /// The prefix each toolchain is served under, and the root of its
+    /// documentation.
+    roots: Vec<(String, PathBuf)>,
+}
+
+impl DocServer {
+    pub(crate) fn new(roots: Vec<(String, PathBuf)>) -> Self {
+        Self { roots }
+    }
+
+    /// Serves connections accepted on `listener` until accepting fails.
+    pub(crate) async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, peer) = listener
+                .accept()
+                .await
+                .context("failed to accept connection")?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.handle(stream).await {
+                    warn!("failed to serve {peer}: {e:#}");
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, mut stream: TcpStream) -> Result<()> {
+        let request = match read_request(&mut stream).await? {
+            Ok(request) => request,
+            Err(status) => return write_head(&mut stream, status, 0, &[]).await,
+        };
+        debug!("{} {}", request.method, request.path);
+
+        let (status, body, content_type) = match self.resolve(&request.path) {
+            Resolved::Index => (Status::Ok, self.index().into_bytes(), HTML),
+            Resolved::File(path) => match tokio::fs::read(&path).await {
+                Ok(body) => (Status::Ok, body, content_type(&path)),
+                Err(_) => (Status::NotFound, Vec::new(), HTML),
+            },
+            Resolved::Redirect(location) => {
+                return write_head(&mut stream, Status::Found, 0, &[("Location", &location)]).await;
+            }
+            Resolved::Invalid => (Status::BadRequest, Vec::new(), HTML),
+            Resolved::NotFound => (Status::NotFound, Vec::new(), HTML),
+        };
+
+        write_head(
+            &mut stream,
+            status,
+            body.len() as u64,
+            &[("Content-Type", content_type)],
+        )
+        .await?;
+        if request.method == Method::Get {
+            stream.write_all(&body).await?;
+        }
+        stream.flush().await?;
+        Ok(())
+    }
+
+    fn resolve(&self, path: &str) -> Resolved {
+        let Some(path) = percent_decode(path) else {
+            return Resolved::Invalid;
+        };
+        if path.contains('\\') {
+            return Resolved::Invalid;
+        }
+
+        let path = path.trim_start_matches('/');
+        if path.is_empty() {
+            return Resolved::Index;
+        }
+        let (prefix, rest) = path.split_once('/').unwrap_or((path, ""));
+        // Decoding mustn't let a request escape the roots, whether through
+        // `..` or an absolute path, which `join` would take as a new root.
+        let escapes = Path::new(rest)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)));
+        if escapes {
+            return Resolved::Invalid;
+        }
+        let Some((_, root)) = self.roots.iter().find(|(p, _)| p == prefix) else {
+            return Resolved::NotFound;
+        };
+
+        let file = root.join(rest);
+        if file.is_dir() {
+            // Relative links within a directory's index need the slash.
+            if !path.ends_with('/') {
+                return Resolved::Redirect(format!("/{path}/"));
+            }
+            return Resolved::File(file.join("index.html"));
+        }
+        Resolved::File(file)
+    }
+
+    /// A page linking to the documentation of each toolchain.
+    fn index(&self) -> String {
+        let mut page = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Rust documentation</title>\n</head>\n<body>\n\
+             <h1>Rust documentation</h1>\n<ul>\n",
+        );
+        for (prefix, _) in &self.roots {
+            let prefix = html_escape(prefix);
+            page += &format!("<li><a href=\"/{prefix}/\">{prefix}</a></li>\n");
+        }
+        page += "</ul>\n</body>\n</html>\n";
+        page
+    }
+}
+
+enum Resolved {
+    Index,
+    File(PathBuf),
+    Redirect(String),
+    Invalid,
+    NotFound,
+}
+
+const HTML: &str = "text/html; charset=utf-8";
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html" | "htm") => HTML,
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt" | "md") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::net::SocketAddr;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    /// Makes a request, returning the head and the body of the response.
+    async fn get(addr: SocketAddr, path: &str) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+
+        let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(response[..end].to_vec()).unwrap();
+        (head, response[end + 4..].to_vec())
+    }
+
+    #[tokio::test]
+    async fn serves_toolchains_under_prefixes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut roots = Vec::new();
+        for name in ["stable", "nightly"] {
+            let root = dir.path().join(name);
+            fs::create_dir_all(root.join("std")).unwrap();
+            fs::write(root.join("std").join("index.html"), name).unwrap();
+            fs::write(root.join("main.css"), "").unwrap();
+            roots.push((name.to_owned(), root));
+        }
+        let secret = dir.path().join("secret");
+        fs::write(&secret, "secret").unwrap();
+        let server = Arc::new(DocServer::new(roots));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = async {
+            let (head, body) = get(addr, "/nightly/std/index.html").await;
+            assert!(head.starts_with("HTTP/1.1 200 OK"));
+            assert!(head.contains("Content-Type: text/html"));
+            assert_eq!(body, b"nightly");
+
+            let (_, body) = get(addr, "/stable/std/").await;
+            assert_eq!(body, b"stable");
+
+            let (head, _) = get(addr, "/stable/std").await;
+            assert!(head.starts_with("HTTP/1.1 302 Found"));
+            assert!(head.contains("Location: /stable/std/"));
+
+            let (head, _) = get(addr, "/stable/main.css").await;
+            assert!(head.contains("Content-Type: text/css"));
+
+            let (_, body) = get(addr, "/").await;
+            let body = String::from_utf8(body).unwrap();
+            assert!(body.contains(r#"<a href="/stable/">"#));
+            assert!(body.contains(r#"<a href="/nightly/">"#));
+
+            let (head, _) = get(addr, "/beta/std/index.html").await;
+            assert!(head.starts_with("HTTP/1.1 404 Not Found"));
+
+            let (head, _) = get(addr, "/stable/%2e%2e/nightly/std/index.html").await;
+            assert!(head.starts_with("HTTP/1.1 400 Bad Request"));
+            let (head, _) = get(addr, "/stable/std/%2E%2E/%2e%2e/secret").await;
+            assert!(head.starts_with("HTTP/1.1 400 Bad Request"));
+
+            // Absolute paths, however they're spelled, aren't new roots
+            let secret = secret.display().to_string();
+            let (head, body) = get(addr, &format!("/stable/{secret}")).await;
+            assert!(head.starts_with("HTTP/1.1 400 Bad Request"), "{head}");
+            assert_ne!(body, b"secret");
+            let (head, _) = get(addr, &format!("/stable/{}", secret.replace('/', "%2F"))).await;
+            assert!(head.starts_with("HTTP/1.1 400 Bad Request"), "{head}");
+            let (head, _) = get(addr, "/stable//std/index.html").await;
+            assert!(head.starts_with("HTTP/1.1 400 Bad Request"), "{head}");
+        };
+        tokio::select! {
+            res = server.serve(listener) => panic!("server stopped: {res:?}"),
+            () = client => {}
+        }
+    }
+}
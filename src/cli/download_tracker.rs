@@ -14,11 +14,14 @@ const DOWNLOAD_TRACK_COUNT: usize = 5;
 
 /// Tracks download progress and displays information about it to a terminal.
 ///
-/// *not* safe for tracking concurrent downloads yet - it is basically undefined
-/// what will happen.
+/// Concurrent downloads, such as those of toolchains updated together, share
+/// one line: their sizes and progress add up, and it is only finished once
+/// the last of them is.
 pub(crate) struct DownloadTracker {
-    /// Content-Length of the to-be downloaded object.
+    /// Content-Length of the to-be downloaded objects, summed.
     content_len: Option<usize>,
+    /// The downloads of a known length that are in progress.
+    in_progress: usize,
     /// Total data downloaded in bytes.
     total_downloaded: usize,
     /// Data downloaded this second.
@@ -43,6 +46,8 @@ pub(crate) struct DownloadTracker {
     displayed_charcount: Option<usize>,
     /// What units to show progress in
     units: Vec<Unit>,
+    /// The download rate cap in bytes per second, if any.
+    rate_limit: Option<usize>,
     /// Whether we display progress
     display_progress: bool,
     stdout_is_a_tty: bool,
@@ -53,6 +58,7 @@ impl DownloadTracker {
     pub(crate) fn new_with_display_progress(display_progress: bool, process: &Process) -> Self {
         Self {
             content_len: None,
+            in_progress: 0,
             total_downloaded: 0,
             downloaded_this_sec: 0,
             downloaded_last_few_secs: VecDeque::with_capacity(DOWNLOAD_TRACK_COUNT),
@@ -61,6 +67,7 @@ impl DownloadTracker {
             term: process.stdout().terminal(process),
             displayed_charcount: None,
             units: vec![Unit::B],
+            rate_limit: None,
             display_progress,
             stdout_is_a_tty: process.stdout().is_a_tty(process),
         }
@@ -83,6 +90,10 @@ impl DownloadTracker {
                 self.download_finished();
                 true
             }
+            Notification::Install(In::Utils(Un::UsingDownloadRateLimit(rate))) => {
+                self.rate_limit = Some(rate as usize);
+                true
+            }
             Notification::Install(In::Utils(Un::DownloadPushUnit(unit))) => {
                 self.push_unit(unit);
                 true
@@ -98,7 +109,8 @@ impl DownloadTracker {
 
     /// Notifies self that Content-Length information has been received.
     pub(crate) fn content_length_received(&mut self, content_len: u64) {
-        self.content_len = Some(content_len as usize);
+        self.in_progress += 1;
+        self.content_len = Some(self.content_len.unwrap_or(0) + content_len as usize);
     }
 
     /// Notifies self that data of size `len` has been received.
@@ -129,6 +141,11 @@ impl DownloadTracker {
     }
     /// Notifies self that the download has finished.
     pub(crate) fn download_finished(&mut self) {
+        self.in_progress = self.in_progress.saturating_sub(1);
+        if self.in_progress > 0 {
+            // Others are still going
+            return;
+        }
         if self.displayed_charcount.is_some() {
             // Display the finished state
             self.display();
@@ -139,12 +156,14 @@ impl DownloadTracker {
     /// Resets the state to be ready for a new download.
     fn prepare_for_new_download(&mut self) {
         self.content_len = None;
+        self.in_progress = 0;
         self.total_downloaded = 0;
         self.downloaded_this_sec = 0;
         self.downloaded_last_few_secs.clear();
         self.start_sec = Some(Instant::now());
         self.last_sec = None;
         self.displayed_charcount = None;
+        self.rate_limit = None;
     }
     /// Display the tracked download information to the terminal.
     fn display(&mut self) {
@@ -159,6 +178,11 @@ impl DownloadTracker {
                 let len = self.downloaded_last_few_secs.len();
                 let speed = if len > 0 { sum / len } else { 0 };
                 let speed_h = Size::new(speed, unit, UnitMode::Rate);
+                // Once the speed gets close to the cap, the cap is what holds it back.
+                let throttled = match self.rate_limit {
+                    Some(limit) if speed >= limit - limit / 10 => " (throttled)",
+                    _ => "",
+                };
                 let elapsed_h = Instant::now().saturating_duration_since(start_sec);
 
                 // First, move to the start of the current line and clear it.
@@ -180,26 +204,28 @@ impl DownloadTracker {
                     Some(content_len) => {
                         let content_len_h = Size::new(content_len, unit, UnitMode::Norm);
                         let percent = (self.total_downloaded as f64 / content_len as f64) * 100.;
-                        let remaining = content_len - self.total_downloaded;
+                        let remaining = content_len.saturating_sub(self.total_downloaded);
                         let eta_h = Duration::from_secs(if speed == 0 {
                             u64::MAX
                         } else {
                             (remaining / speed) as u64
                         });
                         format!(
-                            "{} / {} ({:3.0} %) {} in {}{}",
+                            "{} / {} ({:3.0} %) {}{} in {}{}",
                             total_h,
                             content_len_h,
                             percent,
                             speed_h,
+                            throttled,
                             elapsed_h.display(),
                             Eta(eta_h),
                         )
                     }
                     None => format!(
-                        "Total: {} Speed: {} Elapsed: {}",
+                        "Total: {} Speed: {}{} Elapsed: {}",
                         total_h,
                         speed_h,
+                        throttled,
                         elapsed_h.display()
                     ),
                 };
@@ -272,7 +298,29 @@ fn format_dhms(sec: u64) -> (u64, u8, u8, u8) {
 
 #[cfg(test)]
 mod tests {
-    use super::format_dhms;
+    use super::{format_dhms, DownloadTracker};
+    use crate::process::TestProcess;
+
+    #[test]
+    fn download_tracker_adds_up_concurrent_downloads() {
+        let tp = TestProcess::default();
+        let mut tracker = DownloadTracker::new_with_display_progress(false, &tp.process);
+        tracker.content_length_received(100);
+        tracker.content_length_received(50);
+        tracker.data_received(30);
+        tracker.data_received(20);
+        assert_eq!(tracker.content_len, Some(150));
+        assert_eq!(tracker.total_downloaded, 50);
+
+        // The other download is still going
+        tracker.download_finished();
+        assert_eq!(tracker.content_len, Some(150));
+        assert_eq!(tracker.total_downloaded, 50);
+
+        tracker.download_finished();
+        assert_eq!(tracker.content_len, None);
+        assert_eq!(tracker.total_downloaded, 0);
+    }
 
     #[test]
     fn download_tracker_format_dhms_test() {
@@ -106,6 +106,52 @@ pub(crate) static TOOLCHAIN_LINK_HELP: &str = r"Discussion:
     If you now compile a crate in the current directory, the custom
     toolchain 'latest-stage1' will be used.";
 
+pub(crate) static TOOLCHAIN_ROLLBACK_HELP: &str = r"Discussion:
+    Whenever a toolchain is updated or uninstalled, rustup keeps a copy of
+    the manifest it was installed from and of its list of components.
+    `rustup toolchain rollback` reinstalls the same release with the same
+    components, undoing the latest change to the toolchain, or the one given
+    with `--entry`. See `rustup history` for the changes recorded.
+
+    For example, if an update of the nightly toolchain broke your build, you
+    can go back to the nightly you had before with:
+
+        $ rustup toolchain rollback nightly
+
+    Rolling back again goes back one more change. To undo a rollback
+    instead, pass its own entry with `--entry`.";
+
+pub(crate) static TOOLCHAIN_HOLD_HELP: &str = r"Discussion:
+    A held toolchain is left as it is by `rustup update`, whether it is
+    named or updated along with all the others, and by `rustup toolchain
+    install`. Components and targets a toolchain file asks for aren't
+    installed into it either. Pass `--force` to `rustup update` or
+    `rustup toolchain install` to update a held toolchain anyway.
+
+    This is useful to keep a nightly that is known to work with a project:
+
+        $ rustup toolchain hold nightly
+
+    `rustup toolchain list` and `rustup check` show which toolchains are
+    held, and `rustup toolchain unhold` releases them.";
+
+pub(crate) static TOOLCHAIN_SET_PROFILE_HELP: &str = r"Discussion:
+    Profiles otherwise only apply to newly installed toolchains. This
+    command installs the components of a profile that an installed
+    toolchain lacks, from the same release as the toolchain, so it isn't
+    updated:
+
+        $ rustup toolchain set-profile stable default
+
+    Components the toolchain has beyond the profile are kept, unless
+    `--prune` is passed, which removes them in the same step. This is how
+    to move a toolchain to a smaller profile:
+
+        $ rustup toolchain set-profile nightly minimal --prune
+
+    The standard libraries of other targets are left alone either way: see
+    `rustup target`.";
+
 pub(crate) static OVERRIDE_HELP: &str = r"Discussion:
     Overrides configure Rustup to use a specific toolchain when
     running in a specific directory.
@@ -149,12 +195,75 @@ pub(crate) static RUN_HELP: &str = r"Discussion:
 
         $ rustup run nightly cargo build";
 
+pub(crate) static SHELL_HELP: &str = r"Discussion:
+    Starts your shell with the environment rustup sets for commands run
+    with the toolchain, installing the toolchain first if needed. Tools
+    run from the shell use that toolchain, as if `+toolchain` was passed
+    to each of them:
+
+        $ rustup shell +nightly-2024-05-01
+
+    Nothing is changed outside the shell, so there is nothing to undo
+    when you exit it. The shell is the one `SHELL` names.
+
+    The prompt is not changed. The toolchain name is exported as
+    `RUSTUP_SHELL_TOOLCHAIN`, so a prompt can show it, e.g. in bash:
+
+        PS1='${RUSTUP_SHELL_TOOLCHAIN:+($RUSTUP_SHELL_TOOLCHAIN) }'$PS1";
+
+pub(crate) static ENV_HELP: &str = r#"Discussion:
+    Prints the environment variables rustup sets for commands run with
+    a toolchain: `PATH`, the dynamic library search path,
+    `RUSTUP_TOOLCHAIN`, `RUSTUP_HOME` and `CARGO_HOME`. This lets tools
+    that don't go through rustup, such as direnv, editors or build
+    scripts, use the same environment.
+
+    The toolchain is the active one unless `+toolchain` or `--toolchain`
+    is given. To load it into the current shell:
+
+        $ eval "$(rustup +nightly env)"
+
+    `--shell json` prints the variables as a JSON object instead."#;
+
 pub(crate) static DOC_HELP: &str = r"Discussion:
     Opens the documentation for the currently active toolchain with
     the default browser.
 
     By default, it opens the documentation index. Use the various
-    flags to open specific pieces of documentation.";
+    flags to open specific pieces of documentation.
+
+    `--search` looks for the API items whose names match a query, such
+    as `read_dir` or `io::read`, and prints the best matches with their
+    descriptions. This works offline from the installed `rust-docs`
+    component. Pass `--open <N>` to open the N-th match, or also
+    `--path` to print its location.
+
+    `--serve` serves the documentation over HTTP on localhost and prints
+    the URL of the requested page instead of opening it, which helps
+    where a browser can't open local files, such as in containers, WSL
+    or over SSH. Give `--toolchain` several times to serve the
+    documentation of several toolchains side by side, each under a path
+    named after it.";
+
+pub(crate) static RELEASE_NOTES_HELP: &str = r"Discussion:
+    Shows the release notes of the Rust version a toolchain is at,
+    from the copy installed by its `rust-docs` component, so this
+    works offline.
+
+    To also show the release notes of the new versions whenever
+    `rustup update` updates a toolchain, run
+
+        $ rustup set release-notes enable";
+
+pub(crate) static SERVE_CACHE_HELP: &str = r"Discussion:
+    Runs an HTTP server that other machines can use as their
+    `RUSTUP_DIST_SERVER`, forwarding requests to the upstream
+    distribution server.
+
+    Channel manifests are always fetched from upstream. The package
+    archives they list are stored in the cache directory once their
+    hash has been checked against the manifest, and are then served
+    without going upstream again.";
 
 pub(crate) static COMPLETIONS_HELP: &str = r"Discussion:
     Enable tab completion for Bash, Fish, Zsh, or PowerShell
@@ -274,7 +383,23 @@ pub(crate) static COMPLETIONS_HELP: &str = r"Discussion:
 
     Zsh:
 
-        $ rustup completions zsh cargo > ~/.zfunc/_cargo";
+        $ rustup completions zsh cargo > ~/.zfunc/_cargo
+
+    The script for `cargo` also completes `cargo +<toolchain>` with the
+    installed toolchains.
+
+    Proxies:
+
+    The completions for `rustup` are computed by rustup itself when you
+    press Tab, so they include the installed toolchains, the components
+    and targets available for them, and the known target triples. The
+    script for `proxies` completes `+<toolchain>` as the first argument of
+    the other tools rustup manages, such as `rustc` and `rustdoc`. Bash,
+    Fish, Zsh and PowerShell are supported:
+
+        $ rustup completions bash proxies >> ~/.bashrc
+        $ rustup completions fish proxies > ~/.config/fish/conf.d/rustup_proxies.fish
+        $ rustup completions zsh proxies > ~/.zfunc/_rustup_proxies";
 
 pub(crate) static OFFICIAL_TOOLCHAIN_ARG_HELP: &str =
     "Toolchain name, such as 'stable', 'nightly', \
@@ -286,6 +411,9 @@ pub(crate) static RESOLVABLE_LOCAL_TOOLCHAIN_ARG_HELP: &str = "Toolchain name, s
 pub(crate) static RESOLVABLE_TOOLCHAIN_ARG_HELP: &str = "Toolchain name, such as 'stable', 'nightly', \
                                        '1.8.0', or a custom toolchain name. For more information see `rustup \
                                        help toolchain`";
+pub(crate) static SHELL_TOOLCHAIN_ARG_HELP: &str = "Toolchain name, such as 'stable', 'nightly', \
+                                       '1.8.0', or a custom toolchain name, or an absolute path, optionally \
+                                       preceded by '+' [default: the active toolchain]";
 pub(crate) static MAYBE_RESOLVABLE_TOOLCHAIN_ARG_HELP: &str = "'none', a toolchain name, such as 'stable', 'nightly', \
                                        '1.8.0', or a custom toolchain name. For more information see `rustup \
                                        help toolchain`";
@@ -1,14 +1,80 @@
+use std::io::Write;
+use std::str::FromStr;
 use std::{path::PathBuf, process::ExitStatus};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use clap::{builder::PossibleValue, ValueEnum};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
 
 use crate::{
-    cli::{common::set_globals, job, self_update},
+    cli::{common, common::set_globals, job, self_update},
     command::run_command_for_dir,
+    config::Cfg,
     process::Process,
-    toolchain::ResolvableLocalToolchainName,
+    toolchain::{DistributableToolchain, ResolvableLocalToolchainName},
 };
 
+/// Whether a proxy installs the component providing its tool when the tool
+/// is missing from the toolchain.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AutoInstallMode {
+    /// Ask first, when there is a terminal to ask on.
+    #[default]
+    Prompt,
+    Always,
+    Never,
+}
+
+impl AutoInstallMode {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Prompt => "prompt",
+            Self::Always => "always",
+            Self::Never => "never",
+        }
+    }
+}
+
+impl ValueEnum for AutoInstallMode {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Prompt, Self::Always, Self::Never]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.as_str()))
+    }
+
+    fn from_str(input: &str, _: bool) -> Result<Self, String> {
+        <Self as FromStr>::from_str(input).map_err(|e| e.to_string())
+    }
+}
+
+impl FromStr for AutoInstallMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode {
+            "prompt" => Ok(Self::Prompt),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(anyhow!(format!(
+                "unknown auto install mode: '{}'; valid modes are {}",
+                mode,
+                Self::value_variants().iter().join(", ")
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for AutoInstallMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[tracing::instrument(level = "trace")]
 pub async fn main(arg0: &str, current_dir: PathBuf, process: &Process) -> Result<ExitStatus> {
     self_update::cleanup_self_updater(process)?;
@@ -32,6 +98,54 @@ pub async fn main(arg0: &str, current_dir: PathBuf, process: &Process) -> Result
         .collect();
 
     let cfg = set_globals(current_dir, false, true, process)?;
-    let cmd = cfg.resolve_local_toolchain(toolchain)?.command(arg0)?;
+    let toolchain = cfg.resolve_local_toolchain(toolchain)?;
+    if let Ok(distributable) = DistributableToolchain::try_from(&toolchain) {
+        install_missing_component(&cfg, &distributable, arg0).await?;
+    }
+    let cmd = toolchain.command(arg0)?;
     run_command_for_dir(cmd, arg0, &cmd_args)
 }
+
+/// Installs the component providing `binary` if the toolchain lacks it, as
+/// the `auto-install-components` setting allows. Otherwise running the
+/// binary fails with a hint to add the component.
+async fn install_missing_component(
+    cfg: &Cfg<'_>,
+    toolchain: &DistributableToolchain<'_>,
+    binary: &str,
+) -> Result<()> {
+    let mode = cfg.get_auto_install_mode()?;
+    if mode == AutoInstallMode::Never {
+        return Ok(());
+    }
+    let component = match toolchain.missing_component_for_bin(binary) {
+        Ok(Some(component)) => component,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            // Leave it to running the binary to report what's wrong.
+            debug!("could not look up the component providing '{binary}': {e:#}");
+            return Ok(());
+        }
+    };
+
+    let name = component.short_name_in_manifest().to_owned();
+    let desc = toolchain.desc();
+    if mode == AutoInstallMode::Prompt {
+        let process = cfg.process;
+        if !process.stdin().is_a_tty(process) || !process.stderr().is_a_tty(process) {
+            return Ok(());
+        }
+        write!(
+            process.stderr().lock(),
+            "'{binary}' is provided by the '{name}' component, which is not installed for the toolchain '{desc}'.\n\
+            Install it now? (Y/n) "
+        )?;
+        let input = common::read_line(process)?;
+        if !matches!(&*input.trim().to_lowercase(), "" | "y" | "yes") {
+            return Ok(());
+        }
+    }
+
+    info!("installing the '{name}' component for the toolchain '{desc}' to provide '{binary}'");
+    toolchain.add_component(component).await
+}
@@ -0,0 +1,146 @@
+//! Release notes of the versions a toolchain is updated to, read from the
+//! copy of `RELEASES.md` that the `rust-docs` component installs.
+
+use crate::toolchain::Toolchain;
+use crate::utils::utils;
+
+/// The release notes online, for toolchains without `rust-docs`.
+pub(crate) const RELEASE_NOTES_URL: &str = "https://doc.rust-lang.org/stable/releases.html";
+
+/// Where the notes may be in a toolchain, relative to its documentation.
+const NOTES_PATHS: &[&str] = &["releases.md", "../RELEASES.md"];
+
+/// A Rust version without any pre-release part, such as `1.79.0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Version(u64, u64, u64);
+
+impl Version {
+    /// Finds the version in text such as `rustc 1.79.0 (129f3b996 2024-06-10)`
+    /// or `1.80.0-nightly`.
+    pub(crate) fn find(text: &str) -> Option<Self> {
+        text.split_whitespace().find_map(|word| {
+            let word = word.split('-').next()?;
+            let mut parts = word.split('.').map(|part| part.parse().ok());
+            let major = parts.next()??;
+            let minor = parts.next()??;
+            let patch = match parts.next() {
+                Some(patch) => patch?,
+                None => 0,
+            };
+            match parts.next() {
+                None => Some(Self(major, minor, patch)),
+                Some(_) => None,
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// The release notes installed in `toolchain`, if any.
+pub(crate) fn installed(toolchain: &Toolchain<'_>) -> Option<String> {
+    let root = toolchain.doc_path("").ok()?;
+    NOTES_PATHS
+        .iter()
+        .map(|path| root.join(path))
+        .find(|path| path.is_file())
+        .and_then(|path| utils::read_file("release notes", &path).ok())
+}
+
+/// The sections of `notes` for the versions after `from` up to `to`, or
+/// for the releases of `to`'s minor version up to `to` without `from`.
+pub(crate) fn sections(notes: &str, from: Option<Version>, to: Version) -> String {
+    let wanted = |version: Version| match from {
+        Some(from) => from < version && version <= to,
+        None => (version.0, version.1) == (to.0, to.1) && version <= to,
+    };
+
+    let lines = notes.lines().collect::<Vec<_>>();
+    let mut sections = String::new();
+    let mut in_section = false;
+    for (i, line) in lines.iter().enumerate() {
+        // Each release starts with a `Version 1.79.0 (2024-06-13)` heading,
+        // underlined with `=`.
+        let underline = lines
+            .get(i + 1)
+            .is_some_and(|next| !next.trim().is_empty() && next.trim().chars().all(|c| c == '='));
+        if underline && line.starts_with("Version ") {
+            in_section = Version::find(line).is_some_and(wanted);
+        }
+        // Anchors for the online notes would only render as blank lines.
+        if in_section && !line.starts_with("<a id=") {
+            sections.push_str(line);
+            sections.push('\n');
+        }
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTES: &str = "\
+% Rust Release Notes
+
+Version 1.80.0 (2024-07-25)
+==========================
+
+- Exclusive ranges in patterns
+
+Version 1.79.1 (2024-07-08)
+===========================
+
+- Fixes
+
+Version 1.79.0 (2024-06-13)
+==========================
+
+Language
+--------
+- Inline `const` expressions
+
+Version 1.78.0 (2024-05-02)
+==========================
+
+- Diagnostic attributes
+";
+
+    #[test]
+    fn finds_versions() {
+        assert_eq!(
+            Version::find("rustc 1.79.0 (129f3b996 2024-06-10)"),
+            Some(Version(1, 79, 0))
+        );
+        assert_eq!(
+            Version::find("1.81.0-nightly (hash 2024-07-01)"),
+            Some(Version(1, 81, 0))
+        );
+        assert_eq!(
+            Version::find("Version 1.80 (2024-07-25)"),
+            Some(Version(1, 80, 0))
+        );
+        assert_eq!(Version::find("(toolchain not installed)"), None);
+    }
+
+    #[test]
+    fn extracts_sections_between_versions() {
+        let sections = sections(NOTES, Some(Version(1, 78, 0)), Version(1, 79, 1));
+        assert!(sections.starts_with("Version 1.79.1 (2024-07-08)\n"));
+        assert!(sections.contains("- Inline `const` expressions\n"));
+        assert!(!sections.contains("1.80.0"));
+        assert!(!sections.contains("1.78.0"));
+    }
+
+    #[test]
+    fn extracts_sections_of_minor_version() {
+        let sections = sections(NOTES, None, Version(1, 79, 0));
+        assert!(sections.starts_with("Version 1.79.0 (2024-06-13)\n"));
+        assert!(!sections.contains("1.79.1"));
+        assert!(!sections.contains("1.78.0"));
+    }
+}
@@ -1,12 +1,18 @@
 use std::env::consts::EXE_SUFFIX;
+use std::ffi::OsStr;
 use std::fmt;
 use std::io::Write;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use anyhow::{anyhow, Error, Result};
-use clap::{builder::PossibleValue, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use anyhow::{anyhow, bail, Context, Error, Result};
+use clap::{
+    builder::{PossibleValue, PossibleValuesParser, StringValueParser, TypedValueParser},
+    Args, CommandFactory, Parser, Subcommand, ValueEnum,
+};
 use clap_complete::Shell;
 use itertools::Itertools;
 use tracing::{info, trace, warn};
@@ -14,18 +20,25 @@ use tracing::{info, trace, warn};
 use crate::{
     cli::{
         common::{self, PackageUpdate},
+        complete, doc_search,
+        doc_server::DocServer,
         errors::CLIError,
         help::*,
+        markdown::md,
+        proxy_mode::AutoInstallMode,
+        release_notes,
         self_update::{self, check_rustup_update, SelfUpdateMode},
         topical_doc,
     },
     command,
     config::{ActiveReason, Cfg},
     dist::{
+        cache_server::CacheServer,
         manifest::{Component, ComponentStatus},
-        PartialToolchainDesc, Profile, TargetTriple,
+        manifestation, PartialToolchainDesc, Profile, RunSupport, TargetTriple, Tier,
     },
     errors::RustupError,
+    history::Action,
     install::{InstallMethod, UpdateStatus},
     process::{
         terminalsource::{self, ColorableTerminal},
@@ -37,6 +50,7 @@ use crate::{
         Toolchain, ToolchainName,
     },
     utils::utils::{self, ExitCode},
+    DUP_TOOLS, TOOLS,
 };
 
 const TOOLCHAIN_OVERRIDE_ERROR: &str =
@@ -87,6 +101,33 @@ struct Rustup {
     subcmd: Option<RustupSubcmd>,
 }
 
+/// Parses the name of a profile. Only the built-in profiles are listed as
+/// possible values: names of those defined in the settings file are checked
+/// once the settings are loaded.
+#[derive(Clone)]
+struct ProfileNameParser;
+
+impl TypedValueParser for ProfileNameParser {
+    type Value = String;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> clap::error::Result<String> {
+        StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            Profile::value_variants()
+                .iter()
+                .filter_map(ValueEnum::to_possible_value),
+        ))
+    }
+}
+
 fn plus_toolchain_value_parser(s: &str) -> clap::error::Result<ResolvableToolchainName> {
     use clap::{error::ErrorKind, Error};
     if let Some(stripped) = s.strip_prefix('+') {
@@ -97,6 +138,12 @@ fn plus_toolchain_value_parser(s: &str) -> clap::error::Result<ResolvableToolcha
     }
 }
 
+fn shell_toolchain_value_parser(s: &str) -> clap::error::Result<ResolvableLocalToolchainName> {
+    use clap::{error::ErrorKind, Error};
+    ResolvableLocalToolchainName::try_from(s.strip_prefix('+').unwrap_or(s))
+        .map_err(|e| Error::raw(ErrorKind::InvalidValue, e))
+}
+
 #[derive(Debug, Subcommand)]
 #[command(name = "rustup", bin_name = "rustup[EXE]")]
 enum RustupSubcmd {
@@ -118,6 +165,17 @@ enum RustupSubcmd {
     #[command(hide = true)]
     DumpTestament,
 
+    /// Print the completions for a command line, one per line
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// The index in `words` of the word to complete [default: the last one]
+        #[arg(long)]
+        cword: Option<usize>,
+
+        #[arg(raw = true)]
+        words: Vec<String>,
+    },
+
     /// Show the active and installed toolchains or profiles
     #[command(after_help = SHOW_HELP)]
     Show {
@@ -150,6 +208,10 @@ enum RustupSubcmd {
         /// Install toolchains that require an emulator. See https://github.com/rust-lang/rustup/wiki/Non-host-toolchains
         #[arg(long)]
         force_non_host: bool,
+
+        /// Show the changes that would be made, and how much would be downloaded, without making them
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Check for updates to Rust toolchains and rustup
@@ -186,6 +248,9 @@ enum RustupSubcmd {
         subcmd: OverrideSubcmd,
     },
 
+    /// Show the changes made to toolchains, the default toolchain and overrides
+    History,
+
     /// Run a command with an environment configured for a given toolchain
     #[command(after_help = RUN_HELP, trailing_var_arg = true)]
     Run {
@@ -200,6 +265,16 @@ enum RustupSubcmd {
         install: bool,
     },
 
+    /// Start a shell that uses the given toolchain
+    #[command(after_help = SHELL_HELP)]
+    Shell {
+        #[arg(
+            help = SHELL_TOOLCHAIN_ARG_HELP,
+            value_parser = shell_toolchain_value_parser,
+        )]
+        toolchain: Option<ResolvableLocalToolchainName>,
+    },
+
     /// Display which binary will be run for a given command
     Which {
         command: String,
@@ -208,6 +283,21 @@ enum RustupSubcmd {
         toolchain: Option<ResolvableToolchainName>,
     },
 
+    /// Print the environment rustup sets for a toolchain, as shell commands
+    #[command(after_help = ENV_HELP)]
+    Env {
+        /// The syntax to print the environment in [default: powershell on Windows, bash elsewhere]
+        #[arg(long)]
+        shell: Option<EnvShell>,
+
+        #[arg(long, help = RESOLVABLE_TOOLCHAIN_ARG_HELP)]
+        toolchain: Option<ResolvableToolchainName>,
+
+        /// Put the toolchain's `bin` directory on PATH ahead of the proxies
+        #[arg(long)]
+        bin: bool,
+    },
+
     /// Open the documentation for the current toolchain
     #[command(
         alias = "docs",
@@ -219,13 +309,36 @@ enum RustupSubcmd {
         path: bool,
 
         #[arg(long, help = OFFICIAL_TOOLCHAIN_ARG_HELP)]
-        toolchain: Option<PartialToolchainDesc>,
+        toolchain: Vec<PartialToolchainDesc>,
 
         #[arg(help = TOPIC_ARG_HELP)]
         topic: Option<String>,
 
         #[command(flatten)]
         page: DocPage,
+
+        /// Search the installed documentation for items matching a query
+        #[arg(long, value_name = "QUERY", conflicts_with_all = ["topic", "page"])]
+        search: Option<String>,
+
+        /// Open the given result of `--search`, counting from 1
+        #[arg(long, value_name = "N", requires = "search")]
+        open: Option<usize>,
+
+        /// Serve the documentation over HTTP instead of opening it
+        #[arg(long, conflicts_with_all = ["path", "search"])]
+        serve: bool,
+
+        /// The address to serve the documentation on [default: 127.0.0.1 on any free port]
+        #[arg(long, requires = "serve")]
+        listen: Option<SocketAddr>,
+    },
+
+    /// Show the release notes of a toolchain's version
+    #[command(after_help = RELEASE_NOTES_HELP)]
+    ReleaseNotes {
+        #[arg(help = OFFICIAL_TOOLCHAIN_ARG_HELP)]
+        toolchain: Option<PartialToolchainDesc>,
     },
 
     /// View the man page for a given command
@@ -237,6 +350,22 @@ enum RustupSubcmd {
         toolchain: Option<PartialToolchainDesc>,
     },
 
+    /// Run a caching proxy of the distribution server
+    #[command(after_help = SERVE_CACHE_HELP)]
+    ServeCache {
+        /// The address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+
+        /// The distribution server to forward requests to [default: the current RUSTUP_DIST_SERVER]
+        #[arg(long)]
+        upstream: Option<String>,
+
+        /// The directory to store cached archives in [default: RUSTUP_HOME/serve-cache]
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+    },
+
     /// Modify the rustup installation
     Self_ {
         #[command(subcommand)]
@@ -325,6 +454,44 @@ enum ToolchainSubcmd {
         /// Path to the directory
         path: PathBuf,
     },
+
+    /// Reinstall a toolchain as it was before a change recorded in its history
+    #[command(after_help = TOOLCHAIN_ROLLBACK_HELP)]
+    Rollback {
+        #[arg(help = OFFICIAL_TOOLCHAIN_ARG_HELP)]
+        toolchain: PartialToolchainDesc,
+
+        /// The history entry to undo, as numbered by `rustup history` [default: the latest change to the toolchain]
+        #[arg(long)]
+        entry: Option<u64>,
+    },
+
+    /// Stop `rustup update` from updating the given toolchains
+    #[command(after_help = TOOLCHAIN_HOLD_HELP)]
+    Hold {
+        #[arg(help = OFFICIAL_TOOLCHAIN_ARG_HELP, required = true, num_args = 1..)]
+        toolchain: Vec<PartialToolchainDesc>,
+    },
+
+    /// Let `rustup update` update the given toolchains again
+    Unhold {
+        #[arg(help = OFFICIAL_TOOLCHAIN_ARG_HELP, required = true, num_args = 1..)]
+        toolchain: Vec<PartialToolchainDesc>,
+    },
+
+    /// Install the components of a profile into an installed toolchain
+    #[command(after_help = TOOLCHAIN_SET_PROFILE_HELP)]
+    SetProfile {
+        #[arg(help = OFFICIAL_TOOLCHAIN_ARG_HELP)]
+        toolchain: PartialToolchainDesc,
+
+        #[arg(value_parser = ProfileNameParser)]
+        profile_name: String,
+
+        /// Also remove the components that are not part of the profile
+        #[arg(long)]
+        prune: bool,
+    },
 }
 
 #[derive(Debug, Default, Args)]
@@ -335,8 +502,8 @@ struct UpdateOpts {
     )]
     toolchain: Vec<PartialToolchainDesc>,
 
-    #[arg(long, value_enum)]
-    profile: Option<Profile>,
+    #[arg(long, value_parser = ProfileNameParser)]
+    profile: Option<String>,
 
     /// Add specific components on installation
     #[arg(short, long, value_delimiter = ',', num_args = 1..)]
@@ -361,6 +528,10 @@ struct UpdateOpts {
     /// Install toolchains that require an emulator. See https://github.com/rust-lang/rustup/wiki/Non-host-toolchains
     #[arg(long)]
     force_non_host: bool,
+
+    /// Show the changes that would be made, and how much would be downloaded, without making them
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[derive(Debug, Default, Args)]
@@ -402,6 +573,10 @@ enum TargetSubcmd {
 
         #[arg(long, help = OFFICIAL_TOOLCHAIN_ARG_HELP)]
         toolchain: Option<PartialToolchainDesc>,
+
+        /// Show the changes that would be made, and how much would be downloaded, without making them
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Remove a target from a Rust toolchain
@@ -414,6 +589,15 @@ enum TargetSubcmd {
         #[arg(long, help = OFFICIAL_TOOLCHAIN_ARG_HELP)]
         toolchain: Option<PartialToolchainDesc>,
     },
+
+    /// Show the parts, support tier and availability of a target
+    Info {
+        /// Target triple to show, such as `aarch64-unknown-linux-gnu`
+        target: String,
+
+        #[arg(long, help = OFFICIAL_TOOLCHAIN_ARG_HELP)]
+        toolchain: Option<PartialToolchainDesc>,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -443,6 +627,10 @@ enum ComponentSubcmd {
 
         #[arg(long)]
         target: Option<String>,
+
+        /// Show the changes that would be made, and how much would be downloaded, without making them
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Remove a component from a Rust toolchain
@@ -500,7 +688,14 @@ enum OverrideSubcmd {
 )]
 enum SelfSubcmd {
     /// Download and install updates to rustup
-    Update,
+    Update {
+        /// Install this version of rustup instead of the latest one
+        #[arg(long)]
+        version: Option<String>,
+    },
+
+    /// Reinstall the version of rustup that the last self-update replaced
+    Rollback,
 
     /// Uninstall rustup
     Uninstall {
@@ -520,8 +715,8 @@ enum SetSubcmd {
 
     /// The default components installed with a toolchain
     Profile {
-        #[arg(value_enum, default_value_t)]
-        profile_name: Profile,
+        #[arg(value_parser = ProfileNameParser, default_value = "default")]
+        profile_name: String,
     },
 
     /// The rustup auto self update mode
@@ -529,6 +724,29 @@ enum SetSubcmd {
         #[arg(value_enum, default_value_t)]
         auto_self_update_mode: SelfUpdateMode,
     },
+
+    /// Whether running a tool installs its missing component
+    AutoInstallComponents {
+        #[arg(value_enum, default_value_t)]
+        auto_install_mode: AutoInstallMode,
+    },
+
+    /// The versions of rustup that self-updates may move to
+    SelfUpdateVersion {
+        /// A version requirement such as `~1.27` or `=1.27.1`; `*` allows any version
+        #[arg(default_value = "*")]
+        version_req: String,
+    },
+
+    /// Whether updates show the release notes of new toolchain versions
+    ReleaseNotes {
+        #[arg(
+            action = clap::ArgAction::Set,
+            value_parser = PossibleValuesParser::new(["enable", "disable"]).map(|s| s == "enable"),
+            default_value = "enable",
+        )]
+        enabled: bool,
+    },
 }
 
 #[tracing::instrument(level = "trace", fields(args = format!("{:?}", process.args_os().collect::<Vec<_>>())))]
@@ -583,6 +801,7 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
 
     match subcmd {
         RustupSubcmd::DumpTestament => common::dump_testament(process),
+        RustupSubcmd::Complete { cword, words } => handle_epipe(complete(cfg, cword, &words)),
         RustupSubcmd::Install { opts } => update(cfg, opts, true).await,
         RustupSubcmd::Uninstall { opts } => toolchain_remove(cfg, opts),
         RustupSubcmd::Show { verbose, subcmd } => handle_epipe(match subcmd {
@@ -599,6 +818,7 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
             no_self_update,
             force,
             force_non_host,
+            dry_run,
         } => {
             update(
                 cfg,
@@ -607,6 +827,7 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
                     no_self_update,
                     force,
                     force_non_host,
+                    dry_run,
                     ..UpdateOpts::default()
                 },
                 false,
@@ -622,6 +843,16 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
                 toolchain_link(cfg, &toolchain, &path).await
             }
             ToolchainSubcmd::Uninstall { opts } => toolchain_remove(cfg, opts),
+            ToolchainSubcmd::Rollback { toolchain, entry } => {
+                toolchain_rollback(cfg, toolchain, entry).await
+            }
+            ToolchainSubcmd::Hold { toolchain } => toolchain_hold(cfg, toolchain, true),
+            ToolchainSubcmd::Unhold { toolchain } => toolchain_hold(cfg, toolchain, false),
+            ToolchainSubcmd::SetProfile {
+                toolchain,
+                profile_name,
+                prune,
+            } => toolchain_set_profile(cfg, toolchain, &profile_name, prune).await,
         },
         RustupSubcmd::Check => check_updates(cfg).await,
         RustupSubcmd::Default { toolchain } => default_(cfg, toolchain).await,
@@ -631,10 +862,15 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
                 installed,
                 quiet,
             } => handle_epipe(target_list(cfg, toolchain, installed, quiet).await),
-            TargetSubcmd::Add { target, toolchain } => target_add(cfg, target, toolchain).await,
+            TargetSubcmd::Add {
+                target,
+                toolchain,
+                dry_run,
+            } => target_add(cfg, target, toolchain, dry_run).await,
             TargetSubcmd::Remove { target, toolchain } => {
                 target_remove(cfg, target, toolchain).await
             }
+            TargetSubcmd::Info { target, toolchain } => target_info(cfg, target, toolchain).await,
         },
         RustupSubcmd::Component { subcmd } => match subcmd {
             ComponentSubcmd::List {
@@ -646,7 +882,8 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
                 component,
                 toolchain,
                 target,
-            } => component_add(cfg, component, toolchain, target).await,
+                dry_run,
+            } => component_add(cfg, component, toolchain, target, dry_run).await,
             ComponentSubcmd::Remove {
                 component,
                 toolchain,
@@ -662,6 +899,7 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
                 override_remove(cfg, path.as_deref(), nonexistent)
             }
         },
+        RustupSubcmd::History => handle_epipe(history(cfg)),
         RustupSubcmd::Run {
             toolchain,
             command,
@@ -669,17 +907,47 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
         } => run(cfg, toolchain, command, install)
             .await
             .map(ExitCode::from),
+        RustupSubcmd::Shell { toolchain } => shell(cfg, toolchain).await.map(ExitCode::from),
         RustupSubcmd::Which { command, toolchain } => which(cfg, &command, toolchain).await,
+        RustupSubcmd::Env {
+            shell,
+            toolchain,
+            bin,
+        } => env(cfg, shell, toolchain, bin),
         RustupSubcmd::Doc {
             path,
             toolchain,
             topic,
             page,
-        } => doc(cfg, path, toolchain, topic.as_deref(), &page).await,
+            search,
+            open,
+            serve,
+            listen,
+        } => {
+            if serve {
+                let listen = listen.unwrap_or(SocketAddr::from(([127, 0, 0, 1], 0)));
+                return doc_serve(cfg, toolchain, topic.as_deref(), &page, listen).await;
+            }
+            let toolchain = match toolchain.len() {
+                0 | 1 => toolchain.into_iter().next(),
+                _ => bail!("only `rustup doc --serve` accepts more than one toolchain"),
+            };
+            match search {
+                Some(query) => doc_search(cfg, path, toolchain, &query, open).await,
+                None => doc(cfg, path, toolchain, topic.as_deref(), &page).await,
+            }
+        }
+        RustupSubcmd::ReleaseNotes { toolchain } => release_notes(cfg, toolchain),
         #[cfg(not(windows))]
         RustupSubcmd::Man { command, toolchain } => man(cfg, &command, toolchain).await,
+        RustupSubcmd::ServeCache {
+            listen,
+            upstream,
+            cache_dir,
+        } => serve_cache(cfg, listen, upstream, cache_dir).await,
         RustupSubcmd::Self_ { subcmd } => match subcmd {
-            SelfSubcmd::Update => self_update::update(cfg).await,
+            SelfSubcmd::Update { version } => self_update::update(cfg, version.as_deref()).await,
+            SelfSubcmd::Rollback => self_update::rollback(process),
             SelfSubcmd::Uninstall { no_prompt } => self_update::uninstall(no_prompt, process),
             SelfSubcmd::UpgradeData => cfg.upgrade_data().map(|_| ExitCode(0)),
         },
@@ -688,11 +956,20 @@ pub async fn main(current_dir: PathBuf, process: &Process) -> Result<utils::Exit
                 .set_default_host_triple(host_triple)
                 .map(|_| utils::ExitCode(0)),
             SetSubcmd::Profile { profile_name } => {
-                cfg.set_profile(profile_name).map(|_| utils::ExitCode(0))
+                cfg.set_profile(&profile_name).map(|_| utils::ExitCode(0))
             }
             SetSubcmd::AutoSelfUpdate {
                 auto_self_update_mode,
             } => set_auto_self_update(cfg, auto_self_update_mode),
+            SetSubcmd::AutoInstallComponents { auto_install_mode } => cfg
+                .set_auto_install_components(auto_install_mode)
+                .map(|_| utils::ExitCode(0)),
+            SetSubcmd::SelfUpdateVersion { version_req } => cfg
+                .set_self_update_version(&version_req)
+                .map(|_| utils::ExitCode(0)),
+            SetSubcmd::ReleaseNotes { enabled } => {
+                cfg.set_release_notes(enabled).map(|_| utils::ExitCode(0))
+            }
         },
         RustupSubcmd::Completions { shell, command } => {
             output_completion_script(shell, command, process)
@@ -758,8 +1035,9 @@ async fn check_updates(cfg: &Cfg<'_>) -> Result<utils::ExitCode> {
         let (name, distributable) = channel;
         let current_version = distributable.show_version()?;
         let dist_version = distributable.show_dist_version().await?;
+        let held = if cfg.is_held(&name)? { " (held)" } else { "" };
         let _ = t.attr(terminalsource::Attr::Bold);
-        write!(t.lock(), "{name} - ")?;
+        write!(t.lock(), "{name}{held} - ")?;
         match (current_version, dist_version) {
             (None, None) => {
                 let _ = t.fg(terminalsource::Color::Red);
@@ -808,13 +1086,16 @@ async fn update(
         && self_update_mode == SelfUpdateMode::Enable
         && !opts.no_self_update;
     let forced = opts.force_non_host;
-    if let Some(p) = opts.profile {
-        cfg.set_profile_override(p);
+    if let Some(p) = &opts.profile {
+        cfg.set_profile_override(p.clone());
     }
     let cfg = &cfg;
-    if cfg.get_profile()? == Profile::Complete {
+    if cfg.get_profile()?.base == Profile::Complete {
         warn!("{}", common::WARN_COMPLETE_PROFILE);
     }
+    if opts.dry_run {
+        return plan_update(cfg, opts, ensure_active_toolchain).await;
+    }
     let names = opts.toolchain;
     if !names.is_empty() {
         for name in names {
@@ -822,7 +1103,13 @@ async fn update(
             if name.has_triple() {
                 let host_arch = TargetTriple::from_host_or_build(cfg.process);
                 let target_triple = name.clone().resolve(&host_arch)?.target;
-                common::warn_if_host_is_incompatible(&name, &host_arch, &target_triple, forced)?;
+                common::warn_if_host_is_incompatible(
+                    &name,
+                    &host_arch,
+                    &target_triple,
+                    forced,
+                    cfg.process,
+                )?;
             }
             let desc = name.resolve(&cfg.get_default_host_triple()?)?;
 
@@ -833,8 +1120,9 @@ async fn update(
             let allow_downgrade = opts.allow_downgrade;
             let profile = cfg.get_profile()?;
             let status = match DistributableToolchain::new(cfg, desc.clone()) {
+                Ok(_) if cfg.skips_held(&desc, force)? => continue,
                 Ok(mut d) => {
-                    d.update_extra(&components, &targets, profile, force, allow_downgrade)
+                    d.update_extra(&components, &targets, &profile, force, allow_downgrade)
                         .await?
                 }
                 Err(RustupError::ToolchainNotInstalled(_)) => {
@@ -843,7 +1131,7 @@ async fn update(
                         &desc,
                         &components,
                         &targets,
-                        profile,
+                        &profile,
                         force,
                     )
                     .await?
@@ -863,7 +1151,7 @@ async fn update(
             }
         }
         if self_update {
-            exit_code &= common::self_update(|| Ok(()), cfg.process).await?;
+            exit_code &= common::self_update(|| Ok(()), cfg).await?;
         }
     } else if ensure_active_toolchain {
         let (toolchain, reason) = cfg.find_or_install_active_toolchain(true).await?;
@@ -903,6 +1191,33 @@ async fn run(
     command::run_command_for_dir(cmd, &command[0], &command[1..])
 }
 
+async fn shell(
+    cfg: &Cfg<'_>,
+    toolchain: Option<ResolvableLocalToolchainName>,
+) -> Result<ExitStatus> {
+    let toolchain = match toolchain {
+        Some(toolchain) => {
+            let toolchain = toolchain.resolve(&cfg.get_default_host_triple()?)?;
+            Toolchain::from_local(toolchain, true, cfg).await?
+        }
+        None => cfg.find_or_install_active_toolchain(false).await?.0,
+    };
+    let name = toolchain.name().to_string();
+
+    let shell = self_update::user_shell(cfg.process);
+    let mut cmd = std::process::Command::new(&shell);
+    toolchain.set_env(&mut cmd);
+    // The prompt is left alone, as shells reset it from their rc files;
+    // users can show this in theirs instead.
+    cmd.env("RUSTUP_SHELL_TOOLCHAIN", &name);
+
+    info!(
+        "starting {} with the toolchain '{name}'; exit it to return",
+        shell.to_string_lossy()
+    );
+    command::run_command_for_dir(cmd, &shell.to_string_lossy(), &[] as &[&str])
+}
+
 async fn which(
     cfg: &Cfg<'_>,
     binary: &str,
@@ -916,6 +1231,67 @@ async fn which(
     Ok(utils::ExitCode(0))
 }
 
+fn env(
+    cfg: &Cfg<'_>,
+    shell: Option<EnvShell>,
+    toolchain: Option<ResolvableToolchainName>,
+    bin: bool,
+) -> Result<utils::ExitCode> {
+    let toolchain = cfg.resolve_toolchain(toolchain)?;
+    let shell = shell.unwrap_or(match cfg!(windows) {
+        true => EnvShell::Powershell,
+        false => EnvShell::Bash,
+    });
+    let vars = toolchain.env(bin);
+
+    let stdout = cfg.process.stdout();
+    let mut stdout = stdout.lock();
+    if shell == EnvShell::Json {
+        writeln!(stdout, "{{")?;
+        for (i, (name, value)) in vars.iter().enumerate() {
+            let comma = if i + 1 < vars.len() { "," } else { "" };
+            let value = json_string(&value.to_string_lossy());
+            writeln!(stdout, "  {}: {value}{comma}", json_string(name))?;
+        }
+        writeln!(stdout, "}}")?;
+        return Ok(utils::ExitCode(0));
+    }
+
+    for (name, value) in &vars {
+        let value = value.to_string_lossy();
+        match shell {
+            EnvShell::Bash | EnvShell::Zsh => {
+                writeln!(stdout, "export {name}='{}'", value.replace('\'', r"'\''"))?
+            }
+            EnvShell::Fish => writeln!(
+                stdout,
+                "set -gx {name} '{}'",
+                value.replace('\\', r"\\").replace('\'', r"\'")
+            )?,
+            EnvShell::Powershell => {
+                writeln!(stdout, "$Env:{name} = '{}'", value.replace('\'', "''"))?
+            }
+            EnvShell::Json => unreachable!(),
+        }
+    }
+    Ok(utils::ExitCode(0))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 #[tracing::instrument(level = "trace", skip_all)]
 fn show(cfg: &Cfg<'_>, verbose: bool) -> Result<utils::ExitCode> {
     common::warn_if_host_is_emulated(cfg.process);
@@ -1114,10 +1490,128 @@ async fn target_list(
     )
 }
 
+async fn target_info(
+    cfg: &Cfg<'_>,
+    target: String,
+    toolchain: Option<PartialToolchainDesc>,
+) -> Result<utils::ExitCode> {
+    let target = TargetTriple::new(target);
+    let parsed = target.parse()?;
+    let distributable = DistributableToolchain::from_partial(toolchain, cfg)?;
+    let manifest = distributable.get_manifest()?;
+    let components = distributable
+        .components()?
+        .into_iter()
+        .filter(|c| c.component.target.as_ref() == Some(&target))
+        .collect::<Vec<_>>();
+    let rust_std = components
+        .iter()
+        .find(|c| c.component.short_name_in_manifest() == "rust-std");
+    let host = cfg.get_default_host_triple()?;
+
+    let stdout = cfg.process.stdout();
+    let mut t = stdout.lock();
+    writeln!(t, "target: {target}")?;
+    writeln!(t, "  arch: {}", parsed.arch)?;
+    if let Some(vendor) = &parsed.vendor {
+        writeln!(t, "  vendor: {vendor}")?;
+    }
+    writeln!(t, "  os: {}", parsed.os)?;
+    if let Some(env) = &parsed.env {
+        writeln!(t, "  env: {env}")?;
+    }
+    if let Some(abi) = &parsed.abi {
+        writeln!(t, "  abi: {abi}")?;
+    }
+    let distributed = rust_std.is_some_and(|c| c.available);
+    writeln!(t, "tier: {}", Tier::of(&target, distributed))?;
+    writeln!(t, "toolchain: {}", distributable.desc())?;
+    match rust_std {
+        Some(c) if c.available && c.installed => writeln!(t, "rust-std: installed")?,
+        Some(c) if c.available => writeln!(t, "rust-std: available")?,
+        Some(_) => writeln!(t, "rust-std: unavailable in this release")?,
+        None => writeln!(
+            t,
+            "rust-std: not distributed; building for this target requires the rust-src component and `cargo build -Z build-std`"
+        )?,
+    }
+    let names = components
+        .iter()
+        .filter(|c| c.available)
+        .map(|c| match c.installed {
+            true => format!("{} (installed)", c.component.short_name(&manifest)),
+            false => c.component.short_name(&manifest),
+        })
+        .collect::<Vec<_>>();
+    if !names.is_empty() {
+        writeln!(t, "components: {}", names.join(", "))?;
+    }
+    match host.run_support(&target, cfg.process)? {
+        RunSupport::Native => writeln!(t, "runs on host ({host}): yes")?,
+        RunSupport::Emulated { interpreter } => writeln!(
+            t,
+            "runs on host ({host}): under emulation by {}",
+            interpreter.display()
+        )?,
+        RunSupport::Unsupported { reason } => writeln!(t, "runs on host ({host}): no, {reason}")?,
+    }
+
+    Ok(utils::ExitCode(0))
+}
+
+/// Shows the changes `update` would make with the same options.
+async fn plan_update(
+    cfg: &Cfg<'_>,
+    opts: UpdateOpts,
+    ensure_active_toolchain: bool,
+) -> Result<utils::ExitCode> {
+    let profile = cfg.get_profile()?;
+    let components = opts.component.iter().map(|s| &**s).collect::<Vec<_>>();
+    let targets = opts.target.iter().map(|s| &**s).collect::<Vec<_>>();
+
+    let toolchains = if !opts.toolchain.is_empty() {
+        let host = cfg.get_default_host_triple()?;
+        opts.toolchain
+            .into_iter()
+            .map(|name| name.resolve(&host))
+            .collect::<Result<Vec<_>>>()?
+    } else if ensure_active_toolchain {
+        bail!("`--dry-run` needs the names of the toolchains to install");
+    } else {
+        let channels = cfg.list_channels()?;
+        if channels.is_empty() {
+            info!("no updatable toolchains installed");
+        }
+        channels.into_iter().map(|(desc, _)| desc).collect()
+    };
+
+    for desc in &toolchains {
+        // Like the update itself, leave held toolchains out unless forced
+        let installed = DistributableToolchain::new(cfg, desc.clone()).is_ok();
+        if installed && cfg.skips_held(desc, opts.force)? {
+            continue;
+        }
+        let plan = DistributableToolchain::plan_update(
+            cfg,
+            desc,
+            &components,
+            &targets,
+            &profile,
+            opts.force,
+            opts.allow_downgrade,
+        )
+        .await?;
+        common::show_update_plan(cfg.process, desc, plan.as_ref())?;
+    }
+
+    Ok(utils::ExitCode(0))
+}
+
 async fn target_add(
     cfg: &Cfg<'_>,
     mut targets: Vec<String>,
     toolchain: Option<PartialToolchainDesc>,
+    dry_run: bool,
 ) -> Result<utils::ExitCode> {
     // XXX: long term move this error to cli ? the normal .into doesn't work
     // because Result here is the wrong sort and expression type ascription
@@ -1151,12 +1645,23 @@ async fn target_add(
         }
     }
 
-    for target in targets {
-        let new_component = Component::new(
+    let new_components = targets.into_iter().map(|target| {
+        Component::new(
             "rust-std".to_string(),
             Some(TargetTriple::new(target)),
             false,
-        );
+        )
+    });
+
+    if dry_run {
+        let plan = distributable
+            .plan_add_components(new_components.collect())
+            .await?;
+        common::show_update_plan(cfg.process, distributable.desc(), Some(&plan))?;
+        return Ok(utils::ExitCode(0));
+    }
+
+    for new_component in new_components {
         distributable.add_component(new_component).await?;
     }
 
@@ -1219,10 +1724,21 @@ async fn component_add(
     components: Vec<String>,
     toolchain: Option<PartialToolchainDesc>,
     target: Option<String>,
+    dry_run: bool,
 ) -> Result<utils::ExitCode> {
     let distributable = DistributableToolchain::from_partial(toolchain, cfg)?;
     let target = get_target(target, &distributable);
 
+    if dry_run {
+        let new_components = components
+            .iter()
+            .map(|c| Component::try_new(c, &distributable, target.as_ref()))
+            .collect::<Result<_>>()?;
+        let plan = distributable.plan_add_components(new_components).await?;
+        common::show_update_plan(cfg.process, distributable.desc(), Some(&plan))?;
+        return Ok(utils::ExitCode(0));
+    }
+
     for component in &components {
         let new_component = Component::try_new(component, &distributable, target.as_ref())?;
         distributable.add_component(new_component).await?;
@@ -1313,6 +1829,100 @@ fn toolchain_remove(cfg: &mut Cfg<'_>, opts: UninstallOpts) -> Result<utils::Exi
     Ok(utils::ExitCode(0))
 }
 
+async fn toolchain_rollback(
+    cfg: &Cfg<'_>,
+    toolchain: PartialToolchainDesc,
+    entry: Option<u64>,
+) -> Result<utils::ExitCode> {
+    let desc = toolchain.resolve(&cfg.get_default_host_triple()?)?;
+    let name = desc.to_string();
+    let entries = cfg.history.entries()?;
+    let entry = match entry {
+        Some(id) => {
+            let Some(entry) = entries.iter().find(|e| e.id == id) else {
+                bail!("there is no history entry {id}");
+            };
+            if entry.toolchain.as_ref() != Some(&name) || !entry.has_snapshot() {
+                bail!("history entry {id} doesn't record an earlier state of '{name}'");
+            }
+            entry
+        }
+        None => {
+            // Skip the entries that earlier rollbacks undid, so that rolling
+            // back again goes further back instead of undoing the last one.
+            let mut before = u64::MAX;
+            entries
+                .iter()
+                .rev()
+                .filter(|e| e.toolchain.as_ref() == Some(&name))
+                .find(|e| {
+                    if e.id >= before {
+                        return false;
+                    }
+                    if e.action == Action::Rollback {
+                        before = e.restored.unwrap_or(e.id);
+                        return false;
+                    }
+                    e.has_snapshot()
+                })
+                .ok_or_else(|| anyhow!("no earlier state of '{name}' is recorded in its history"))?
+        }
+    };
+    let date = entry.previous_date.as_deref().unwrap_or_default();
+
+    match DistributableToolchain::rollback(cfg, &desc, entry).await? {
+        manifestation::UpdateStatus::Changed => {
+            info!("rolled back '{name}' to its {date} release")
+        }
+        manifestation::UpdateStatus::Unchanged => {
+            info!("'{name}' is already at its {date} release")
+        }
+    }
+    Ok(utils::ExitCode(0))
+}
+
+fn toolchain_hold(
+    cfg: &Cfg<'_>,
+    toolchains: Vec<PartialToolchainDesc>,
+    hold: bool,
+) -> Result<utils::ExitCode> {
+    let host = cfg.get_default_host_triple()?;
+    for toolchain in toolchains {
+        let desc = toolchain.resolve(&host)?;
+        if hold {
+            if !cfg.hold_toolchain(&desc)? {
+                info!("toolchain '{desc}' is already held");
+            }
+        } else if !cfg.unhold_toolchain(&desc)? {
+            info!("toolchain '{desc}' is not held");
+        }
+    }
+    Ok(utils::ExitCode(0))
+}
+
+async fn toolchain_set_profile(
+    cfg: &Cfg<'_>,
+    toolchain: PartialToolchainDesc,
+    profile_name: &str,
+    prune: bool,
+) -> Result<utils::ExitCode> {
+    let profile = cfg.resolve_profile(profile_name)?;
+    if profile.base == Profile::Complete {
+        warn!("{}", common::WARN_COMPLETE_PROFILE);
+    }
+    let distributable = DistributableToolchain::from_partial(Some(toolchain), cfg)?;
+    let desc = distributable.desc();
+    match distributable.set_profile(&profile, prune).await? {
+        manifestation::UpdateStatus::Changed => {
+            info!("toolchain '{desc}' now has the components of profile '{profile}'")
+        }
+        manifestation::UpdateStatus::Unchanged => {
+            info!("toolchain '{desc}' already has the components of profile '{profile}'")
+        }
+    }
+    Ok(utils::ExitCode(0))
+}
+
 async fn override_add(
     cfg: &Cfg<'_>,
     toolchain: ResolvableToolchainName,
@@ -1324,10 +1934,16 @@ async fn override_add(
         Err(e @ RustupError::ToolchainNotInstalled(_)) => match &toolchain_name {
             ToolchainName::Custom(_) => Err(e)?,
             ToolchainName::Official(desc) => {
-                let status =
-                    DistributableToolchain::install(cfg, desc, &[], &[], cfg.get_profile()?, false)
-                        .await?
-                        .0;
+                let status = DistributableToolchain::install(
+                    cfg,
+                    desc,
+                    &[],
+                    &[],
+                    &cfg.get_profile()?,
+                    false,
+                )
+                .await?
+                .0;
                 writeln!(cfg.process.stdout().lock())?;
                 common::show_channel_update(
                     cfg,
@@ -1369,10 +1985,7 @@ fn override_remove(
     };
 
     for p in &paths {
-        if cfg
-            .settings_file
-            .with_mut(|s| Ok(s.remove_override(p, cfg.notify_handler.as_ref())))?
-        {
+        if cfg.remove_override(p)? {
             info!("override toolchain for '{}' removed", p.display());
         } else {
             info!("no override toolchain for '{}'", p.display());
@@ -1387,6 +2000,47 @@ fn override_remove(
     Ok(utils::ExitCode(0))
 }
 
+fn history(cfg: &Cfg<'_>) -> Result<utils::ExitCode> {
+    let entries = cfg.history.entries()?;
+    if entries.is_empty() {
+        info!("no changes have been recorded yet");
+        return Ok(utils::ExitCode(0));
+    }
+
+    let stdout = cfg.process.stdout();
+    let mut stdout = stdout.lock();
+    for entry in &entries {
+        let toolchain = entry.toolchain.as_deref();
+        let previous = entry.previous_toolchain.as_deref().unwrap_or("none");
+        let description = match entry.action {
+            Action::Default => format!("{} (was {previous})", toolchain.unwrap_or("none")),
+            Action::Override => format!(
+                "{} for '{}' (was {previous})",
+                toolchain.unwrap_or("unset"),
+                entry.path.as_deref().unwrap_or(Path::new("")).display(),
+            ),
+            Action::Install | Action::Update | Action::Uninstall | Action::Rollback => {
+                match &entry.previous_date {
+                    Some(date) => format!(
+                        "{} (was {date}, with {})",
+                        toolchain.unwrap_or_default(),
+                        entry.previous_components.join(", "),
+                    ),
+                    None => toolchain.unwrap_or_default().to_owned(),
+                }
+            }
+        };
+        writeln!(
+            stdout,
+            "{:>4}  {}  {:<9}  {description}",
+            entry.id,
+            entry.time(),
+            entry.action,
+        )?;
+    }
+    Ok(utils::ExitCode(0))
+}
+
 macro_rules! docs_data {
     (
         $(
@@ -1440,16 +2094,8 @@ docs_data![
     (embedded_book, "The Embedded Rust Book", "embedded-book/index.html"),
 ];
 
-async fn doc(
-    cfg: &Cfg<'_>,
-    path_only: bool,
-    toolchain: Option<PartialToolchainDesc>,
-    mut topic: Option<&str>,
-    doc_page: &DocPage,
-) -> Result<utils::ExitCode> {
-    let toolchain = cfg.toolchain_from_partial(toolchain)?;
-
-    if let Ok(distributable) = DistributableToolchain::try_from(&toolchain) {
+fn ensure_docs_installed(toolchain: &Toolchain<'_>) -> Result<()> {
+    if let Ok(distributable) = DistributableToolchain::try_from(toolchain) {
         if let [_] = distributable
             .components()?
             .into_iter()
@@ -1472,20 +2118,86 @@ async fn doc(
                 "unable to view documentation which is not installed"
             ));
         }
-    };
+    }
+    Ok(())
+}
 
-    let topical_path: PathBuf;
+async fn doc_search(
+    cfg: &Cfg<'_>,
+    path_only: bool,
+    toolchain: Option<PartialToolchainDesc>,
+    query: &str,
+    open: Option<usize>,
+) -> Result<utils::ExitCode> {
+    const MAX_RESULTS: usize = 20;
 
-    let doc_url = if let Some(topic) = topic {
-        topical_path = topical_doc::local_path(&toolchain.doc_path("").unwrap(), topic)?;
-        topical_path.to_str().unwrap()
-    } else {
-        topic = doc_page.name();
-        doc_page.path().unwrap_or("index.html")
-    };
+    let toolchain = cfg.toolchain_from_partial(toolchain)?;
+    ensure_docs_installed(&toolchain)?;
+
+    let root = toolchain.doc_path("")?;
+    let items = doc_search::search(&root, query)?;
+    if items.is_empty() {
+        bail!("no documentation matches '{query}'");
+    }
+
+    if let Some(n) = open {
+        let Some(item) = n.checked_sub(1).and_then(|i| items.get(i)) else {
+            bail!(
+                "there is no result {n} for '{query}', there are {}",
+                items.len()
+            );
+        };
+        let doc_url = item.file.to_str().unwrap();
+        if path_only {
+            let doc_path = toolchain.doc_path(doc_url)?;
+            writeln!(cfg.process.stdout().lock(), "{}", doc_path.display())?;
+        } else {
+            writeln!(
+                cfg.process.stderr().lock(),
+                "Opening docs named `{}` in your browser",
+                item.path
+            )?;
+            toolchain.open_docs(doc_url)?;
+        }
+        return Ok(utils::ExitCode(0));
+    }
+
+    let shown = &items[..items.len().min(MAX_RESULTS)];
+    let width = shown.iter().map(|i| i.path.len()).max().unwrap_or_default();
+    let stdout = cfg.process.stdout();
+    let mut stdout = stdout.lock();
+    for (n, item) in shown.iter().enumerate() {
+        let line = format!("{:>2}. {:<9} {:<width$}", n + 1, item.kind, item.path);
+        match doc_search::description(&root, item) {
+            Some(description) => writeln!(stdout, "{line}  {description}")?,
+            None => writeln!(stdout, "{}", line.trim_end())?,
+        }
+    }
+    if items.len() > shown.len() {
+        info!(
+            "{} more results, refine the query to see them",
+            items.len() - shown.len()
+        );
+    }
+    info!("to open a result, run `rustup doc --search '{query}' --open <N>`");
+    Ok(utils::ExitCode(0))
+}
+
+async fn doc(
+    cfg: &Cfg<'_>,
+    path_only: bool,
+    toolchain: Option<PartialToolchainDesc>,
+    topic: Option<&str>,
+    doc_page: &DocPage,
+) -> Result<utils::ExitCode> {
+    let toolchain = cfg.toolchain_from_partial(toolchain)?;
+    ensure_docs_installed(&toolchain)?;
+
+    let doc_url = doc_url(&toolchain, topic, doc_page)?;
+    let topic = topic.or(doc_page.name());
 
     if path_only {
-        let doc_path = toolchain.doc_path(doc_url)?;
+        let doc_path = toolchain.doc_path(&doc_url)?;
         writeln!(cfg.process.stdout().lock(), "{}", doc_path.display())?;
         Ok(utils::ExitCode(0))
     } else {
@@ -1497,11 +2209,110 @@ async fn doc(
         } else {
             writeln!(cfg.process.stderr().lock(), "Opening docs in your browser")?;
         }
-        toolchain.open_docs(doc_url)?;
+        toolchain.open_docs(&doc_url)?;
         Ok(utils::ExitCode(0))
     }
 }
 
+/// The page for `topic` or `doc_page`, relative to the root of the
+/// documentation and with `/` separators.
+fn doc_url(toolchain: &Toolchain<'_>, topic: Option<&str>, doc_page: &DocPage) -> Result<String> {
+    let Some(topic) = topic else {
+        return Ok(doc_page.path().unwrap_or("index.html").to_owned());
+    };
+    let root = toolchain.doc_path("")?;
+    let path = topical_doc::local_path(&root, topic)?;
+    // Pages found by searching a directory are given with the root.
+    let path = path.strip_prefix(&root).unwrap_or(&path);
+    Ok(path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .join("/"))
+}
+
+async fn doc_serve(
+    cfg: &Cfg<'_>,
+    toolchains: Vec<PartialToolchainDesc>,
+    topic: Option<&str>,
+    doc_page: &DocPage,
+    listen: SocketAddr,
+) -> Result<utils::ExitCode> {
+    let toolchains = match toolchains.is_empty() {
+        true => vec![cfg.toolchain_from_partial(None)?],
+        false => toolchains
+            .into_iter()
+            .map(|t| cfg.toolchain_from_partial(Some(t)))
+            .collect::<Result<_>>()?,
+    };
+
+    let mut roots = Vec::new();
+    let mut urls = Vec::new();
+    for toolchain in &toolchains {
+        ensure_docs_installed(toolchain)?;
+        // Path toolchains are served under the name of their directory.
+        let prefix = match toolchain.name() {
+            LocalToolchainName::Named(name) => name.to_string(),
+            LocalToolchainName::Path(path) => Path::new(&path.to_string())
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "toolchain".to_owned()),
+        };
+        if roots.iter().any(|(p, _)| *p == prefix) {
+            continue;
+        }
+        urls.push(format!("{prefix}/{}", doc_url(toolchain, topic, doc_page)?));
+        roots.push((prefix, toolchain.doc_path("")?));
+    }
+
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("failed to listen on {listen}"))?;
+    let addr = listener.local_addr()?;
+    {
+        let stdout = cfg.process.stdout();
+        let mut stdout = stdout.lock();
+        for url in urls {
+            writeln!(stdout, "http://{addr}/{url}")?;
+        }
+    }
+    info!("serving the documentation at http://{addr}/ until interrupted");
+    Arc::new(DocServer::new(roots)).serve(listener).await?;
+    Ok(utils::ExitCode(0))
+}
+
+fn release_notes(
+    cfg: &Cfg<'_>,
+    toolchain: Option<PartialToolchainDesc>,
+) -> Result<utils::ExitCode> {
+    let toolchain = cfg.toolchain_from_partial(toolchain)?;
+    let Some(notes) = release_notes::installed(&toolchain) else {
+        if let Ok(distributable) = DistributableToolchain::try_from(&toolchain) {
+            info!(
+                "To install, try `rustup component add --toolchain {} rust-docs`",
+                distributable.desc()
+            );
+        }
+        bail!(
+            "the release notes of toolchain '{}' are not installed",
+            toolchain.name()
+        );
+    };
+
+    let rustc_version = toolchain.rustc_version();
+    let Some(version) = release_notes::Version::find(&rustc_version) else {
+        bail!(
+            "could not determine the version of toolchain '{}'",
+            toolchain.name()
+        );
+    };
+    let sections = release_notes::sections(&notes, None, version);
+    if sections.is_empty() {
+        bail!("the release notes have no entry for version {version}");
+    }
+    md(&mut cfg.process.stdout().terminal(cfg.process), sections);
+    Ok(utils::ExitCode(0))
+}
+
 #[cfg(not(windows))]
 async fn man(
     cfg: &Cfg<'_>,
@@ -1525,6 +2336,26 @@ async fn man(
     Ok(utils::ExitCode(0))
 }
 
+async fn serve_cache(
+    cfg: &Cfg<'_>,
+    listen: SocketAddr,
+    upstream: Option<String>,
+    cache_dir: Option<PathBuf>,
+) -> Result<utils::ExitCode> {
+    let upstream = upstream.unwrap_or_else(|| cfg.tmp_cx.dist_server.clone());
+    let cache_dir = cache_dir.unwrap_or_else(|| cfg.rustup_dir.join("serve-cache"));
+    let server = CacheServer::new(&upstream, cache_dir, cfg.process.clone())?;
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("failed to listen on {listen}"))?;
+    info!(
+        "serving a cache of '{upstream}' at http://{}",
+        listener.local_addr()?
+    );
+    Arc::new(server).serve(listener).await?;
+    Ok(utils::ExitCode(0))
+}
+
 fn set_auto_self_update(
     cfg: &mut Cfg<'_>,
     auto_self_update_mode: SelfUpdateMode,
@@ -1542,21 +2373,55 @@ fn set_auto_self_update(
     Ok(utils::ExitCode(0))
 }
 
+/// The syntaxes `rustup env` prints the environment in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum EnvShell {
+    Bash,
+    Fish,
+    Zsh,
+    Powershell,
+    Json,
+}
+
+impl clap::ValueEnum for EnvShell {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Self::Bash,
+            Self::Fish,
+            Self::Zsh,
+            Self::Powershell,
+            Self::Json,
+        ]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::Bash => PossibleValue::new("bash"),
+            Self::Fish => PossibleValue::new("fish"),
+            Self::Zsh => PossibleValue::new("zsh"),
+            Self::Powershell => PossibleValue::new("powershell"),
+            Self::Json => PossibleValue::new("json"),
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub(crate) enum CompletionCommand {
     Rustup,
     Cargo,
+    Proxies,
 }
 
 impl clap::ValueEnum for CompletionCommand {
     fn value_variants<'a>() -> &'a [Self] {
-        &[Self::Rustup, Self::Cargo]
+        &[Self::Rustup, Self::Cargo, Self::Proxies]
     }
 
     fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
         Some(match self {
             CompletionCommand::Rustup => PossibleValue::new("rustup"),
             CompletionCommand::Cargo => PossibleValue::new("cargo"),
+            CompletionCommand::Proxies => PossibleValue::new("proxies"),
         })
     }
 }
@@ -1570,35 +2435,55 @@ impl fmt::Display for CompletionCommand {
     }
 }
 
+fn complete(cfg: &Cfg<'_>, cword: Option<usize>, words: &[String]) -> Result<utils::ExitCode> {
+    let cword = cword.unwrap_or(words.len().saturating_sub(1));
+    let candidates = complete::candidates(cfg, Rustup::command(), words, cword);
+    let stdout = cfg.process.stdout();
+    let mut stdout = stdout.lock();
+    for candidate in candidates {
+        writeln!(stdout, "{candidate}")?;
+    }
+    Ok(utils::ExitCode(0))
+}
+
 fn output_completion_script(
     shell: Shell,
     command: CompletionCommand,
     process: &Process,
 ) -> Result<utils::ExitCode> {
+    let unsupported = || {
+        anyhow!(
+            "{} does not currently support completions for {}",
+            command,
+            shell
+        )
+    };
+
     match command {
-        CompletionCommand::Rustup => {
-            clap_complete::generate(
+        CompletionCommand::Rustup => match complete::script(shell, "_rustup", &["rustup"]) {
+            Some(script) => write!(process.stdout().lock(), "{script}")?,
+            None => clap_complete::generate(
                 shell,
                 &mut Rustup::command(),
                 "rustup",
                 &mut process.stdout().lock(),
-            );
-        }
+            ),
+        },
         CompletionCommand::Cargo => {
             if let Shell::Zsh = shell {
                 writeln!(process.stdout().lock(), "#compdef cargo")?;
             }
 
-            let script = match shell {
-                Shell::Bash => "/etc/bash_completion.d/cargo",
-                Shell::Zsh => "/share/zsh/site-functions/_cargo",
-                _ => {
-                    return Err(anyhow!(
-                        "{} does not currently support completions for {}",
-                        command,
-                        shell
-                    ))
-                }
+            let (script, plus_toolchain) = match shell {
+                Shell::Bash => (
+                    "/etc/bash_completion.d/cargo",
+                    complete::BASH_CARGO_PLUS_TOOLCHAIN,
+                ),
+                Shell::Zsh => (
+                    "/share/zsh/site-functions/_cargo",
+                    complete::ZSH_CARGO_PLUS_TOOLCHAIN,
+                ),
+                _ => return Err(unsupported()),
             };
 
             writeln!(
@@ -1607,6 +2492,19 @@ fn output_completion_script(
                     \tsource \"$(rustc --print sysroot)\"{script}\n\
                  fi",
             )?;
+            write!(process.stdout().lock(), "{plus_toolchain}")?;
+        }
+        CompletionCommand::Proxies => {
+            // cargo's own completions are extended by `rustup completions <shell> cargo`.
+            let proxies = TOOLS
+                .iter()
+                .chain(DUP_TOOLS)
+                .copied()
+                .filter(|&tool| tool != "cargo")
+                .collect::<Vec<_>>();
+            let script =
+                complete::script(shell, "_rustup_proxies", &proxies).ok_or_else(unsupported)?;
+            write!(process.stdout().lock(), "{script}")?;
         }
     }
 
@@ -39,12 +39,13 @@ use std::process::Command;
 use std::str::FromStr;
 use std::{env, fmt};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use cfg_if::cfg_if;
 use clap::builder::PossibleValue;
 use clap::ValueEnum;
 use itertools::Itertools;
 use same_file::Handle;
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, trace, warn};
 
@@ -67,8 +68,12 @@ use crate::{
     DUP_TOOLS, TOOLS,
 };
 
+mod signature;
+
 #[cfg(unix)]
 mod shell;
+#[cfg(unix)]
+pub(crate) use shell::user_shell;
 
 #[cfg(unix)]
 mod unix;
@@ -86,7 +91,7 @@ use windows::{delete_rustup_and_cargo_home, do_add_to_path, do_remove_from_path}
 #[cfg(all(windows, feature = "test"))]
 pub use windows::{get_path, RegistryGuard, RegistryValueId, USER_PATH};
 #[cfg(windows)]
-pub(crate) use windows::{run_update, self_replace};
+pub(crate) use windows::{run_update, self_replace, user_shell};
 
 pub(crate) struct InstallOpts<'a> {
     pub default_host_triple: Option<String>,
@@ -96,6 +101,49 @@ pub(crate) struct InstallOpts<'a> {
     pub no_update_toolchain: bool,
     pub components: &'a [&'a str],
     pub targets: &'a [&'a str],
+    /// Toolchains to install besides the default one
+    pub toolchains: &'a [&'a str],
+}
+
+/// The installation options of `rustup-init --config`, read from a TOML
+/// answer file such as
+///
+/// ```toml
+/// default-toolchain = "stable"
+/// profile = "minimal"
+/// components = ["rustfmt", "clippy"]
+/// targets = ["wasm32-unknown-unknown"]
+/// toolchains = ["nightly"]
+/// modify-path = false
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct InstallConfig {
+    pub default_host: Option<String>,
+    pub default_toolchain: Option<String>,
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub components: Vec<String>,
+    #[serde(default)]
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub toolchains: Vec<String>,
+    #[serde(default = "default_true")]
+    pub modify_path: bool,
+    #[serde(default = "default_true")]
+    pub update_default_toolchain: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl InstallConfig {
+    pub(crate) fn from_file(path: &Path) -> Result<Self> {
+        let contents = utils::read_file("installation options", path)?;
+        toml::from_str(&contents)
+            .with_context(|| format!("invalid installation options in '{}'", path.display()))
+    }
 }
 
 impl<'a> InstallOpts<'a> {
@@ -108,9 +156,10 @@ impl<'a> InstallOpts<'a> {
             no_update_toolchain,
             components,
             targets,
+            toolchains: _,
         } = self;
 
-        cfg.set_profile(profile)?;
+        cfg.set_profile(profile.as_str())?;
 
         if let Some(default_host_triple) = &default_host_triple {
             // Set host triple now as it will affect resolution of toolchain_str
@@ -238,6 +287,12 @@ impl<'a> InstallOpts<'a> {
         };
         let resolved = partial_channel.resolve(&host_triple)?;
         trace!("Successfully resolved installation toolchain as: {resolved}");
+        for name in self.toolchains {
+            let resolved = name
+                .parse::<PartialToolchainDesc>()?
+                .resolve(&host_triple)?;
+            trace!("Successfully resolved additional toolchain as: {resolved}");
+        }
         Ok(())
     }
 }
@@ -384,6 +439,9 @@ the corresponding `env` file under {cargo_home}.
 This is usually done by running one of the following (note the leading DOT):
     . "{cargo_home}/env"            # For sh/bash/zsh/ash/dash/pdksh
     source "{cargo_home}/env.fish"  # For fish
+    source "{cargo_home}/env.xsh"   # For xonsh
+
+For nushell and elvish, load `env.nu` or `env.elv` from the same place.
 "#
     };
 }
@@ -686,7 +744,7 @@ fn pre_install_msg(no_modify_path: bool, process: &Process) -> Result<String> {
 }
 
 fn current_install_opts(opts: &InstallOpts<'_>, process: &Process) -> String {
-    format!(
+    let mut current = format!(
         r"Current installation options:
 
 - ` `default host triple: `{}`
@@ -704,7 +762,17 @@ fn current_install_opts(opts: &InstallOpts<'_>, process: &Process) -> String {
             .unwrap_or("stable (default)".into()),
         opts.profile,
         if !opts.no_modify_path { "yes" } else { "no" }
-    )
+    );
+    for (label, values) in [
+        ("`          `components", opts.components),
+        ("`             `targets", opts.targets),
+        ("`    `extra toolchains", opts.toolchains),
+    ] {
+        if !values.is_empty() {
+            current.push_str(&format!("- {label}: `{}`\n", values.join(", ")));
+        }
+    }
+    current
 }
 
 fn install_bins(process: &Process) -> Result<()> {
@@ -830,40 +898,61 @@ async fn maybe_install_rust(
 
     let mut cfg = common::set_globals(current_dir, verbose, quiet, process)?;
 
-    let (components, targets) = (opts.components, opts.targets);
+    let (components, targets, toolchains) = (opts.components, opts.targets, opts.toolchains);
+    let no_update_toolchain = opts.no_update_toolchain;
     let toolchain = opts.install(&mut cfg)?;
     if let Some(ref desc) = toolchain {
-        let status = if Toolchain::exists(&cfg, &desc.into())? {
+        if Toolchain::exists(&cfg, &desc.into())? {
             warn!("Updating existing toolchain, profile choice will be ignored");
-            // If we have a partial install we might not be able to read content here. We could:
-            // - fail and folk have to delete the partially present toolchain to recover
-            // - silently ignore it (and provide inconsistent metadata for reporting the install/update change)
-            // - delete the partial install and start over
-            // For now, we error.
-            let mut toolchain = DistributableToolchain::new(&cfg, desc.clone())?;
-            toolchain
-                .update(components, targets, cfg.get_profile()?)
-                .await?
-        } else {
-            DistributableToolchain::install(
-                &cfg,
-                desc,
-                components,
-                targets,
-                cfg.get_profile()?,
-                true,
-            )
-            .await?
-            .0
-        };
-
+        }
+        let status = install_toolchain(&cfg, desc, components, targets).await?;
         cfg.set_default(Some(&desc.into()))?;
         writeln!(process.stdout().lock())?;
         common::show_channel_update(&cfg, PackageUpdate::Toolchain(desc.clone()), Ok(status))?;
     }
+
+    for name in toolchains {
+        let desc = name
+            .parse::<PartialToolchainDesc>()?
+            .resolve(&cfg.get_default_host_triple()?)?;
+        if Toolchain::exists(&cfg, &(&desc).into())? {
+            if no_update_toolchain {
+                info!("skipping existing toolchain '{desc}'");
+                continue;
+            }
+            info!("updating existing toolchain '{desc}'");
+        }
+        let status = install_toolchain(&cfg, &desc, &[], &[]).await?;
+        writeln!(process.stdout().lock())?;
+        common::show_channel_update(&cfg, PackageUpdate::Toolchain(desc), Ok(status))?;
+    }
     Ok(())
 }
 
+/// Installs the toolchain `desc`, or updates it if it's already installed.
+async fn install_toolchain(
+    cfg: &Cfg<'_>,
+    desc: &ToolchainDesc,
+    components: &[&str],
+    targets: &[&str],
+) -> Result<UpdateStatus> {
+    Ok(if Toolchain::exists(cfg, &desc.into())? {
+        // If we have a partial install we might not be able to read content here. We could:
+        // - fail and folk have to delete the partially present toolchain to recover
+        // - silently ignore it (and provide inconsistent metadata for reporting the install/update change)
+        // - delete the partial install and start over
+        // For now, we error.
+        let mut toolchain = DistributableToolchain::new(cfg, desc.clone())?;
+        toolchain
+            .update(components, targets, &cfg.get_profile()?)
+            .await?
+    } else {
+        DistributableToolchain::install(cfg, desc, components, targets, &cfg.get_profile()?, true)
+            .await?
+            .0
+    })
+}
+
 pub(crate) fn uninstall(no_prompt: bool, process: &Process) -> Result<utils::ExitCode> {
     if NEVER_SELF_UPDATE {
         error!("self-uninstall is disabled for this build of rustup");
@@ -979,7 +1068,10 @@ pub(crate) fn uninstall(no_prompt: bool, process: &Process) -> Result<utils::Exi
 /// (and on windows this process will not be running to do it),
 /// rustup-init is stored in `CARGO_HOME`/bin, and then deleted next
 /// time rustup runs.
-pub(crate) async fn update(cfg: &Cfg<'_>) -> Result<utils::ExitCode> {
+///
+/// Without a `version`, this moves to the latest release of rustup, unless
+/// that is outside the range self-updates are pinned to.
+pub(crate) async fn update(cfg: &Cfg<'_>, version: Option<&str>) -> Result<utils::ExitCode> {
     common::warn_if_host_is_emulated(cfg.process);
 
     use common::SelfUpdatePermission::*;
@@ -1003,7 +1095,8 @@ pub(crate) async fn update(cfg: &Cfg<'_>) -> Result<utils::ExitCode> {
         Permit => {}
     }
 
-    match prepare_update(cfg.process).await? {
+    let pin = cfg.get_self_update_version()?;
+    match prepare_update(cfg.process, version, pin.as_ref()).await? {
         Some(setup_path) => {
             let Some(version) = get_and_parse_new_rustup_version(&setup_path) else {
                 error!("failed to get rustup version");
@@ -1061,7 +1154,13 @@ fn parse_new_rustup_version(version: String) -> String {
     String::from(matched_version)
 }
 
-pub(crate) async fn prepare_update(process: &Process) -> Result<Option<PathBuf>> {
+/// Downloads the rustup `version`, or else the latest release matching `pin`,
+/// and keeps the installed rustup for [`rollback`].
+pub(crate) async fn prepare_update(
+    process: &Process,
+    version: Option<&str>,
+    pin: Option<&VersionReq>,
+) -> Result<Option<PathBuf>> {
     let cargo_home = process.cargo_home()?;
     let rustup_path = cargo_home.join(format!("bin{MAIN_SEPARATOR}rustup{EXE_SUFFIX}"));
     let setup_path = cargo_home.join(format!("bin{MAIN_SEPARATOR}rustup-init{EXE_SUFFIX}"));
@@ -1093,8 +1192,30 @@ pub(crate) async fn prepare_update(process: &Process) -> Result<Option<PathBuf>>
     let current_version = env!("CARGO_PKG_VERSION");
 
     // Get available version
-    info!("checking for self-update");
-    let available_version = get_available_rustup_version(process).await?;
+    let available_version = match version {
+        Some(version) => {
+            Version::parse(version)
+                .with_context(|| format!("invalid rustup version: '{version}'"))?;
+            version.to_owned()
+        }
+        None => {
+            info!("checking for self-update");
+            let release = get_rustup_release(process).await?;
+            match pin {
+                Some(pin) => match release.highest_matching(pin) {
+                    Some(version) => version,
+                    None => {
+                        info!(
+                            "skipping rustup {}, which doesn't match the pinned version '{pin}'",
+                            release.version
+                        );
+                        return Ok(None);
+                    }
+                },
+                None => release.version,
+            }
+        }
+    };
 
     // If up-to-date
     if available_version == current_version {
@@ -1111,13 +1232,88 @@ pub(crate) async fn prepare_update(process: &Process) -> Result<Option<PathBuf>>
     info!("downloading self-update");
     utils::download_file(&download_url, &setup_path, None, &|_| (), process).await?;
 
+    // Verify it before it gets anywhere near running
+    if let Err(e) = verify_update(&url, &setup_path, process).await {
+        utils::remove_file("setup", &setup_path)?;
+        return Err(e);
+    }
+
     // Mark as executable
     utils::make_executable(&setup_path)?;
 
+    // Keep the installed version to roll back to
+    utils::copy_file(&rustup_path, &previous_rustup_path(process)?)?;
+
     Ok(Some(setup_path))
 }
 
+/// Checks the signature of the `rustup-init` downloaded from `url` to
+/// `setup_path`.
+async fn verify_update(url: &str, setup_path: &Path, process: &Process) -> Result<()> {
+    let tempdir = tempfile::Builder::new()
+        .prefix("rustup-update")
+        .tempdir()
+        .context("error creating temp directory")?;
+
+    let signature_url = utils::parse_url(&format!("{url}.sig"))?;
+    let signature_file = tempdir.path().join("rustup-init.sig");
+    utils::download_file(&signature_url, &signature_file, None, &|_| (), process)
+        .await
+        .context("could not download the signature of the self-update")?;
+    let signature = utils::read_file("signature", &signature_file)?;
+    let binary = fs::read(setup_path).context("reading the self-update")?;
+
+    signature::verify(process, url, &binary, &signature)
+}
+
+/// Where the rustup that the last self-update replaced is kept.
+fn previous_rustup_path(process: &Process) -> Result<PathBuf> {
+    Ok(process
+        .rustup_home()?
+        .join(format!("rustup-previous{EXE_SUFFIX}")))
+}
+
+/// Reinstalls the rustup that the last self-update replaced, keeping the
+/// current one in its place so that rolling back again undoes this.
+pub(crate) fn rollback(process: &Process) -> Result<utils::ExitCode> {
+    if NEVER_SELF_UPDATE {
+        error!("self-update is disabled for this build of rustup");
+        error!("you should probably use your system package manager to update rustup");
+        return Ok(utils::ExitCode(1));
+    }
+
+    let cargo_home = process.cargo_home()?;
+    let rustup_path = cargo_home.join(format!("bin{MAIN_SEPARATOR}rustup{EXE_SUFFIX}"));
+    let setup_path = cargo_home.join(format!("bin{MAIN_SEPARATOR}rustup-init{EXE_SUFFIX}"));
+    let previous_path = previous_rustup_path(process)?;
+
+    if !rustup_path.exists() {
+        return Err(CLIError::NotSelfInstalled { p: cargo_home }.into());
+    }
+    if !previous_path.exists() {
+        bail!("there is no previous version of rustup to roll back to");
+    }
+
+    if setup_path.exists() {
+        utils::remove_file("setup", &setup_path)?;
+    }
+    utils::copy_file(&previous_path, &setup_path)?;
+    utils::make_executable(&setup_path)?;
+    utils::copy_file(&rustup_path, &previous_path)?;
+
+    let version = get_and_parse_new_rustup_version(&setup_path);
+    info!(
+        "rolling back rustup to {}",
+        version.as_deref().unwrap_or("(unknown)")
+    );
+    run_update(&setup_path)
+}
+
 async fn get_available_rustup_version(process: &Process) -> Result<String> {
+    Ok(get_rustup_release(process).await?.version)
+}
+
+async fn get_rustup_release(process: &Process) -> Result<RustupManifest> {
     let update_root = update_root(process);
     let tempdir = tempfile::Builder::new()
         .prefix("rustup-update")
@@ -1130,10 +1326,8 @@ async fn get_available_rustup_version(process: &Process) -> Result<String> {
     let release_file = tempdir.path().join("release-stable.toml");
     utils::download_file(&release_file_url, &release_file, None, &|_| (), process).await?;
     let release_toml_str = utils::read_file("rustup release", &release_file)?;
-    let release_toml = toml::from_str::<RustupManifest>(&release_toml_str)
-        .context("unable to parse rustup release file")?;
-
-    Ok(release_toml.version)
+    toml::from_str::<RustupManifest>(&release_toml_str)
+        .context("unable to parse rustup release file")
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -1141,6 +1335,23 @@ async fn get_available_rustup_version(process: &Process) -> Result<String> {
 struct RustupManifest {
     schema_version: SchemaVersion,
     version: String,
+    /// Every release in the archive, if the update server lists them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    versions: Vec<String>,
+}
+
+impl RustupManifest {
+    /// The highest release that matches `pin`, out of the latest one and
+    /// those listed in `versions`.
+    fn highest_matching(&self, pin: &VersionReq) -> Option<String> {
+        self.versions
+            .iter()
+            .chain([&self.version])
+            .filter_map(|v| Version::parse(v).ok())
+            .filter(|v| pin.matches(v))
+            .max()
+            .map(|v| v.to_string())
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
@@ -1240,6 +1451,7 @@ mod tests {
                 no_modify_path: false,
                 components: &[],
                 targets: &[],
+                toolchains: &[],
                 no_update_toolchain: false,
             };
 
@@ -24,7 +24,8 @@
 //! 2) sourcing this script (`. /path/to/script`) in any appropriate rc file
 
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Result};
 
@@ -69,14 +70,16 @@ pub(crate) fn cargo_home_str(process: &Process) -> Result<Cow<'static, str>> {
 }
 
 // TODO: Tcsh (BSD)
-// TODO?: Make a decision on Ion Shell, Power Shell, Nushell
-// Cross-platform non-POSIX shells have not been assessed for integration yet
+// TODO?: Make a decision on Ion Shell, Power Shell
 fn enumerate_shells() -> Vec<Shell> {
     vec![
         Box::new(Posix),
         Box::new(Bash),
         Box::new(Zsh),
         Box::new(Fish),
+        Box::new(Nushell),
+        Box::new(Elvish),
+        Box::new(Xonsh),
     ]
 }
 
@@ -86,6 +89,21 @@ pub(crate) fn get_available_shells(process: &Process) -> impl Iterator<Item = Sh
         .filter(|sh| sh.does_exist(process))
 }
 
+/// The shell for `rustup shell` to start: the login shell `SHELL` names, or
+/// else the last of the available shells that can be run, as the more
+/// specific shells come after the POSIX one.
+pub(crate) fn user_shell(process: &Process) -> OsString {
+    if let Some(shell) = process.var_os("SHELL").filter(|s| !s.is_empty()) {
+        return shell;
+    }
+    get_available_shells(process)
+        .map(|sh| sh.command())
+        .filter(|cmd| utils::find_cmd(&[cmd], process).is_some())
+        .last()
+        .unwrap_or("sh")
+        .into()
+}
+
 pub(crate) trait UnixShell {
     // Detects if a shell "exists". Users have multiple shells, so an "eager"
     // heuristic should be used, assuming shells exist if any traces do.
@@ -98,6 +116,9 @@ pub(crate) trait UnixShell {
     // Gives rcs that should be written to.
     fn update_rcs(&self, process: &Process) -> Vec<PathBuf>;
 
+    // The command that starts the shell.
+    fn command(&self) -> &'static str;
+
     // Writes the relevant env file.
     fn env_script(&self) -> ShellScript {
         ShellScript {
@@ -129,6 +150,10 @@ impl UnixShell for Posix {
         // POSIX spec so it should always be set up.
         self.rcfiles(process)
     }
+
+    fn command(&self) -> &'static str {
+        "sh"
+    }
 }
 
 struct Bash;
@@ -153,6 +178,10 @@ impl UnixShell for Bash {
             .filter(|rc| rc.is_file())
             .collect()
     }
+
+    fn command(&self) -> &'static str {
+        "bash"
+    }
 }
 
 struct Zsh;
@@ -207,6 +236,10 @@ impl UnixShell for Zsh {
             .take(1)
             .collect()
     }
+
+    fn command(&self) -> &'static str {
+        "zsh"
+    }
 }
 
 struct Fish;
@@ -243,6 +276,10 @@ impl UnixShell for Fish {
         }
     }
 
+    fn command(&self) -> &'static str {
+        "fish"
+    }
+
     fn env_script(&self) -> ShellScript {
         ShellScript {
             name: "env.fish",
@@ -255,6 +292,157 @@ impl UnixShell for Fish {
     }
 }
 
+// Whether `SHELL` names the given shell. The file name is compared in full,
+// as short names such as `nu` are found in unrelated paths.
+fn is_login_shell(process: &Process, name: &str) -> bool {
+    matches!(process.var("SHELL"), Ok(sh) if Path::new(&sh).file_name() == Some(name.as_ref()))
+}
+
+// The configuration files of shells following the XDG base directory spec:
+// in "$XDG_CONFIG_HOME" if it is set, and in "~/.config" either way.
+fn xdg_config_files(process: &Process, file: &str) -> Vec<PathBuf> {
+    let p0 = process
+        .var("XDG_CONFIG_HOME")
+        .ok()
+        .map(|p| PathBuf::from(p).join(file));
+    let p1 = process.home_dir().map(|p| p.join(".config").join(file));
+    p0.into_iter().chain(p1).collect()
+}
+
+struct Nushell;
+
+impl UnixShell for Nushell {
+    fn does_exist(&self, process: &Process) -> bool {
+        // nushell has to either be the shell or be callable for nushell setup.
+        is_login_shell(process, "nu") || utils::find_cmd(&["nu"], process).is_some()
+    }
+
+    fn rcfiles(&self, process: &Process) -> Vec<PathBuf> {
+        xdg_config_files(process, "nushell/env.nu")
+    }
+
+    fn update_rcs(&self, process: &Process) -> Vec<PathBuf> {
+        // Like zsh, write to the first rcfile that exists, or else create the
+        // first one.
+        self.rcfiles(process)
+            .into_iter()
+            .filter(|rc| rc.is_file())
+            .chain(self.rcfiles(process))
+            .take(1)
+            .collect()
+    }
+
+    fn command(&self) -> &'static str {
+        "nu"
+    }
+
+    fn env_script(&self) -> ShellScript {
+        ShellScript {
+            name: "env.nu",
+            content: include_str!("env.nu"),
+        }
+    }
+
+    fn source_string(&self, process: &Process) -> Result<String> {
+        // `source` needs a path known when parsing, so `$env.HOME` can't be
+        // used: `$nu.home-path` is a constant.
+        Ok(match cargo_home_str(process)?.strip_prefix("$HOME") {
+            Some(rest) => format!(r#"source $"($nu.home-path){rest}/env.nu""#),
+            None => format!(r#"source "{}/env.nu""#, cargo_home_str(process)?),
+        })
+    }
+}
+
+struct Elvish;
+
+impl UnixShell for Elvish {
+    fn does_exist(&self, process: &Process) -> bool {
+        // elvish has to either be the shell or be callable for elvish setup.
+        is_login_shell(process, "elvish") || utils::find_cmd(&["elvish"], process).is_some()
+    }
+
+    fn rcfiles(&self, process: &Process) -> Vec<PathBuf> {
+        // Older versions of elvish read "~/.elvish/rc.elv" instead.
+        let legacy = process.home_dir().map(|p| p.join(".elvish/rc.elv"));
+        xdg_config_files(process, "elvish/rc.elv")
+            .into_iter()
+            .chain(legacy)
+            .collect()
+    }
+
+    fn update_rcs(&self, process: &Process) -> Vec<PathBuf> {
+        self.rcfiles(process)
+            .into_iter()
+            .filter(|rc| rc.is_file())
+            .chain(self.rcfiles(process))
+            .take(1)
+            .collect()
+    }
+
+    fn command(&self) -> &'static str {
+        "elvish"
+    }
+
+    fn env_script(&self) -> ShellScript {
+        ShellScript {
+            name: "env.elv",
+            content: include_str!("env.elv"),
+        }
+    }
+
+    fn source_string(&self, process: &Process) -> Result<String> {
+        // elvish has no `source`; evaluating the script is the idiom.
+        Ok(match cargo_home_str(process)?.strip_prefix("$HOME") {
+            Some(rest) => format!("eval (slurp < ~{rest}/env.elv)"),
+            None => format!("eval (slurp < '{}/env.elv')", cargo_home_str(process)?),
+        })
+    }
+}
+
+struct Xonsh;
+
+impl UnixShell for Xonsh {
+    fn does_exist(&self, process: &Process) -> bool {
+        // xonsh has to either be the shell or be callable for xonsh setup.
+        is_login_shell(process, "xonsh") || utils::find_cmd(&["xonsh"], process).is_some()
+    }
+
+    fn rcfiles(&self, process: &Process) -> Vec<PathBuf> {
+        process
+            .home_dir()
+            .map(|p| p.join(".xonshrc"))
+            .into_iter()
+            .chain(xdg_config_files(process, "xonsh/rc.xsh"))
+            .collect()
+    }
+
+    fn update_rcs(&self, process: &Process) -> Vec<PathBuf> {
+        // xonsh reads all of its rcfiles, so only one is written, and
+        // "~/.xonshrc" if there are none yet.
+        self.rcfiles(process)
+            .into_iter()
+            .filter(|rc| rc.is_file())
+            .chain(self.rcfiles(process))
+            .take(1)
+            .collect()
+    }
+
+    fn command(&self) -> &'static str {
+        "xonsh"
+    }
+
+    fn env_script(&self) -> ShellScript {
+        ShellScript {
+            name: "env.xsh",
+            content: include_str!("env.xsh"),
+        }
+    }
+
+    fn source_string(&self, process: &Process) -> Result<String> {
+        Ok(format!(r#"source "{}/env.xsh""#, cargo_home_str(process)?))
+    }
+}
+
 pub(crate) fn legacy_paths(process: &Process) -> impl Iterator<Item = PathBuf> + '_ {
     let zprofiles = Zsh::zdotdir(process)
         .into_iter()
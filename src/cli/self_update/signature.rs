@@ -0,0 +1,87 @@
+//! Signatures of rustup-init binaries
+//!
+//! Each `rustup-init` on the update server comes with a detached Ed25519
+//! signature at the same url with a `.sig` suffix, holding the signature of
+//! the whole binary in hex. The `.sha256` of a download only guards against
+//! transport corruption, since it comes from the same server as the binary;
+//! the signature is checked against a key built into rustup instead, so a
+//! compromised server or mirror cannot get its own binary installed.
+
+use anyhow::{Context, Result};
+use ring::signature::{UnparsedPublicKey, ED25519};
+
+use crate::errors::RustupError;
+use crate::process::Process;
+use crate::utils::utils::decode_hex;
+
+/// The public half of the key that the release `rustup-init` binaries are
+/// signed with, in hex.
+const SIGNING_KEY: &str = "4e546731978f74d53b9da52acbb4a0bcc5417dac7e2a685c5f4690f7b07b8a13";
+
+/// The key to verify self-updates with.
+///
+/// Builds for the test suite take it from `RUSTUP_OVERRIDE_SIGNING_KEY`, so
+/// that the mock update server can sign with a key of its own.
+fn signing_key(process: &Process) -> Result<Vec<u8>> {
+    #[cfg(feature = "test")]
+    if let Ok(key) = process.var("RUSTUP_OVERRIDE_SIGNING_KEY") {
+        return decode_hex(&key)
+            .ok_or_else(|| anyhow::anyhow!("invalid RUSTUP_OVERRIDE_SIGNING_KEY: '{key}'"));
+    }
+    #[cfg(not(feature = "test"))]
+    let _ = process;
+
+    Ok(decode_hex(SIGNING_KEY).expect("the built-in signing key is valid hex"))
+}
+
+/// Checks that `signature`, the contents of the `.sig` file found next to
+/// `url`, is a valid signature of `binary`.
+pub(super) fn verify(process: &Process, url: &str, binary: &[u8], signature: &str) -> Result<()> {
+    let failed = || RustupError::SignatureFailed {
+        url: url.to_owned(),
+    };
+    let signature = decode_hex(signature.trim()).ok_or_else(failed)?;
+    UnparsedPublicKey::new(&ED25519, signing_key(process)?)
+        .verify(binary, &signature)
+        .map_err(|_| failed())
+        .context("refusing to install an unverified rustup")
+}
+
+#[cfg(all(test, feature = "test"))]
+mod tests {
+    use super::*;
+
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    use crate::process::TestProcess;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn test_verify() {
+        let key = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+        let tp = TestProcess::with_vars(
+            [(
+                "RUSTUP_OVERRIDE_SIGNING_KEY".to_owned(),
+                to_hex(key.public_key().as_ref()),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let url = "https://static.rust-lang.org/rustup/archive/1.27.1/rustup-init";
+        let binary = b"rustup-init";
+        let signature = to_hex(key.sign(binary).as_ref());
+
+        verify(&tp.process, url, binary, &format!("{signature}\n")).unwrap();
+        // Another binary
+        assert!(verify(&tp.process, url, b"rustup-init\n", &signature).is_err());
+        // A mangled signature
+        assert!(verify(&tp.process, url, binary, &signature[2..]).is_err());
+        assert!(verify(&tp.process, url, binary, "").is_err());
+        // The built-in key did not sign it
+        let tp = TestProcess::default();
+        assert!(verify(&tp.process, url, binary, &signature).is_err());
+    }
+}
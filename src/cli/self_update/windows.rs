@@ -27,6 +27,17 @@ use crate::process::{terminalsource::ColorableTerminal, Process};
 use crate::utils::utils;
 use crate::utils::Notification;
 
+/// The shell for `rustup shell` to start: PowerShell if it can be found, or
+/// else the command interpreter `COMSPEC` names.
+pub(crate) fn user_shell(process: &Process) -> OsString {
+    match utils::find_cmd(&["pwsh", "powershell"], process) {
+        Some(cmd) => cmd.into(),
+        None => process
+            .var_os("COMSPEC")
+            .unwrap_or_else(|| "cmd.exe".into()),
+    }
+}
+
 pub(crate) fn ensure_prompt(process: &Process) -> Result<()> {
     writeln!(process.stdout().lock(),)?;
     writeln!(process.stdout().lock(), "Press the Enter key to continue.")?;
@@ -8,7 +8,7 @@ use tracing_subscriber::{reload::Handle, EnvFilter, Registry};
 use crate::{
     cli::{
         common,
-        self_update::{self, InstallOpts},
+        self_update::{self, InstallConfig, InstallOpts},
     },
     dist::Profile,
     process::Process,
@@ -64,6 +64,22 @@ struct RustupInit {
     #[arg(long)]
     no_modify_path: bool,
 
+    /// Read the installation options from a TOML answer file
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "default_host",
+            "default_toolchain",
+            "profile",
+            "component",
+            "target",
+            "no_update_default_toolchain",
+            "no_modify_path",
+        ],
+    )]
+    config: Option<PathBuf>,
+
     /// Secret command used during self-update. Not for users
     #[arg(long, hide = true)]
     self_replace: bool,
@@ -85,13 +101,14 @@ pub async fn main(
         verbose,
         quiet,
         no_prompt,
-        default_host,
-        default_toolchain,
-        profile,
-        component,
-        target,
-        no_update_default_toolchain,
-        no_modify_path,
+        mut default_host,
+        mut default_toolchain,
+        mut profile,
+        mut component,
+        mut target,
+        mut no_update_default_toolchain,
+        mut no_modify_path,
+        config,
         self_replace,
         dump_testament,
     } = match RustupInit::try_parse() {
@@ -111,6 +128,24 @@ pub async fn main(
         return common::dump_testament(process);
     }
 
+    let mut toolchains = Vec::new();
+    if let Some(path) = config {
+        let config = InstallConfig::from_file(&path)?;
+        default_host = config.default_host;
+        default_toolchain = config
+            .default_toolchain
+            .map(MaybeOfficialToolchainName::try_from)
+            .transpose()?;
+        if let Some(name) = config.profile {
+            profile = name.parse()?;
+        }
+        component = config.components;
+        target = config.targets;
+        toolchains = config.toolchains;
+        no_update_default_toolchain = !config.update_default_toolchain;
+        no_modify_path = !config.modify_path;
+    }
+
     if profile == Profile::Complete {
         warn!("{}", common::WARN_COMPLETE_PROFILE);
     }
@@ -129,6 +164,7 @@ pub async fn main(
         no_update_toolchain: no_update_default_toolchain,
         components: &component.iter().map(|s| &**s).collect::<Vec<_>>(),
         targets: &target.iter().map(|s| &**s).collect::<Vec<_>>(),
+        toolchains: &toolchains.iter().map(|s| &**s).collect::<Vec<_>>(),
     };
 
     self_update::install(current_dir, no_prompt, verbose, quiet, opts, process).await
@@ -5,19 +5,21 @@ use std::sync::Arc;
 use std::{env, io};
 
 use anyhow::{anyhow, bail, Context, Result};
+use futures_util::StreamExt;
+use semver::VersionReq;
 use serde::Deserialize;
 use thiserror::Error as ThisError;
-use tokio_stream::StreamExt;
 use tracing::trace;
 
 use crate::{
-    cli::{common, self_update::SelfUpdateMode},
+    cli::{common, proxy_mode::AutoInstallMode, self_update::SelfUpdateMode},
     dist::{
-        self, download::DownloadCfg, temp, PartialToolchainDesc, Profile, TargetTriple,
-        ToolchainDesc,
+        self, download::DownloadCfg, http_cache::HttpCache, temp, PartialToolchainDesc, Profile,
+        ResolvedProfile, TargetTriple, ToolchainDesc,
     },
     errors::RustupError,
     fallback_settings::FallbackSettings,
+    history::{Action, Change, History},
     install::UpdateStatus,
     notifications::*,
     process::Process,
@@ -123,7 +125,7 @@ enum OverrideCfg {
         toolchain: ToolchainDesc,
         components: Vec<String>,
         targets: Vec<String>,
-        profile: Option<Profile>,
+        profile: Option<ResolvedProfile>,
     },
 }
 
@@ -175,7 +177,7 @@ impl OverrideCfg {
                         .toolchain
                         .profile
                         .as_deref()
-                        .map(Profile::from_str)
+                        .map(|name| cfg.resolve_profile(name))
                         .transpose()?,
                 }
             }
@@ -226,17 +228,23 @@ impl From<LocalToolchainName> for OverrideCfg {
     }
 }
 
+/// How many toolchains `rustup update` updates at a time, unless
+/// `RUSTUP_CONCURRENT_UPDATES` says otherwise.
+const DEFAULT_CONCURRENT_UPDATES: usize = 4;
+
 #[cfg(unix)]
 pub(crate) const UNIX_FALLBACK_SETTINGS: &str = "/etc/rustup/settings.toml";
 
 pub(crate) struct Cfg<'a> {
-    profile_override: Option<Profile>,
+    profile_override: Option<String>,
     pub rustup_dir: PathBuf,
     pub settings_file: SettingsFile,
     pub fallback_settings: Option<FallbackSettings>,
     pub toolchains_dir: PathBuf,
     pub update_hash_dir: PathBuf,
     pub download_dir: PathBuf,
+    pub http_cache: HttpCache,
+    pub history: History,
     pub tmp_cx: temp::Context,
     pub toolchain_override: Option<ResolvableToolchainName>,
     pub env_override: Option<LocalToolchainName>,
@@ -297,6 +305,8 @@ impl<'a> Cfg<'a> {
             .transpose()?;
 
         let dist_root_server = dist_root_server(process)?;
+        let http_cache = HttpCache::new(rustup_dir.join("http-cache"), &dist_root_server);
+        let history = History::new(rustup_dir.join("history"));
 
         let notify_clone = notify_handler.clone();
         let tmp_cx = temp::Context::new(
@@ -314,6 +324,8 @@ impl<'a> Cfg<'a> {
             toolchains_dir,
             update_hash_dir,
             download_dir,
+            http_cache,
+            history,
             tmp_cx,
             notify_handler,
             toolchain_override: None,
@@ -343,31 +355,75 @@ impl<'a> Cfg<'a> {
             dist_root: &self.dist_root_url,
             tmp_cx: &self.tmp_cx,
             download_dir: &self.download_dir,
+            http_cache: Some(&self.http_cache),
             notify_handler,
             process: self.process,
         }
     }
 
-    pub(crate) fn set_profile_override(&mut self, profile: Profile) {
+    pub(crate) fn set_profile_override(&mut self, profile: String) {
         self.profile_override = Some(profile);
     }
 
     pub(crate) fn set_default(&self, toolchain: Option<&ToolchainName>) -> Result<()> {
-        self.settings_file.with_mut(|s| {
-            s.default_toolchain = toolchain.map(|t| t.to_string());
-            Ok(())
+        let toolchain_str = toolchain.map(|t| t.to_string());
+        let previous = self.settings_file.with_mut(|s| {
+            Ok(std::mem::replace(
+                &mut s.default_toolchain,
+                toolchain_str.clone(),
+            ))
         })?;
         (self.notify_handler)(Notification::SetDefaultToolchain(toolchain));
-        Ok(())
+        self.history
+            .record(Change::new(Action::Default, toolchain_str).previous_toolchain(previous))
+    }
+
+    /// Hold a toolchain so that it isn't updated, returning whether it
+    /// wasn't already
+    pub(crate) fn hold_toolchain(&self, toolchain: &ToolchainDesc) -> Result<bool> {
+        let held = self
+            .settings_file
+            .with_mut(|s| Ok(s.held_toolchains.insert(toolchain.to_string())))?;
+        if held {
+            (self.notify_handler)(Notification::HeldToolchain(toolchain));
+        }
+        Ok(held)
+    }
+
+    /// Let a toolchain be updated again, returning whether it was held
+    pub(crate) fn unhold_toolchain(&self, toolchain: &ToolchainDesc) -> Result<bool> {
+        let unheld = self
+            .settings_file
+            .with_mut(|s| Ok(s.held_toolchains.remove(&toolchain.to_string())))?;
+        if unheld {
+            (self.notify_handler)(Notification::UnheldToolchain(toolchain));
+        }
+        Ok(unheld)
     }
 
-    pub(crate) fn set_profile(&mut self, profile: Profile) -> Result<()> {
+    pub(crate) fn is_held(&self, toolchain: &ToolchainDesc) -> Result<bool> {
+        self.settings_file
+            .with(|s| Ok(s.held_toolchains.contains(&toolchain.to_string())))
+    }
+
+    /// Whether updates leave `toolchain` alone for being held, which `force`
+    /// overrides, notifying of the skip if so.
+    pub(crate) fn skips_held(&self, toolchain: &ToolchainDesc, force: bool) -> Result<bool> {
+        let skip = !force && self.is_held(toolchain)?;
+        if skip {
+            (self.notify_handler)(Notification::SkippingHeldToolchain(toolchain));
+        }
+        Ok(skip)
+    }
+
+    pub(crate) fn set_profile(&mut self, name: &str) -> Result<()> {
         self.profile_override = None;
-        self.settings_file.with_mut(|s| {
-            s.profile = Some(profile);
-            Ok(())
+        let profile = self.settings_file.with_mut(|s| {
+            let profile = s.resolve_profile(name)?;
+            s.set_profile(&profile);
+            Ok(profile)
         })?;
-        (self.notify_handler)(Notification::SetProfile(profile.as_str()));
+        (self.notify_handler)(Notification::SetProfile(profile.name()));
         Ok(())
     }
 
@@ -380,6 +436,43 @@ impl<'a> Cfg<'a> {
         Ok(())
     }
 
+    pub(crate) fn set_auto_install_components(&mut self, mode: AutoInstallMode) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.auto_install_components = Some(mode);
+            Ok(())
+        })?;
+        (self.notify_handler)(Notification::SetAutoInstallComponents(mode.as_str()));
+        Ok(())
+    }
+
+    /// Pins self-updates to the versions of rustup matching `req`, or
+    /// unpins them with `*`.
+    pub(crate) fn set_self_update_version(&mut self, req: &str) -> Result<()> {
+        let req = match req.trim() {
+            "*" => None,
+            req => {
+                VersionReq::parse(req)
+                    .with_context(|| format!("invalid version requirement: '{req}'"))?;
+                Some(req.to_owned())
+            }
+        };
+        self.settings_file.with_mut(|s| {
+            s.self_update_version = req.clone();
+            Ok(())
+        })?;
+        (self.notify_handler)(Notification::SetSelfUpdateVersion(req.as_deref()));
+        Ok(())
+    }
+
+    pub(crate) fn set_release_notes(&mut self, enabled: bool) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.release_notes = enabled;
+            Ok(())
+        })?;
+        (self.notify_handler)(Notification::SetReleaseNotes(enabled));
+        Ok(())
+    }
+
     pub(crate) fn set_toolchain_override(&mut self, toolchain_override: &ResolvableToolchainName) {
         self.toolchain_override = Some(toolchain_override.to_owned());
     }
@@ -391,12 +484,17 @@ impl<'a> Cfg<'a> {
     // if there is no profile in the settings file. The last variant happens when
     // a user upgrades from a version of Rustup without profiles to a version of
     // Rustup with profiles.
-    pub(crate) fn get_profile(&self) -> Result<Profile> {
-        if let Some(p) = self.profile_override {
-            return Ok(p);
-        }
-        self.settings_file
-            .with(|s| Ok(s.profile.unwrap_or_default()))
+    pub(crate) fn get_profile(&self) -> Result<ResolvedProfile> {
+        self.settings_file.with(|s| match &self.profile_override {
+            Some(name) => s.resolve_profile(name),
+            None => Ok(s.profile()?.unwrap_or_else(|| Profile::default().into())),
+        })
+    }
+
+    /// Resolves the name of a built-in profile, or of one defined in the
+    /// settings file.
+    pub(crate) fn resolve_profile(&self, name: &str) -> Result<ResolvedProfile> {
+        self.settings_file.with(|s| s.resolve_profile(name))
     }
 
     pub(crate) fn get_self_update_mode(&self) -> Result<SelfUpdateMode> {
@@ -414,6 +512,28 @@ impl<'a> Cfg<'a> {
         })
     }
 
+    pub(crate) fn get_auto_install_mode(&self) -> Result<AutoInstallMode> {
+        self.settings_file
+            .with(|s| Ok(s.auto_install_components.unwrap_or_default()))
+    }
+
+    pub(crate) fn get_self_update_version(&self) -> Result<Option<VersionReq>> {
+        self.settings_file.with(|s| {
+            s.self_update_version
+                .as_deref()
+                .map(|req| {
+                    VersionReq::parse(req).with_context(|| {
+                        format!("invalid self_update_version in settings: '{req}'")
+                    })
+                })
+                .transpose()
+        })
+    }
+
+    pub(crate) fn get_release_notes(&self) -> Result<bool> {
+        self.settings_file.with(|s| Ok(s.release_notes))
+    }
+
     pub(crate) fn ensure_toolchains_dir(&self) -> Result<(), anyhow::Error> {
         utils::ensure_dir_exists("toolchains", &self.toolchains_dir, &|n| {
             (self.notify_handler)(n)
@@ -798,7 +918,7 @@ impl<'a> Cfg<'a> {
         toolchain: &ToolchainDesc,
         components: Vec<String>,
         targets: Vec<String>,
-        profile: Option<Profile>,
+        profile: Option<ResolvedProfile>,
         verbose: bool,
     ) -> Result<(UpdateStatus, Toolchain<'_>)> {
         common::warn_if_host_is_incompatible(
@@ -806,6 +926,7 @@ impl<'a> Cfg<'a> {
             &TargetTriple::from_host_or_build(self.process),
             &toolchain.target,
             false,
+            self.process,
         )?;
         if verbose {
             (self.notify_handler)(Notification::LookingForToolchain(toolchain));
@@ -823,7 +944,7 @@ impl<'a> Cfg<'a> {
                     toolchain,
                     &components,
                     &targets,
-                    profile,
+                    &profile,
                     false,
                 )
                 .await?
@@ -832,10 +953,15 @@ impl<'a> Cfg<'a> {
                 if verbose {
                     (self.notify_handler)(Notification::UsingExistingToolchain(toolchain));
                 }
-                let status = if !distributable.components_exist(&components, &targets)? {
-                    distributable.update(&components, &targets, profile).await?
-                } else {
+                let status = if distributable.components_exist(&components, &targets)? {
+                    UpdateStatus::Unchanged
+                } else if self.is_held(toolchain)? {
+                    (self.notify_handler)(Notification::HeldToolchainNotModified(toolchain));
                     UpdateStatus::Unchanged
+                } else {
+                    distributable
+                        .update(&components, &targets, &profile)
+                        .await?
                 };
                 (status, distributable)
             }
@@ -911,30 +1037,68 @@ impl<'a> Cfg<'a> {
 
     /// Create an override for a toolchain
     pub(crate) fn make_override(&self, path: &Path, toolchain: &ToolchainName) -> Result<()> {
-        self.settings_file.with_mut(|s| {
+        let previous = self.settings_file.with_mut(|s| {
+            let previous = s.dir_override(path, self.notify_handler.as_ref());
             s.add_override(path, toolchain.to_string(), self.notify_handler.as_ref());
-            Ok(())
-        })
+            Ok(previous)
+        })?;
+        self.history.record(
+            Change::new(Action::Override, Some(toolchain.to_string()))
+                .path(path.to_owned())
+                .previous_toolchain(previous),
+        )
+    }
+
+    /// Remove the override for a directory, returning whether there was one
+    pub(crate) fn remove_override(&self, path: &Path) -> Result<bool> {
+        let previous = self.settings_file.with_mut(|s| {
+            let previous = s.dir_override(path, self.notify_handler.as_ref());
+            s.remove_override(path, self.notify_handler.as_ref());
+            Ok(previous)
+        })?;
+        let removed = previous.is_some();
+        if removed {
+            self.history.record(
+                Change::new(Action::Override, None)
+                    .path(path.to_owned())
+                    .previous_toolchain(previous),
+            )?;
+        }
+        Ok(removed)
     }
 
     pub(crate) async fn update_all_channels(
         &self,
         force_update: bool,
     ) -> Result<Vec<(ToolchainDesc, Result<UpdateStatus>)>> {
-        let channels = self.list_channels()?;
-        let channels = channels.into_iter();
-        let profile = self.get_profile()?;
-
-        // Update toolchains and collect the results
-        let channels = tokio_stream::iter(channels).then(|(desc, mut distributable)| async move {
-            let st = distributable
-                .update_extra(&[], &[], profile, force_update, false)
-                .await;
-            if let Err(ref e) = st {
-                (self.notify_handler)(Notification::NonFatalError(e));
+        let mut channels = Vec::new();
+        for (desc, toolchain) in self.list_channels()? {
+            if !self.skips_held(&desc, force_update)? {
+                channels.push((desc, toolchain));
             }
-            (desc, st)
-        });
+        }
+        let profile = &self.get_profile()?;
+        let concurrency = self
+            .process
+            .var("RUSTUP_CONCURRENT_UPDATES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_CONCURRENT_UPDATES)
+            .max(1);
+
+        // Update toolchains, several at a time, and collect the results in
+        // the order the toolchains are listed
+        let channels = futures_util::stream::iter(channels)
+            .map(|(desc, mut distributable)| async move {
+                let st = distributable
+                    .update_extra(&[], &[], profile, force_update, false)
+                    .await;
+                if let Err(ref e) = st {
+                    (self.notify_handler)(Notification::NonFatalError(e));
+                }
+                (desc, st)
+            })
+            .buffered(concurrency);
 
         Ok(channels.collect().await)
     }
@@ -994,6 +1158,8 @@ impl<'a> Debug for Cfg<'a> {
             toolchains_dir,
             update_hash_dir,
             download_dir,
+            http_cache,
+            history,
             tmp_cx,
             toolchain_override,
             env_override,
@@ -1011,6 +1177,8 @@ impl<'a> Debug for Cfg<'a> {
             .field("toolchains_dir", toolchains_dir)
             .field("update_hash_dir", update_hash_dir)
             .field("download_dir", download_dir)
+            .field("http_cache", http_cache)
+            .field("history", history)
             .field("tmp_cx", tmp_cx)
             .field("toolchain_override", toolchain_override)
             .field("env_override", env_override)
@@ -0,0 +1,301 @@
+//! A caching proxy for a Rust distribution server, run by `rustup serve-cache`.
+//!
+//! Channel manifests, and anything else they don't list, are forwarded from
+//! the upstream server on every request. Package archives listed in a
+//! manifest that went through the proxy are stored content-addressed by the
+//! hash the manifest gives for them, and are only stored once that hash has
+//! been validated. The hashes learned from manifests are kept in an index
+//! next to the archives, so that a restarted proxy serves them right away.
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+use std::io::{SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::dist::manifest::{Manifest, PackageTargets};
+use crate::dist::DEFAULT_DIST_SERVER;
+use crate::errors::RustupError;
+use crate::process::Process;
+use crate::utils::http_server::{read_request, write_head, Method, Status};
+use crate::utils::utils;
+
+/// The index of `CacheServer::hashes`, in the format of `sha256sum`.
+const INDEX: &str = "index.sha256";
+
+pub(crate) struct CacheServer {
+    /// The root of the upstream distribution server, without a trailing `/`.
+    upstream: String,
+    /// Where validated archives are stored, named by their SHA-256 hash.
+    cache_dir: PathBuf,
+    /// Expected hashes of package archives keyed by request path, learned
+    /// from the channel manifests forwarded so far, and stored in the
+    /// [`INDEX`] file of `cache_dir`.
+    hashes: Mutex<HashMap<String, String>>,
+    process: Process,
+}
+
+impl CacheServer {
+    pub(crate) fn new(upstream: &str, cache_dir: PathBuf, process: Process) -> Result<Self> {
+        std::fs::create_dir_all(&cache_dir).with_context(|| RustupError::CreatingDirectory {
+            name: "cache",
+            path: cache_dir.clone(),
+        })?;
+        let hashes = match std::fs::read_to_string(cache_dir.join(INDEX)) {
+            Ok(index) => index
+                .lines()
+                .filter_map(|line| line.split_once("  "))
+                .map(|(hash, path)| (path.to_owned(), hash.to_owned()))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e).context("failed to read the cache index"),
+        };
+        Ok(Self {
+            upstream: upstream.trim_end_matches('/').to_owned(),
+            cache_dir,
+            hashes: Mutex::new(hashes),
+            process,
+        })
+    }
+
+    /// Serves connections accepted on `listener` until accepting fails.
+    pub(crate) async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        // Downloads are not `Send`, so connections are handled concurrently
+        // on the current thread.
+        let local = tokio::task::LocalSet::new();
+        local
+            .run_until(async move {
+                loop {
+                    let (stream, peer) = listener
+                        .accept()
+                        .await
+                        .context("failed to accept connection")?;
+                    let server = self.clone();
+                    tokio::task::spawn_local(async move {
+                        if let Err(e) = server.handle(stream).await {
+                            warn!("failed to serve {peer}: {e:#}");
+                        }
+                    });
+                }
+            })
+            .await
+    }
+
+    async fn handle(&self, mut stream: TcpStream) -> Result<()> {
+        let request = match read_request(&mut stream).await? {
+            Ok(request) => request,
+            Err(status) => return write_head(&mut stream, status, 0, &[]).await,
+        };
+
+        if request.method == Method::Head {
+            let (status, len) = match self.size(&request.path).await {
+                Ok(Some(len)) => (Status::Ok, len),
+                Ok(None) => (Status::NotFound, 0),
+                Err(e) => {
+                    warn!("{} {}: {e:#}", request.method, request.path);
+                    (Status::BadGateway, 0)
+                }
+            };
+            write_head(&mut stream, status, len, &[]).await?;
+            return Ok(stream.flush().await?);
+        }
+
+        let file = match self.fetch(&request.path).await {
+            Ok(file) => file,
+            Err(e) => {
+                let status = match e.downcast_ref::<RustupError>() {
+                    Some(RustupError::DownloadNotExists { .. }) => Status::NotFound,
+                    _ => Status::BadGateway,
+                };
+                warn!("{} {}: {e:#}", request.method, request.path);
+                return write_head(&mut stream, status, 0, &[]).await;
+            }
+        };
+
+        let mut body = tokio::fs::File::open(file.path()).await?;
+        let len = body.metadata().await?.len();
+        let (status, start, range) = match request.range_start {
+            Some(start) if start >= len => {
+                return write_head(&mut stream, Status::RangeNotSatisfiable, 0, &[]).await;
+            }
+            Some(start) => (
+                Status::PartialContent,
+                start,
+                Some(format!("bytes {start}-{}/{len}", len - 1)),
+            ),
+            None => (Status::Ok, 0, None),
+        };
+
+        let headers = match &range {
+            Some(range) => vec![("Content-Range", range.as_str())],
+            None => vec![],
+        };
+        write_head(&mut stream, status, len - start, &headers).await?;
+        body.seek(SeekFrom::Start(start)).await?;
+        tokio::io::copy(&mut body, &mut stream).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+
+    /// Returns a local copy of the upstream file at `path`.
+    async fn fetch(&self, path: &str) -> Result<Fetched> {
+        let expected = self.hashes.lock().unwrap().get(path).cloned();
+        let Some(hash) = expected else {
+            let file = self.download(path).await?.0;
+            if is_channel_manifest(path) {
+                self.index_manifest(file.path())?;
+            }
+            info!("forwarded {path}");
+            return Ok(Fetched::Temporary(file));
+        };
+
+        let cached = self.cache_dir.join(&hash);
+        if cached.is_file() {
+            info!("served {path} from the cache");
+            return Ok(Fetched::Cached(cached));
+        }
+
+        let (file, actual) = self.download(path).await?;
+        if actual != hash {
+            return Err(RustupError::ChecksumFailed {
+                url: self.upstream_url(path)?.to_string(),
+                expected: hash,
+                calculated: actual,
+            }
+            .into());
+        }
+        file.persist(&cached)
+            .map_err(|e| e.error)
+            .with_context(|| format!("failed to store '{}'", cached.display()))?;
+        info!("cached {path}");
+        Ok(Fetched::Cached(cached))
+    }
+
+    /// The size of the file at `path`, from the cache or else as reported by
+    /// upstream, without downloading it; `None` if upstream doesn't have it.
+    async fn size(&self, path: &str) -> Result<Option<u64>> {
+        let expected = self.hashes.lock().unwrap().get(path).cloned();
+        if let Some(hash) = expected {
+            if let Ok(metadata) = std::fs::metadata(self.cache_dir.join(hash)) {
+                return Ok(Some(metadata.len()));
+            }
+        }
+
+        match utils::content_length(&self.upstream_url(path)?, &self.process).await {
+            Ok(Some(len)) => Ok(Some(len)),
+            // Upstream doesn't say, so find out the long way.
+            Ok(None) => {
+                let file = self.fetch(path).await?;
+                Ok(Some(std::fs::metadata(file.path())?.len()))
+            }
+            Err(e) => match e.downcast_ref::<download::DownloadError>() {
+                Some(download::DownloadError::FileNotFound)
+                | Some(download::DownloadError::HttpStatus(404)) => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Downloads `path` from upstream into a temporary file, returning it
+    /// along with its SHA-256 hash.
+    async fn download(&self, path: &str) -> Result<(tempfile::NamedTempFile, String)> {
+        let url = self.upstream_url(path)?;
+        let file = tempfile::Builder::new()
+            .prefix("download-")
+            .tempfile_in(&self.cache_dir)
+            .context("failed to create temporary file")?;
+        let mut hasher = Sha256::new();
+        utils::download_file(
+            &url,
+            file.path(),
+            Some(&mut hasher),
+            &|n| debug!("{n}"),
+            &self.process,
+        )
+        .await?;
+        Ok((file, format!("{:x}", hasher.finalize())))
+    }
+
+    /// Records the hashes of all the archives listed in the manifest at `path`.
+    fn index_manifest(&self, path: &Path) -> Result<()> {
+        let manifest = Manifest::parse(&utils::read_file("manifest", path)?)?;
+        let mut hashes = self.hashes.lock().unwrap();
+        for package in manifest.packages.values() {
+            let targets = match &package.targets {
+                PackageTargets::Wildcard(tpkg) => vec![tpkg],
+                PackageTargets::Targeted(tpkgs) => tpkgs.values().collect(),
+            };
+            for bin in targets.into_iter().flat_map(|tpkg| &tpkg.bins) {
+                match self.request_path(&bin.url) {
+                    Some(path) => {
+                        hashes.insert(path, bin.hash.clone());
+                    }
+                    None => debug!("not caching archive outside the dist server: {}", bin.url),
+                }
+            }
+        }
+
+        let mut index = tempfile::NamedTempFile::new_in(&self.cache_dir)
+            .context("failed to create temporary file")?;
+        for (path, hash) in hashes.iter() {
+            writeln!(index, "{hash}  {path}").context("failed to write the cache index")?;
+        }
+        index
+            .persist(self.cache_dir.join(INDEX))
+            .map_err(|e| e.error)
+            .context("failed to store the cache index")?;
+        Ok(())
+    }
+
+    fn upstream_url(&self, path: &str) -> Result<Url> {
+        let url = utils::parse_url(&format!("{}{path}", self.upstream))?;
+        // Parsing resolves dot segments, so make sure the result didn't
+        // leave the upstream root.
+        let root = utils::parse_url(&format!("{}/", self.upstream))?;
+        if !url.as_str().starts_with(root.as_str()) {
+            bail!("'{path}' is outside the dist server");
+        }
+        Ok(url)
+    }
+
+    /// The path clients request `url` at: manifests list archives on the
+    /// official server, which clients replace with their own dist server.
+    fn request_path(&self, url: &str) -> Option<String> {
+        [self.upstream.as_str(), DEFAULT_DIST_SERVER]
+            .iter()
+            .find_map(|root| url.strip_prefix(root))
+            .filter(|path| path.starts_with('/'))
+            .map(str::to_owned)
+    }
+}
+
+enum Fetched {
+    /// Stored in the cache directory.
+    Cached(PathBuf),
+    /// Forwarded from upstream without being cached.
+    Temporary(tempfile::NamedTempFile),
+}
+
+impl Fetched {
+    fn path(&self) -> &Path {
+        match self {
+            Self::Cached(path) => path,
+            Self::Temporary(file) => file.path(),
+        }
+    }
+}
+
+fn is_channel_manifest(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .is_some_and(|name| name.starts_with("channel-rust-") && name.ends_with(".toml"))
+}
@@ -0,0 +1,267 @@
+// Tests of the caching proxy against a Rust distribution server mocked on
+// the file system
+
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+use super::{CacheServer, INDEX};
+use crate::process::TestProcess;
+use crate::test::mock::{dist::*, MockComponentBuilder, MockFile, MockInstallerBuilder};
+
+const ARCHIVE: &str = "/dist/2016-02-01/rustc-nightly-x86_64-unknown-linux-gnu.tar.gz";
+
+fn create_mock_dist_server(path: &Path) -> MockDistServer {
+    let target = "x86_64-unknown-linux-gnu";
+    MockDistServer {
+        path: path.to_owned(),
+        channels: vec![MockChannel {
+            name: "nightly".to_owned(),
+            date: "2016-02-01".to_owned(),
+            packages: vec![
+                MockPackage {
+                    name: "rust",
+                    version: "1.0.0".to_owned(),
+                    targets: vec![MockTargetedPackage {
+                        target: target.to_owned(),
+                        available: true,
+                        components: vec![MockComponent {
+                            name: "rustc".to_owned(),
+                            target: target.to_owned(),
+                            is_extension: false,
+                        }],
+                        installer: MockInstallerBuilder { components: vec![] },
+                    }],
+                },
+                MockPackage {
+                    name: "rustc",
+                    version: "1.0.0".to_owned(),
+                    targets: vec![MockTargetedPackage {
+                        target: target.to_owned(),
+                        available: true,
+                        components: vec![],
+                        installer: MockInstallerBuilder {
+                            components: vec![MockComponentBuilder {
+                                name: "rustc".to_owned(),
+                                files: vec![MockFile::new("bin/rustc", b"rustc")],
+                            }],
+                        },
+                    }],
+                },
+            ],
+            renames: HashMap::new(),
+        }],
+    }
+}
+
+struct Setup {
+    _dir: tempfile::TempDir,
+    dist: PathBuf,
+    cache: PathBuf,
+    server: Arc<CacheServer>,
+    tp: TestProcess,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let dir = tempfile::Builder::new()
+            .prefix("rustup-cache-server")
+            .tempdir()
+            .unwrap();
+        let dist = dir.path().join("dist-server");
+        create_mock_dist_server(&dist).write(&[MockManifestVersion::V2], false, false);
+
+        let tp = TestProcess::default();
+        let cache = dir.path().join("cache");
+        let upstream = Url::from_directory_path(&dist).unwrap();
+        let server = CacheServer::new(upstream.as_str(), cache.clone(), tp.process.clone());
+        Self {
+            _dir: dir,
+            dist,
+            cache,
+            server: Arc::new(server.unwrap()),
+            tp,
+        }
+    }
+
+    fn upstream_archive(&self) -> PathBuf {
+        self.dist.join(ARCHIVE.trim_start_matches('/'))
+    }
+
+    fn cached_files(&self) -> Vec<String> {
+        fs::read_dir(&self.cache)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name != INDEX)
+            .collect()
+    }
+
+    /// Runs `client` against the server, which is stopped once it returns.
+    async fn with_server<F: Future<Output = ()>>(&self, client: impl FnOnce(SocketAddr) -> F) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::select! {
+            res = self.server.clone().serve(listener) => panic!("server stopped: {res:?}"),
+            () = client(addr) => {}
+        }
+    }
+}
+
+/// Makes a request, returning the status code and the body of the response.
+async fn get(addr: SocketAddr, path: &str, headers: &str) -> (u16, Vec<u8>) {
+    request(addr, "GET", path, headers).await
+}
+
+async fn request(addr: SocketAddr, method: &str, path: &str, headers: &str) -> (u16, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("{method} {path} HTTP/1.1\r\nHost: {addr}\r\n{headers}\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8(response[..end].to_vec()).unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, response[end + 4..].to_vec())
+}
+
+#[tokio::test]
+async fn caches_archives_listed_in_manifest() {
+    let setup = &Setup::new();
+    let upstream = fs::read(setup.upstream_archive()).unwrap();
+    let hash = calc_hash(&setup.upstream_archive());
+
+    setup
+        .with_server(|addr| async move {
+            let (status, _) = get(addr, "/dist/channel-rust-nightly.toml", "").await;
+            assert_eq!(status, 200);
+            // Manifests themselves are never cached.
+            assert!(setup.cached_files().is_empty());
+
+            let (status, body) = get(addr, ARCHIVE, "").await;
+            assert_eq!(status, 200);
+            assert_eq!(body, upstream);
+            assert_eq!(setup.cached_files(), [hash]);
+
+            // Served without going upstream from now on.
+            fs::remove_file(setup.upstream_archive()).unwrap();
+            let (status, body) = get(addr, ARCHIVE, "").await;
+            assert_eq!(status, 200);
+            assert_eq!(body, upstream);
+
+            let (status, body) = get(addr, ARCHIVE, "Range: bytes=10-\r\n").await;
+            assert_eq!(status, 206);
+            assert_eq!(body, upstream[10..]);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn rejects_archives_not_matching_manifest() {
+    let setup = &Setup::new();
+
+    setup
+        .with_server(|addr| async move {
+            let (status, _) = get(addr, "/dist/channel-rust-nightly.toml", "").await;
+            assert_eq!(status, 200);
+
+            fs::write(setup.upstream_archive(), b"tampered").unwrap();
+            let (status, _) = get(addr, ARCHIVE, "").await;
+            assert_eq!(status, 502);
+            assert!(setup.cached_files().is_empty());
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn forwards_unlisted_files_uncached() {
+    let setup = &Setup::new();
+
+    setup
+        .with_server(|addr| async move {
+            // Not listed in any manifest seen so far
+            let (status, body) = get(addr, ARCHIVE, "").await;
+            assert_eq!(status, 200);
+            assert_eq!(body, fs::read(setup.upstream_archive()).unwrap());
+            assert!(setup.cached_files().is_empty());
+
+            let (status, _) = get(addr, "/dist/channel-rust-unknown.toml", "").await;
+            assert_eq!(status, 404);
+            let (status, _) = get(addr, "/dist/../secret", "").await;
+            assert_eq!(status, 400);
+            let (status, _) = get(addr, "/dist/%2e%2e/%2E%2E/secret", "").await;
+            assert_eq!(status, 400);
+
+            // Even if a request got that far
+            assert!(setup
+                .server
+                .upstream_url("/dist/%2e%2e/%2e%2e/secret")
+                .is_err());
+            assert!(setup.server.upstream_url("/dist/a/%2e%2e/b").is_ok());
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn serves_cached_archives_after_restart() {
+    let mut setup = Setup::new();
+    let upstream = fs::read(setup.upstream_archive()).unwrap();
+
+    setup
+        .with_server(|addr| async move {
+            get(addr, "/dist/channel-rust-nightly.toml", "").await;
+            get(addr, ARCHIVE, "").await;
+        })
+        .await;
+
+    // A new server on the same cache, which hasn't seen the manifest
+    let upstream_url = Url::from_directory_path(&setup.dist).unwrap();
+    let server = CacheServer::new(
+        upstream_url.as_str(),
+        setup.cache.clone(),
+        setup.tp.process.clone(),
+    );
+    setup.server = Arc::new(server.unwrap());
+    fs::remove_file(setup.upstream_archive()).unwrap();
+
+    let setup = &setup;
+    setup
+        .with_server(|addr| async move {
+            let (status, body) = get(addr, ARCHIVE, "").await;
+            assert_eq!(status, 200);
+            assert_eq!(body, upstream);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn answers_head_without_downloading() {
+    let setup = &Setup::new();
+    let len = fs::metadata(setup.upstream_archive()).unwrap().len();
+
+    setup
+        .with_server(|addr| async move {
+            get(addr, "/dist/channel-rust-nightly.toml", "").await;
+
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let head = format!("HEAD {ARCHIVE} HTTP/1.1\r\nHost: {addr}\r\n\r\n");
+            stream.write_all(head.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+            assert!(response.contains(&format!("Content-Length: {len}\r\n")));
+            assert!(response.ends_with("\r\n\r\n"));
+            assert!(setup.cached_files().is_empty());
+
+            let (status, _) = request(addr, "HEAD", "/dist/channel-rust-unknown.toml", "").await;
+            assert_eq!(status, 404);
+        })
+        .await;
+}
@@ -188,6 +188,14 @@ impl Component {
         }
         Ok(result)
     }
+    /// The disk space taken up by the files of the component.
+    pub(crate) fn disk_usage(&self) -> Result<u64> {
+        let mut total = 0;
+        for part in self.parts()? {
+            total += utils::disk_usage(&self.components.prefix.abs_path(&part.1))?;
+        }
+        Ok(total)
+    }
     pub fn uninstall<'a>(
         &self,
         mut tx: Transaction<'a>,
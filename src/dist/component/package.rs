@@ -4,7 +4,8 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::io::{self, ErrorKind as IOErrorKind, Read};
+use std::fs;
+use std::io::{self, ErrorKind as IOErrorKind, Read, Seek, SeekFrom};
 use std::mem;
 use std::path::{Path, PathBuf};
 
@@ -15,10 +16,12 @@ use tracing::warn;
 use crate::diskio::{get_executor, CompletedIo, Executor, FileBuffer, Item, Kind, IO_CHUNK_SIZE};
 use crate::dist::component::components::*;
 use crate::dist::component::transaction::*;
+use crate::dist::manifest::CompressionKind;
 use crate::dist::temp;
 use crate::errors::*;
 use crate::process::Process;
 use crate::utils::notifications::Notification;
+use crate::utils::units::Unit;
 use crate::utils::utils;
 
 /// The current metadata revision used by rust-installer
@@ -160,6 +163,94 @@ impl<'a> TarPackage<'a> {
             temp_dir,
         ))
     }
+
+    /// Unpacks the `format` archive at `path`, like [`Self::new`] but on a
+    /// thread of its own, so that the other tasks of the runtime, such as
+    /// concurrent downloads, keep going meanwhile.
+    pub(crate) async fn unpack(
+        path: &Path,
+        format: CompressionKind,
+        tmp_cx: &'a temp::Context,
+        notify_handler: &dyn Fn(Notification<'_>),
+        process: &Process,
+    ) -> Result<Self> {
+        let temp_dir = tmp_cx.new_directory()?;
+
+        // Bounded, so that a slow handler slows unpacking down rather than
+        // letting its notifications pile up in memory.
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (path, dir, process) = (path.to_owned(), temp_dir.to_owned(), process.clone());
+        let unpack = tokio::task::spawn_blocking(move || -> Result<()> {
+            let forward = |n: Notification<'_>| {
+                if let Some(event) = UnpackEvent::new(n) {
+                    // The receiver only goes away if unpacking was abandoned
+                    let _ = tx.blocking_send(event);
+                }
+            };
+            let reader = utils::FileReaderWithProgress::new_file(&path, &forward)?;
+            let stream: Box<dyn Read> = match format {
+                CompressionKind::GZip => Box::new(flate2::read::GzDecoder::new(reader)),
+                CompressionKind::XZ => Box::new(xz2::read::XzDecoder::new(reader)),
+                CompressionKind::ZStd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            };
+            let mut archive = tar::Archive::new(stream);
+            unpack_without_first_dir(&mut archive, &dir, Some(&forward), &process)
+                .context("failed to extract package")
+        });
+
+        while let Some(event) = rx.recv().await {
+            event.notify(notify_handler);
+        }
+        unpack.await??;
+
+        Ok(TarPackage(
+            DirectoryPackage::new(temp_dir.to_owned(), false)?,
+            temp_dir,
+        ))
+    }
+}
+
+/// A [`Notification`] sent from the thread unpacking an archive.
+enum UnpackEvent {
+    DownloadContentLengthReceived(u64),
+    DownloadDataReceived(Vec<u8>),
+    DownloadFinished,
+    DownloadPushUnit(Unit),
+    DownloadPopUnit,
+    SetDefaultBufferSize(usize),
+    Error(String),
+}
+
+impl UnpackEvent {
+    fn new(notification: Notification<'_>) -> Option<Self> {
+        Some(match notification {
+            Notification::DownloadContentLengthReceived(len) => {
+                Self::DownloadContentLengthReceived(len)
+            }
+            Notification::DownloadDataReceived(data) => Self::DownloadDataReceived(data.to_vec()),
+            Notification::DownloadFinished => Self::DownloadFinished,
+            Notification::DownloadPushUnit(unit) => Self::DownloadPushUnit(unit),
+            Notification::DownloadPopUnit => Self::DownloadPopUnit,
+            Notification::SetDefaultBufferSize(size) => Self::SetDefaultBufferSize(size),
+            Notification::Error(e) => Self::Error(e),
+            // Nothing else is reported while unpacking
+            _ => return None,
+        })
+    }
+
+    fn notify(&self, notify_handler: &dyn Fn(Notification<'_>)) {
+        notify_handler(match self {
+            Self::DownloadContentLengthReceived(len) => {
+                Notification::DownloadContentLengthReceived(*len)
+            }
+            Self::DownloadDataReceived(data) => Notification::DownloadDataReceived(data),
+            Self::DownloadFinished => Notification::DownloadFinished,
+            Self::DownloadPushUnit(unit) => Notification::DownloadPushUnit(*unit),
+            Self::DownloadPopUnit => Notification::DownloadPopUnit,
+            Self::SetDefaultBufferSize(size) => Notification::SetDefaultBufferSize(*size),
+            Self::Error(e) => Notification::Error(e.clone()),
+        })
+    }
 }
 
 // Probably this should live in diskio but ¯\_(ツ)_/¯
@@ -590,78 +681,90 @@ impl<'a> Package for TarGzPackage<'a> {
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct TarXzPackage<'a>(TarPackage<'a>);
-
-impl<'a> TarXzPackage<'a> {
-    pub(crate) fn new<R: Read>(
-        stream: R,
-        tmp_cx: &'a temp::Context,
-        notify_handler: Option<&'a dyn Fn(Notification<'_>)>,
-        process: &Process,
-    ) -> Result<Self> {
-        let stream = xz2::read::XzDecoder::new(stream);
-        Ok(TarXzPackage(TarPackage::new(
-            stream,
-            tmp_cx,
-            notify_handler,
-            process,
-        )?))
+/// Reads the size of the contents of the compressed archive at `path` from
+/// its headers, if they record it.
+pub(crate) fn unpacked_size(format: CompressionKind, path: &Path) -> io::Result<Option<u64>> {
+    let mut file = fs::File::open(path)?;
+    match format {
+        CompressionKind::GZip => gz_unpacked_size(&mut file),
+        CompressionKind::XZ => xz_unpacked_size(&mut file),
+        CompressionKind::ZStd => {
+            // A frame header is at most 18 bytes long.
+            let mut header = Vec::with_capacity(18);
+            (&mut file).take(18).read_to_end(&mut header)?;
+            Ok(zstd::zstd_safe::get_frame_content_size(&header)
+                .ok()
+                .flatten())
+        }
     }
 }
 
-impl<'a> Package for TarXzPackage<'a> {
-    fn contains(&self, component: &str, short_name: Option<&str>) -> bool {
-        self.0.contains(component, short_name)
-    }
-    fn install<'b>(
-        &self,
-        target: &Components,
-        component: &str,
-        short_name: Option<&str>,
-        tx: Transaction<'b>,
-    ) -> Result<Transaction<'b>> {
-        self.0.install(target, component, short_name, tx)
-    }
-    fn components(&self) -> Vec<String> {
-        self.0.components()
+/// The gzip trailer records the size of the contents modulo 2^32, so this
+/// gives up on any that seem to have wrapped around.
+fn gz_unpacked_size(file: &mut fs::File) -> io::Result<Option<u64>> {
+    let archive_size = file.seek(SeekFrom::End(0))?;
+    if archive_size < 4 {
+        return Ok(None);
     }
+    let mut isize = [0; 4];
+    file.seek(SeekFrom::End(-4))?;
+    file.read_exact(&mut isize)?;
+    let size = u64::from(u32::from_le_bytes(isize));
+    Ok((size >= archive_size).then_some(size))
 }
 
-#[derive(Debug)]
-pub(crate) struct TarZStdPackage<'a>(TarPackage<'a>);
+/// Sums the uncompressed sizes of the blocks listed in the index of the
+/// (last) xz stream.
+fn xz_unpacked_size(file: &mut fs::File) -> io::Result<Option<u64>> {
+    const FOOTER_SIZE: u64 = 12;
 
-impl<'a> TarZStdPackage<'a> {
-    pub(crate) fn new<R: Read>(
-        stream: R,
-        tmp_cx: &'a temp::Context,
-        notify_handler: Option<&'a dyn Fn(Notification<'_>)>,
-        process: &Process,
-    ) -> Result<Self> {
-        let stream = zstd::stream::read::Decoder::new(stream)?;
-        Ok(TarZStdPackage(TarPackage::new(
-            stream,
-            tmp_cx,
-            notify_handler,
-            process,
-        )?))
+    let archive_size = file.seek(SeekFrom::End(0))?;
+    if archive_size < FOOTER_SIZE {
+        return Ok(None);
+    }
+    let mut footer = [0; FOOTER_SIZE as usize];
+    file.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+    file.read_exact(&mut footer)?;
+    if &footer[10..] != b"YZ" {
+        return Ok(None);
     }
-}
 
-impl<'a> Package for TarZStdPackage<'a> {
-    fn contains(&self, component: &str, short_name: Option<&str>) -> bool {
-        self.0.contains(component, short_name)
+    let backward_size = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+    let index_size = (u64::from(backward_size) + 1) * 4;
+    if index_size > archive_size - FOOTER_SIZE {
+        return Ok(None);
     }
-    fn install<'b>(
-        &self,
-        target: &Components,
-        component: &str,
-        short_name: Option<&str>,
-        tx: Transaction<'b>,
-    ) -> Result<Transaction<'b>> {
-        self.0.install(target, component, short_name, tx)
+    let mut index = vec![0; index_size as usize];
+    file.seek(SeekFrom::End(-((FOOTER_SIZE + index_size) as i64)))?;
+    file.read_exact(&mut index)?;
+
+    fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+        let mut value = 0;
+        for i in 0..9 {
+            let byte = bytes.next()?;
+            value |= u64::from(byte & 0x7f) << (i * 7);
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
     }
-    fn components(&self) -> Vec<String> {
-        self.0.components()
+
+    let mut bytes = index.into_iter();
+    if bytes.next() != Some(0) {
+        return Ok(None);
+    }
+    let Some(records) = read_varint(&mut bytes) else {
+        return Ok(None);
+    };
+    let mut total: u64 = 0;
+    for _ in 0..records {
+        let (Some(_unpadded_size), Some(uncompressed_size)) =
+            (read_varint(&mut bytes), read_varint(&mut bytes))
+        else {
+            return Ok(None);
+        };
+        total = total.saturating_add(uncompressed_size);
     }
+    Ok(Some(total))
 }
@@ -803,3 +803,49 @@ fn rollback_failure_keeps_going() {
 #[test]
 #[ignore]
 fn intermediate_dir_rollback() {}
+
+#[test]
+fn unpacked_size_from_archive_headers() {
+    use crate::dist::component::unpacked_size;
+    use crate::dist::manifest::CompressionKind;
+
+    let dir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+    let contents: Vec<u8> = (0..100_000u32)
+        .flat_map(|i| (i % 7).to_le_bytes())
+        .collect();
+
+    let gz_path = dir.path().join("c.tar.gz");
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(&contents).unwrap();
+    fs::write(&gz_path, gz.finish().unwrap()).unwrap();
+
+    let xz_path = dir.path().join("c.tar.xz");
+    let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+    xz.write_all(&contents).unwrap();
+    fs::write(&xz_path, xz.finish().unwrap()).unwrap();
+
+    let zst_path = dir.path().join("c.tar.zst");
+    fs::write(&zst_path, zstd::bulk::compress(&contents, 0).unwrap()).unwrap();
+
+    let expected = Some(contents.len() as u64);
+    assert_eq!(
+        unpacked_size(CompressionKind::GZip, &gz_path).unwrap(),
+        expected
+    );
+    assert_eq!(
+        unpacked_size(CompressionKind::XZ, &xz_path).unwrap(),
+        expected
+    );
+    assert_eq!(
+        unpacked_size(CompressionKind::ZStd, &zst_path).unwrap(),
+        expected
+    );
+
+    // Headers that don't record the size aren't trusted
+    let garbage_path = dir.path().join("garbage");
+    fs::write(&garbage_path, b"not an archive").unwrap();
+    assert_eq!(
+        unpacked_size(CompressionKind::XZ, &garbage_path).unwrap(),
+        None
+    );
+}
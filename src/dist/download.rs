@@ -1,30 +1,93 @@
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::{self, BufReader, Read, Write};
 use std::ops;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use sha2::{Digest, Sha256};
 use url::Url;
 
+use crate::dist::http_cache::HttpCache;
+use crate::dist::manifest::{Patch, PatchFormat};
 use crate::dist::notifications::*;
 use crate::dist::temp;
 use crate::errors::*;
 use crate::process::Process;
 use crate::utils::utils;
 
+/// The length of the hashes stored in update hash files by older versions.
 const UPDATE_HASH_LEN: usize = 20;
 
+/// The log of the largest archive patches are applied to.
+///
+/// Patching keeps the whole archive being patched in memory, and the zstd
+/// window has to cover it, so this bounds the memory that takes. It is the
+/// window `zstd --long` uses; larger archives are downloaded in full.
+const PATCH_WINDOW_LOG: u32 = 27;
+
 #[derive(Copy, Clone)]
 pub struct DownloadCfg<'a> {
     pub dist_root: &'a str,
     pub tmp_cx: &'a temp::Context,
     pub download_dir: &'a PathBuf,
+    /// Where to keep channel manifests between updates, if anywhere.
+    pub(crate) http_cache: Option<&'a HttpCache>,
     pub notify_handler: &'a dyn Fn(Notification<'_>),
     pub process: &'a Process,
 }
 
+/// Archives in download directories that updates in this process are using.
+/// Toolchains are updated concurrently and can share archives, such as
+/// `rust-src` between the targets of a channel, so an archive has to be
+/// downloaded only once, and not cleaned up while another update needs it.
+static CLAIMS: Mutex<BTreeMap<PathBuf, Users>> = Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct Users {
+    count: usize,
+    /// Held while the archive is downloaded or checked.
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// A claim on an archive in a download directory, released when dropped.
+struct Claim {
+    path: PathBuf,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Claim {
+    fn new(path: &Path) -> Self {
+        let mut claims = CLAIMS.lock().unwrap();
+        let users = claims.entry(path.to_owned()).or_default();
+        users.count += 1;
+        Self {
+            path: path.to_owned(),
+            lock: users.lock.clone(),
+        }
+    }
+
+    fn is_claimed(path: &Path) -> bool {
+        CLAIMS.lock().unwrap().contains_key(path)
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let mut claims = CLAIMS.lock().unwrap();
+        if let Some(users) = claims.get_mut(&self.path) {
+            users.count -= 1;
+            if users.count == 0 {
+                claims.remove(&self.path);
+            }
+        }
+    }
+}
+
 pub(crate) struct File {
     path: PathBuf,
+    _claim: Claim,
 }
 
 impl ops::Deref for File {
@@ -36,6 +99,34 @@ impl ops::Deref for File {
 }
 
 impl<'a> DownloadCfg<'a> {
+    /// Returns the size of the archive with the given hash if it is already in
+    /// `self.download_dir`.
+    pub(crate) fn downloaded_size(&self, hash: &str) -> Option<u64> {
+        match fs::metadata(self.download_dir.join(hash)) {
+            Ok(metadata) if metadata.is_file() => Some(metadata.len()),
+            _ => None,
+        }
+    }
+
+    /// Returns the size of the archive with the given hash at `url`, and
+    /// whether it is already in `self.download_dir`. The server is only asked
+    /// for the size when the archive isn't there and `listed` doesn't give it.
+    /// Nothing is downloaded.
+    pub(crate) async fn archive_size(
+        &self,
+        url: &Url,
+        hash: &str,
+        listed: Option<u64>,
+    ) -> Result<(Option<u64>, bool)> {
+        if let Some(size) = self.downloaded_size(hash) {
+            return Ok((Some(size), true));
+        }
+        match listed {
+            Some(size) => Ok((Some(size), false)),
+            None => Ok((utils::content_length(url, self.process).await?, false)),
+        }
+    }
+
     /// Downloads a file and validates its hash. Resumes interrupted downloads.
     /// Partial downloads are stored in `self.download_dir`, keyed by hash. If the
     /// target file already exists, then the hash is checked and it is returned
@@ -47,13 +138,19 @@ impl<'a> DownloadCfg<'a> {
             &self.notify_handler,
         )?;
         let target_file = self.download_dir.join(Path::new(hash));
+        let claim = Claim::new(&target_file);
+        let lock = claim.lock.clone();
+        let _guard = lock.lock().await;
 
         if target_file.exists() {
             let cached_result = file_hash(&target_file, self.notify_handler)?;
             if hash == cached_result {
                 (self.notify_handler)(Notification::FileAlreadyDownloaded);
                 (self.notify_handler)(Notification::ChecksumValid(url.as_ref()));
-                return Ok(File { path: target_file });
+                return Ok(File {
+                    path: target_file,
+                    _claim: claim,
+                });
             } else {
                 (self.notify_handler)(Notification::CachedFileChecksumFailed);
                 fs::remove_file(&target_file).context("cleaning up previous download")?;
@@ -116,14 +213,102 @@ impl<'a> DownloadCfg<'a> {
                 self.notify_handler,
                 self.process,
             )?;
-            Ok(File { path: target_file })
+            Ok(File {
+                path: target_file,
+                _claim: claim,
+            })
+        }
+    }
+
+    /// Reconstructs the archive with the given `hash` by applying one of
+    /// `patches` to an archive kept in `self.download_dir` by a previous update.
+    /// Returns `None` if no patch could be applied, in which case the full
+    /// archive has to be downloaded.
+    pub(crate) async fn download_patched(&self, patches: &[Patch], hash: &str) -> Option<File> {
+        let claim = Claim::new(&self.download_dir.join(hash));
+        let lock = claim.lock.clone();
+        let _guard = lock.lock().await;
+        for patch in patches {
+            let base = self.download_dir.join(&patch.from);
+            if patch.to != hash || patch.format == PatchFormat::Unsupported || !base.is_file() {
+                continue;
+            }
+
+            (self.notify_handler)(Notification::DownloadingPatch(&patch.url));
+            match self.apply_patch(patch, &base).await {
+                Ok(path) => {
+                    return Some(File {
+                        path,
+                        _claim: claim,
+                    })
+                }
+                Err(e) => (self.notify_handler)(Notification::PatchFailed(&patch.url, &e)),
+            }
+        }
+        None
+    }
+
+    async fn apply_patch(&self, patch: &Patch, base: &Path) -> Result<PathBuf> {
+        let url = utils::parse_url(&patch.url)?;
+        let patch_file = self.tmp_cx.new_file()?;
+        let mut hasher = Sha256::new();
+        utils::download_file(
+            &url,
+            &patch_file,
+            Some(&mut hasher),
+            &|n| (self.notify_handler)(n.into()),
+            self.process,
+        )
+        .await?;
+        let actual_hash = format!("{:x}", hasher.finalize());
+        if actual_hash != patch.hash {
+            return Err(RustupError::ChecksumFailed {
+                url: patch.url.clone(),
+                expected: patch.hash.clone(),
+                calculated: actual_hash,
+            }
+            .into());
+        }
+
+        // Not named like a partial download, so that a failure here can't be
+        // mistaken for one when falling back to the full archive.
+        let target_file = self.download_dir.join(&patch.to);
+        let patched_file = self.download_dir.join(patch.to.clone() + ".patched");
+        let res = patch_archive(base, &patch_file, &patched_file);
+        let actual_hash = match res {
+            Ok(actual_hash) => actual_hash,
+            Err(e) => {
+                let _ = fs::remove_file(&patched_file);
+                return Err(e);
+            }
+        };
+        if actual_hash != patch.to {
+            fs::remove_file(&patched_file).context("cleaning up patched archive")?;
+            return Err(RustupError::ChecksumFailed {
+                url: patch.url.clone(),
+                expected: patch.to.clone(),
+                calculated: actual_hash,
+            }
+            .into());
         }
+
+        (self.notify_handler)(Notification::ChecksumValid(&patch.url));
+        utils::rename(
+            "patched",
+            &patched_file,
+            &target_file,
+            self.notify_handler,
+            self.process,
+        )?;
+        Ok(target_file)
     }
 
+    /// Removes the archives with the given hashes from `self.download_dir`,
+    /// except those another update in this process is using.
     pub(crate) fn clean(&self, hashes: &[String]) -> Result<()> {
         for hash in hashes.iter() {
             let used_file = self.download_dir.join(hash);
-            if self.download_dir.join(&used_file).exists() {
+            if used_file.exists() && !Claim::is_claimed(&used_file) {
                 fs::remove_file(used_file).context("cleaning up cached downloads")?;
             }
         }
@@ -132,6 +317,13 @@ impl<'a> DownloadCfg<'a> {
 
     async fn download_hash(&self, url: &str) -> Result<String> {
         let hash_url = utils::parse_url(&(url.to_owned() + ".sha256"))?;
+        if let Some(cache) = self.http_cache {
+            let hash_file = cache
+                .fetch(&hash_url, self.notify_handler, self.process)
+                .await?;
+            return utils::read_file("hash", &hash_file).map(|s| s[0..64].to_owned());
+        }
+
         let hash_file = self.tmp_cx.new_file()?;
 
         utils::download_file(
@@ -146,9 +338,37 @@ impl<'a> DownloadCfg<'a> {
         utils::read_file("hash", &hash_file).map(|s| s[0..64].to_owned())
     }
 
+    /// Copies the cached up to date file at `url` to `file`, returning its hash.
+    /// A cached copy not matching `hash` is downloaded again, as it may have
+    /// been served by a server in the middle of an update.
+    async fn download_cached(
+        &self,
+        cache: &HttpCache,
+        url: &Url,
+        file: &Path,
+        hash: &str,
+    ) -> Result<String> {
+        let cached_hash = |path: &Path| -> Result<String> {
+            let data = fs::read(path).context("reading cached file")?;
+            Ok(format!("{:x}", Sha256::digest(data)))
+        };
+
+        let mut cached = cache.fetch(url, self.notify_handler, self.process).await?;
+        let mut actual_hash = cached_hash(&cached)?;
+        if actual_hash != hash {
+            cache.remove(url)?;
+            cached = cache.fetch(url, self.notify_handler, self.process).await?;
+            actual_hash = cached_hash(&cached)?;
+        }
+        utils::copy_file(&cached, file)?;
+        Ok(actual_hash)
+    }
+
     /// Downloads a file, sourcing its hash from the same url with a `.sha256` suffix.
     /// If `update_hash` is present, then that will be compared to the downloaded hash,
     /// and if they match, the download is skipped.
+    /// When there is an HTTP cache, both are only downloaded if they changed
+    /// since they were last cached.
     /// Verifies the signature found at the same url with a `.asc` suffix, and prints a
     /// warning when the signature does not verify, or is not found.
     pub(crate) async fn download_and_check(
@@ -158,12 +378,12 @@ impl<'a> DownloadCfg<'a> {
         ext: &str,
     ) -> Result<Option<(temp::File<'a>, String)>> {
         let hash = self.download_hash(url_str).await?;
-        let partial_hash: String = hash.chars().take(UPDATE_HASH_LEN).collect();
 
         if let Some(hash_file) = update_hash {
             if utils::is_file(hash_file) {
                 if let Ok(contents) = utils::read_file("update hash", hash_file) {
-                    if contents == partial_hash {
+                    // Older versions only stored a prefix of the hash.
+                    if contents.len() >= UPDATE_HASH_LEN && hash.starts_with(&contents) {
                         // Skip download, update hash matches
                         return Ok(None);
                     }
@@ -178,16 +398,21 @@ impl<'a> DownloadCfg<'a> {
         let url = utils::parse_url(url_str)?;
         let file = self.tmp_cx.new_file_with_ext("", ext)?;
 
-        let mut hasher = Sha256::new();
-        utils::download_file(
-            &url,
-            &file,
-            Some(&mut hasher),
-            &|n| (self.notify_handler)(n.into()),
-            self.process,
-        )
-        .await?;
-        let actual_hash = format!("{:x}", hasher.finalize());
+        let actual_hash = match self.http_cache {
+            Some(cache) => self.download_cached(cache, &url, &file, &hash).await?,
+            None => {
+                let mut hasher = Sha256::new();
+                utils::download_file(
+                    &url,
+                    &file,
+                    Some(&mut hasher),
+                    &|n| (self.notify_handler)(n.into()),
+                    self.process,
+                )
+                .await?;
+                format!("{:x}", hasher.finalize())
+            }
+        };
 
         if hash != actual_hash {
             // Incorrect hash
@@ -201,8 +426,40 @@ impl<'a> DownloadCfg<'a> {
             (self.notify_handler)(Notification::ChecksumValid(url_str));
         }
 
-        Ok(Some((file, partial_hash)))
+        Ok(Some((file, hash)))
+    }
+}
+
+/// Writes the result of applying the zstd `patch` to `base` to `dest`,
+/// returning its hash.
+fn patch_archive(base: &Path, patch: &Path, dest: &Path) -> Result<String> {
+    let mut base = fs::File::open(base).context("opening archive to patch")?;
+    let size = base.metadata().context("reading archive to patch")?.len();
+    if size > 1 << PATCH_WINDOW_LOG {
+        bail!("archive of {size} bytes is too large to patch");
+    }
+    let mut prefix = Vec::with_capacity(size as usize);
+    base.read_to_end(&mut prefix)
+        .context("reading archive to patch")?;
+    let patch = fs::File::open(patch).context("opening patch")?;
+    let mut decoder = zstd::stream::read::Decoder::with_ref_prefix(BufReader::new(patch), &prefix)?;
+    // Patches needing a larger window than that are refused rather than
+    // allocating it.
+    decoder.window_log_max(PATCH_WINDOW_LOG)?;
+
+    let mut dest = io::BufWriter::new(fs::File::create(dest).context("creating patched archive")?);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 32768];
+    loop {
+        let n = decoder.read(&mut buf).context("applying patch")?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        dest.write_all(&buf[..n])?;
     }
+    dest.flush()?;
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
 fn file_hash(path: &Path, notify_handler: &dyn Fn(Notification<'_>)) -> Result<String> {
@@ -222,3 +479,26 @@ fn file_hash(path: &Path, notify_handler: &dyn Fn(Notification<'_>)) -> Result<S
 
     Ok(format!("{:x}", hasher.finalize()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_to_patch_large_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        // Sparse, so that it takes no space to make.
+        fs::File::create(&base)
+            .unwrap()
+            .set_len((1 << PATCH_WINDOW_LOG) + 1)
+            .unwrap();
+        let patch = dir.path().join("patch");
+        fs::write(&patch, "").unwrap();
+        let dest = dir.path().join("dest");
+
+        let err = patch_archive(&base, &patch, &dest).unwrap_err();
+        assert!(err.to_string().contains("too large to patch"));
+        assert!(!dest.exists());
+    }
+}
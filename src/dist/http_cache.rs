@@ -0,0 +1,317 @@
+//! A persistent cache of the small files rustup checks the distribution
+//! server for on every update, i.e. channel manifests and their hashes.
+//!
+//! The `ETag` and `Last-Modified` headers each file was served with are kept
+//! alongside it, so that checking it for changes only takes a conditional
+//! request, answered with `304 Not Modified` while the cached copy is current.
+//!
+//! Files unused for [`MAX_AGE`] are evicted, as are the least recently used
+//! ones once the cache outgrows [`MAX_SIZE`], so that the manifests of dated
+//! toolchains that are never updated again don't pile up.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use download::{Outcome, Validators};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::dist::Notification;
+use crate::process::Process;
+use crate::utils::utils;
+
+/// Records the dist server the cache was filled from.
+const DIST_SERVER_FILE: &str = "dist-server";
+
+/// How long a cached file is kept after it was last used.
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The size the cached files are kept under.
+const MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub(crate) struct HttpCache {
+    dir: PathBuf,
+    dist_server: String,
+}
+
+/// What is known about the cached copy of a file.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Entry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl HttpCache {
+    pub(crate) fn new(dir: PathBuf, dist_server: &str) -> Self {
+        Self {
+            dir,
+            dist_server: dist_server.to_owned(),
+        }
+    }
+
+    /// Returns the path of an up to date copy of the file at `url`, only
+    /// downloading it if it has changed since it was last cached.
+    pub(crate) async fn fetch(
+        &self,
+        url: &Url,
+        notify_handler: &dyn Fn(Notification<'_>),
+        process: &Process,
+    ) -> Result<PathBuf> {
+        self.prepare(notify_handler)?;
+        let (body, entry) = self.paths(url);
+        let cached = match self.read_entry(url) {
+            Some(entry) if body.is_file() => Validators {
+                etag: entry.etag,
+                last_modified: entry.last_modified,
+            },
+            _ => Validators::default(),
+        };
+
+        let file = tempfile::Builder::new()
+            .prefix("download-")
+            .tempfile_in(&self.dir)
+            .context("failed to create temporary file")?;
+        let outcome = utils::download_file_if_modified(
+            url,
+            file.path(),
+            &cached,
+            &|n| notify_handler(n.into()),
+            process,
+        )
+        .await?;
+
+        let validators = match outcome {
+            Outcome::NotModified => {
+                notify_handler(Notification::FileNotModified(url.as_str()));
+                // Its age is that of its last use
+                let _ = fs::File::options()
+                    .write(true)
+                    .open(&body)
+                    .and_then(|f| f.set_modified(SystemTime::now()));
+                return Ok(body);
+            }
+            Outcome::Downloaded(validators) => validators,
+        };
+
+        // The entry describes the body it was stored with, so it must not
+        // outlive it.
+        remove_if_exists(&entry)?;
+        file.persist(&body)
+            .map_err(|e| e.error)
+            .with_context(|| format!("failed to store '{}'", body.display()))?;
+        if !validators.is_empty() {
+            let entry_data = toml::to_string(&Entry {
+                url: url.to_string(),
+                etag: validators.etag,
+                last_modified: validators.last_modified,
+            })?;
+            utils::write_file("http cache entry", &entry, &entry_data)?;
+        }
+        self.evict(MAX_AGE, MAX_SIZE)
+            .context("failed to evict old files from the http cache")?;
+        Ok(body)
+    }
+
+    /// Removes the cached files last used over `max_age` ago, and then the
+    /// least recently used ones until the rest fit in `max_size`.
+    fn evict(&self, max_age: Duration, max_size: u64) -> Result<()> {
+        let mut bodies = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            // Only the bodies are named by their key alone
+            if name.len() != 64 || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            bodies.push((metadata.modified()?, metadata.len(), path));
+        }
+        bodies.sort_by_key(|&(modified, ..)| std::cmp::Reverse(modified));
+
+        let now = SystemTime::now();
+        let mut size: u64 = 0;
+        for (modified, len, body) in bodies {
+            size = size.saturating_add(len);
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age > max_age || size > max_size {
+                remove_if_exists(&body.with_extension("toml"))?;
+                remove_if_exists(&body)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the cached copy of `url`, so that it is downloaded afresh.
+    pub(crate) fn remove(&self, url: &Url) -> Result<()> {
+        let (body, entry) = self.paths(url);
+        remove_if_exists(&entry)?;
+        remove_if_exists(&body)
+    }
+
+    /// Creates the cache directory, emptying it first if it was filled from
+    /// another dist server: none of its entries would be requested again.
+    fn prepare(&self, notify_handler: &dyn Fn(Notification<'_>)) -> Result<()> {
+        let marker = self.dir.join(DIST_SERVER_FILE);
+        if self.dir.exists() {
+            match fs::read_to_string(&marker) {
+                Ok(server) if server == self.dist_server => return Ok(()),
+                _ => utils::remove_dir("http cache", &self.dir, notify_handler)?,
+            }
+        }
+        utils::ensure_dir_exists("http cache", &self.dir, notify_handler)?;
+        utils::write_file("http cache marker", &marker, &self.dist_server)
+    }
+
+    fn read_entry(&self, url: &Url) -> Option<Entry> {
+        let data = fs::read_to_string(self.paths(url).1).ok()?;
+        toml::from_str(&data).ok()
+    }
+
+    /// The paths of the cached copy of `url` and of its entry.
+    fn paths(&self, url: &Url) -> (PathBuf, PathBuf) {
+        let key = format!("{:x}", Sha256::digest(url.as_str()));
+        let body = self.dir.join(&key);
+        (body, self.dir.join(key + ".toml"))
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("failed to remove '{}'", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::process::TestProcess;
+
+    const ETAG: &str = "\"v1\"";
+
+    /// Serves `body` with an `ETag`, counting the responses that include it.
+    async fn serve(body: &'static str, served: Arc<AtomicUsize>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/dist/channel-rust-stable.toml",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8(request).unwrap().to_ascii_lowercase();
+                let response = if request.contains(&format!("if-none-match: {ETAG}")) {
+                    "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_owned()
+                } else {
+                    served.fetch_add(1, Ordering::SeqCst);
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: {ETAG}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn only_downloads_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let tp = TestProcess::default();
+        let served = Arc::new(AtomicUsize::new(0));
+        let url = serve("manifest", served.clone()).await;
+        let notify = |_: Notification<'_>| {};
+
+        let cache = HttpCache::new(dir.path().join("http-cache"), "https://example.com");
+        let body = cache.fetch(&url, &notify, &tp.process).await.unwrap();
+        assert_eq!(fs::read_to_string(&body).unwrap(), "manifest");
+        assert_eq!(served.load(Ordering::SeqCst), 1);
+
+        let body = cache.fetch(&url, &notify, &tp.process).await.unwrap();
+        assert_eq!(fs::read_to_string(&body).unwrap(), "manifest");
+        assert_eq!(served.load(Ordering::SeqCst), 1);
+
+        cache.remove(&url).unwrap();
+        cache.fetch(&url, &notify, &tp.process).await.unwrap();
+        assert_eq!(served.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn evicts_old_and_least_recently_used_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(dir.path().join("http-cache"), "https://example.com");
+        cache.prepare(&|_| {}).unwrap();
+
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let cached = |name: &str, days: u32| {
+            let url = Url::parse(&format!("https://example.com/dist/{name}")).unwrap();
+            let (body, entry) = cache.paths(&url);
+            fs::write(&entry, "").unwrap();
+            fs::write(&body, [0; 100]).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&body)
+                .and_then(|f| f.set_modified(now - day * days))
+                .unwrap();
+            (body, entry)
+        };
+        let new = cached("new.toml", 0);
+        let recent = cached("recent.toml", 1);
+        let older = cached("older.toml", 2);
+        let stale = cached("stale.toml", 10);
+
+        cache.evict(day * 5, 250).unwrap();
+        for (body, entry) in [&new, &recent] {
+            assert!(body.exists() && entry.exists());
+        }
+        for (body, entry) in [&older, &stale] {
+            assert!(!body.exists() && !entry.exists());
+        }
+        assert!(dir
+            .path()
+            .join("http-cache")
+            .join(DIST_SERVER_FILE)
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn changing_dist_server_empties_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let tp = TestProcess::default();
+        let served = Arc::new(AtomicUsize::new(0));
+        let url = serve("manifest", served.clone()).await;
+        let notify = |_: Notification<'_>| {};
+
+        let cache = HttpCache::new(dir.path().join("http-cache"), "https://example.com");
+        let body = cache.fetch(&url, &notify, &tp.process).await.unwrap();
+
+        let cache = HttpCache::new(dir.path().join("http-cache"), "https://example.org");
+        cache.prepare(&notify).unwrap();
+        assert!(!body.exists());
+        cache.fetch(&url, &notify, &tp.process).await.unwrap();
+        assert_eq!(served.load(Ordering::SeqCst), 2);
+    }
+}
@@ -102,6 +102,8 @@ pub struct TargetedPackage {
     #[serde(default)]
     pub bins: Vec<HashedBinary>,
     pub components: Vec<Component>,
+    /// Patches producing `bins` from the archives of earlier releases.
+    pub patches: Vec<Patch>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -113,8 +115,16 @@ struct Target {
     xz_hash: Option<String>,
     zst_url: Option<String>,
     zst_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    xz_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zst_size: Option<u64>,
     components: Option<Vec<Component>>,
     extensions: Option<Vec<Component>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    patches: Vec<Patch>,
 }
 
 impl From<Target> for TargetedPackage {
@@ -129,7 +139,11 @@ impl From<Target> for TargetedPackage {
 
         let mut bins = Vec::new();
         if !target.available {
-            return Self { bins, components };
+            return Self {
+                bins,
+                components,
+                patches: Vec::new(),
+            };
         }
 
         if let (Some(url), Some(hash)) = (target.zst_url, target.zst_hash) {
@@ -137,6 +151,7 @@ impl From<Target> for TargetedPackage {
                 url,
                 hash,
                 compression: CompressionKind::ZStd,
+                size: target.zst_size,
             });
         }
 
@@ -145,6 +160,7 @@ impl From<Target> for TargetedPackage {
                 url,
                 hash,
                 compression: CompressionKind::XZ,
+                size: target.xz_size,
             });
         }
 
@@ -153,32 +169,40 @@ impl From<Target> for TargetedPackage {
                 url,
                 hash,
                 compression: CompressionKind::GZip,
+                size: target.size,
             });
         }
 
-        Self { bins, components }
+        Self {
+            bins,
+            components,
+            patches: target.patches,
+        }
     }
 }
 
 impl From<TargetedPackage> for Target {
     fn from(tpkg: TargetedPackage) -> Self {
-        let (mut url, mut hash) = (None, None);
-        let (mut xz_url, mut xz_hash) = (None, None);
-        let (mut zst_url, mut zst_hash) = (None, None);
+        let (mut url, mut hash, mut size) = (None, None, None);
+        let (mut xz_url, mut xz_hash, mut xz_size) = (None, None, None);
+        let (mut zst_url, mut zst_hash, mut zst_size) = (None, None, None);
         let available = !tpkg.bins.is_empty();
         for bin in tpkg.bins {
             match bin.compression {
                 CompressionKind::GZip => {
                     url = Some(bin.url);
                     hash = Some(bin.hash);
+                    size = bin.size;
                 }
                 CompressionKind::XZ => {
                     xz_url = Some(bin.url);
                     xz_hash = Some(bin.hash);
+                    xz_size = bin.size;
                 }
                 CompressionKind::ZStd => {
                     zst_url = Some(bin.url);
                     zst_hash = Some(bin.hash);
+                    zst_size = bin.size;
                 }
             }
         }
@@ -201,8 +225,12 @@ impl From<TargetedPackage> for Target {
             xz_hash,
             zst_url,
             zst_hash,
+            size,
+            xz_size,
+            zst_size,
             components: Some(components),
             extensions: Some(extensions),
+            patches: tpkg.patches,
         }
     }
 }
@@ -219,6 +247,31 @@ pub struct HashedBinary {
     pub url: String,
     pub hash: String,
     pub compression: CompressionKind,
+    /// The size of the archive in bytes, if the manifest lists it.
+    pub size: Option<u64>,
+}
+
+/// A binary patch turning a previously downloaded archive into a newer one.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Patch {
+    /// The hash of the archive the patch applies to.
+    pub from: String,
+    /// The hash of the archive it produces, one of the `bins`.
+    pub to: String,
+    pub format: PatchFormat,
+    pub url: String,
+    pub hash: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PatchFormat {
+    /// Zstandard compression using the old archive as a reference prefix,
+    /// as produced by `zstd --patch-from`.
+    Zstd,
+    /// A format this version of rustup can't apply.
+    #[serde(other)]
+    Unsupported,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Ord, PartialOrd, Serialize)]
@@ -628,7 +681,7 @@ impl fmt::Display for ManifestVersion {
 
 #[cfg(test)]
 mod tests {
-    use crate::dist::manifest::Manifest;
+    use crate::dist::manifest::{Manifest, PatchFormat};
     use crate::dist::TargetTriple;
     use crate::RustupError;
 
@@ -684,6 +737,39 @@ mod tests {
         assert_eq!(manifest.reverse_renames["cargo"], "cargo-old");
     }
 
+    #[test]
+    fn parse_patches() {
+        let x86_64_unknown_linux_gnu = TargetTriple::new("x86_64-unknown-linux-gnu");
+        let patches = r#"
+[[pkg.rustc.target.x86_64-unknown-linux-gnu.patches]]
+from = "old"
+to = "new"
+format = "zstd"
+url = "example.com/rustc.patch.zst"
+hash = "..."
+
+[[pkg.rustc.target.x86_64-unknown-linux-gnu.patches]]
+from = "old"
+to = "new"
+format = "bsdiff"
+url = "example.com/rustc.bsdiff"
+hash = "..."
+"#;
+        let manifest = Manifest::parse(&(EXAMPLE.to_owned() + patches)).unwrap();
+        let tpkg = manifest
+            .get_package("rustc")
+            .unwrap()
+            .get_target(Some(&x86_64_unknown_linux_gnu))
+            .unwrap();
+        assert_eq!(tpkg.patches.len(), 2);
+        assert_eq!(tpkg.patches[0].format, PatchFormat::Zstd);
+        assert_eq!(tpkg.patches[0].url, "example.com/rustc.patch.zst");
+        assert_eq!(tpkg.patches[1].format, PatchFormat::Unsupported);
+
+        let serialized = manifest.clone().stringify().unwrap();
+        assert_eq!(Manifest::parse(&serialized).unwrap(), manifest);
+    }
+
     #[test]
     fn parse_round_trip() {
         let original = Manifest::parse(EXAMPLE).unwrap();
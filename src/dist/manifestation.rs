@@ -10,11 +10,11 @@ use anyhow::{anyhow, bail, Context, Result};
 use tokio_retry::{strategy::FixedInterval, RetryIf};
 
 use crate::dist::component::{
-    Components, Package, TarGzPackage, TarXzPackage, TarZStdPackage, Transaction,
+    unpacked_size, Components, Package, TarGzPackage, TarPackage, Transaction,
 };
 use crate::dist::config::Config;
 use crate::dist::download::{DownloadCfg, File};
-use crate::dist::manifest::{Component, CompressionKind, Manifest, TargetedPackage};
+use crate::dist::manifest::{Component, CompressionKind, Manifest, Patch, TargetedPackage};
 use crate::dist::notifications::*;
 use crate::dist::prefix::InstallPrefix;
 use crate::dist::temp;
@@ -68,6 +68,58 @@ pub enum UpdateStatus {
     Unchanged,
 }
 
+/// The changes an update would make to an installation, as computed by
+/// [`Manifestation::plan`].
+#[derive(Debug, Default)]
+pub struct UpdatePlan {
+    pub add: Vec<PlannedComponent>,
+    pub upgrade: Vec<PlannedComponent>,
+    pub remove: Vec<PlannedComponent>,
+}
+
+impl UpdatePlan {
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.upgrade.is_empty() && self.remove.is_empty()
+    }
+
+    fn components(&self) -> impl Iterator<Item = &PlannedComponent> {
+        self.add.iter().chain(&self.upgrade).chain(&self.remove)
+    }
+
+    /// The number of bytes to download, if the server reported the size of
+    /// every archive.
+    pub fn download_size(&self) -> Option<u64> {
+        self.components().map(|c| c.download_size).sum()
+    }
+
+    /// The estimated change in disk usage, if the size of every component
+    /// is known.
+    pub fn disk_usage_change(&self) -> Option<i64> {
+        self.components()
+            .map(|c| {
+                let installed = c.installed_size.unwrap_or(0);
+                Some(c.estimated_size? as i64 - installed as i64)
+            })
+            .sum()
+    }
+}
+
+/// A component added, upgraded or removed by an [`UpdatePlan`].
+#[derive(Debug)]
+pub struct PlannedComponent {
+    pub name: String,
+    /// The version currently installed, unless the component is added.
+    pub old_version: Option<String>,
+    /// The version to install, unless the component is removed.
+    pub new_version: Option<String>,
+    /// The number of bytes to download, `None` if the server didn't report it.
+    pub download_size: Option<u64>,
+    /// The disk space taken up by the currently installed version.
+    pub installed_size: Option<u64>,
+    /// The disk space the new version is estimated to take up.
+    pub estimated_size: Option<u64>,
+}
+
 impl Manifestation {
     /// Open the install prefix for updates from a distribution
     /// channel.  The install prefix directory does not need to exist;
@@ -118,42 +170,42 @@ impl Manifestation {
 
         // Create the lists of components needed for installation
         let config = self.read_config()?;
-        let mut update = Update::build_update(
-            self,
+        let Some(update) = self.schedule_update(
             new_manifest,
             &changes,
             &config,
-            &download_cfg.notify_handler,
-        )?;
-
-        if update.nothing_changes() {
+            force_update,
+            download_cfg,
+            toolchain_str,
+        )?
+        else {
             return Ok(UpdateStatus::Unchanged);
-        }
-
-        // Validate that the requested components are available
-        if let Err(e) = update.unavailable_components(new_manifest, toolchain_str) {
-            if !force_update {
-                return Err(e);
-            }
-            if let Ok(RustupError::RequestedComponentsUnavailable { components, .. }) =
-                e.downcast::<RustupError>()
-            {
-                for component in &components {
-                    (download_cfg.notify_handler)(Notification::ForcingUnavailableComponent(
-                        &component.name(new_manifest),
-                    ));
-                }
-                update.drop_components_to_install(&components);
-            }
-        }
+        };
 
-        let altered = tmp_cx.dist_server != DEFAULT_DIST_SERVER;
+        // With delta updates, the archives of the installed components are
+        // kept in the download directory, as the base of patches to the next
+        // update's archives.
+        let delta_updates = download_cfg
+            .process
+            .var("RUSTUP_DELTA_UPDATES")
+            .is_ok_and(|s| s != "0");
+        let previous_archives = match (delta_updates, &config) {
+            (true, Some(config)) => self
+                .load_manifest()?
+                .map(|old_manifest| Self::archive_hashes(&old_manifest, &config.components))
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
 
         // Download component packages and validate hashes
         let mut things_to_install: Vec<(Component, CompressionKind, File)> = Vec::new();
         let mut things_downloaded: Vec<String> = Vec::new();
         let components = update.components_urls_and_hashes(new_manifest)?;
 
+        // Fail before downloading anything if the archives or their contents
+        // clearly won't fit
+        ensure_space_for_downloads(&components, new_manifest, download_cfg, prefix.path())?;
+
         const DEFAULT_MAX_RETRIES: usize = 3;
         let max_retries: usize = download_cfg
             .process
@@ -162,20 +214,33 @@ impl Manifestation {
             .and_then(|s| s.parse().ok())
             .unwrap_or(DEFAULT_MAX_RETRIES);
 
-        for (component, format, url, hash) in components {
+        for (component, format, url, hash, patches) in components {
             (download_cfg.notify_handler)(Notification::DownloadingComponent(
                 &component.short_name(new_manifest),
                 &self.target_triple,
                 component.target.as_ref(),
             ));
-            let url = if altered {
-                url.replace(DEFAULT_DIST_SERVER, tmp_cx.dist_server.as_str())
-            } else {
-                url
-            };
+            let url = rewrite_url(url, download_cfg);
 
             let url_url = utils::parse_url(&url)?;
 
+            let patches: Vec<_> = patches
+                .into_iter()
+                .map(|patch| Patch {
+                    url: rewrite_url(patch.url, download_cfg),
+                    ..patch
+                })
+                .collect();
+            let patched_file = match delta_updates {
+                true => download_cfg.download_patched(&patches, &hash).await,
+                false => None,
+            };
+            if let Some(file) = patched_file {
+                things_downloaded.push(hash);
+                things_to_install.push((component, format, file));
+                continue;
+            }
+
             let downloaded_file = RetryIf::spawn(
                 FixedInterval::from_millis(0).take(max_retries),
                 || download_cfg.download(&url_url, &hash),
@@ -199,6 +264,10 @@ impl Manifestation {
             things_to_install.push((component, format, downloaded_file));
         }
 
+        // Check again with the actual sizes of the contents, rather than fail
+        // partway through unpacking them
+        ensure_disk_space(&things_to_install, prefix.path(), tmp_cx.root_directory())?;
+
         // Begin transaction
         let mut tx = Transaction::new(
             prefix.clone(),
@@ -234,67 +303,9 @@ impl Manifestation {
         }
 
         // Install components
-        for (component, format, installer_file) in things_to_install {
-            // For historical reasons, the rust-installer component
-            // names are not the same as the dist manifest component
-            // names. Some are just the component name some are the
-            // component name plus the target triple.
-            let pkg_name = component.name_in_manifest();
-            let short_pkg_name = component.short_name_in_manifest();
-            let short_name = component.short_name(new_manifest);
-
-            (download_cfg.notify_handler)(Notification::InstallingComponent(
-                &short_name,
-                &self.target_triple,
-                component.target.as_ref(),
-            ));
-
-            let notification_converter = |notification: crate::utils::Notification<'_>| {
-                (download_cfg.notify_handler)(notification.into());
-            };
-            let gz;
-            let xz;
-            let zst;
-            let reader =
-                utils::FileReaderWithProgress::new_file(&installer_file, &notification_converter)?;
-            let package: &dyn Package = match format {
-                CompressionKind::GZip => {
-                    gz = TarGzPackage::new(
-                        reader,
-                        tmp_cx,
-                        Some(&notification_converter),
-                        download_cfg.process,
-                    )?;
-                    &gz
-                }
-                CompressionKind::XZ => {
-                    xz = TarXzPackage::new(
-                        reader,
-                        tmp_cx,
-                        Some(&notification_converter),
-                        download_cfg.process,
-                    )?;
-                    &xz
-                }
-                CompressionKind::ZStd => {
-                    zst = TarZStdPackage::new(
-                        reader,
-                        tmp_cx,
-                        Some(&notification_converter),
-                        download_cfg.process,
-                    )?;
-                    &zst
-                }
-            };
-
-            // If the package doesn't contain the component that the
-            // manifest says it does then somebody must be playing a joke on us.
-            if !package.contains(&pkg_name, Some(short_pkg_name)) {
-                return Err(RustupError::CorruptComponent(short_name).into());
-            }
-
-            tx = package.install(&self.installation, &pkg_name, Some(short_pkg_name), tx)?;
-        }
+        tx = self
+            .install_components(things_to_install, new_manifest, download_cfg, tx)
+            .await?;
 
         // Install new distribution manifest
         let new_manifest_str = new_manifest.clone().stringify()?;
@@ -320,11 +331,160 @@ impl Manifestation {
         // End transaction
         tx.commit();
 
-        download_cfg.clean(&things_downloaded)?;
+        if delta_updates {
+            // Keep the archives of every installed component, not only of
+            // those just downloaded, as they are all bases for the next update
+            let current = Self::archive_hashes(new_manifest, &new_config.components);
+            let outdated: Vec<_> = previous_archives
+                .into_iter()
+                .filter(|hash| !current.contains(hash))
+                .collect();
+            download_cfg.clean(&outdated)?;
+        } else {
+            download_cfg.clean(&things_downloaded)?;
+        }
 
         Ok(UpdateStatus::Changed)
     }
 
+    /// Computes what `update` would do with the same arguments, without
+    /// downloading any package or modifying the installation.
+    ///
+    /// The sizes of the archives to download are queried from the
+    /// distribution server, and the disk space the new components will
+    /// take up is estimated from them.
+    pub async fn plan(
+        &self,
+        new_manifest: &Manifest,
+        changes: Changes,
+        force_update: bool,
+        download_cfg: &DownloadCfg<'_>,
+        toolchain_str: &str,
+    ) -> Result<UpdatePlan> {
+        let config = self.read_config()?;
+        let Some(update) = self.schedule_update(
+            new_manifest,
+            &changes,
+            &config,
+            force_update,
+            download_cfg,
+            toolchain_str,
+        )?
+        else {
+            return Ok(UpdatePlan::default());
+        };
+
+        let old_manifest = self.load_manifest()?;
+        let version = |manifest: Option<&Manifest>, component: &Component| {
+            manifest?
+                .get_package(component.short_name_in_manifest())
+                .ok()
+                .map(|pkg| pkg.version.clone())
+        };
+
+        let mut plan = UpdatePlan::default();
+        let components = update.components_urls_and_hashes(new_manifest)?;
+        for (component, format, url, hash, _) in components {
+            let url = utils::parse_url(&rewrite_url(url, download_cfg))?;
+            let listed = listed_size(new_manifest, &component, &hash);
+            let (archive_size, downloaded) = download_cfg.archive_size(&url, &hash, listed).await?;
+            let upgraded = update.components_to_uninstall.contains(&component);
+            let planned = PlannedComponent {
+                name: component.name(new_manifest),
+                old_version: match upgraded {
+                    true => version(old_manifest.as_ref(), &component),
+                    false => None,
+                },
+                new_version: version(Some(new_manifest), &component),
+                download_size: match downloaded {
+                    true => Some(0),
+                    false => archive_size,
+                },
+                installed_size: match upgraded {
+                    true => self.installed_size(&component)?,
+                    false => None,
+                },
+                estimated_size: archive_size.map(|size| estimated_unpacked_size(format, size)),
+            };
+            match upgraded {
+                true => plan.upgrade.push(planned),
+                false => plan.add.push(planned),
+            }
+        }
+
+        for component in &update.components_to_uninstall {
+            if update.components_to_install.contains(component) {
+                continue;
+            }
+            let manifest = old_manifest.as_ref().unwrap_or(new_manifest);
+            plan.remove.push(PlannedComponent {
+                name: component.name(manifest),
+                old_version: version(Some(manifest), component),
+                new_version: None,
+                download_size: Some(0),
+                installed_size: self.installed_size(component)?,
+                estimated_size: Some(0),
+            });
+        }
+
+        Ok(plan)
+    }
+
+    /// Schedules the components to install and uninstall to apply
+    /// `changes`, or returns `None` if there is nothing to do.
+    ///
+    /// Fails if any of the components to install are unavailable, unless
+    /// `force_update` is set, in which case they are skipped.
+    fn schedule_update(
+        &self,
+        new_manifest: &Manifest,
+        changes: &Changes,
+        config: &Option<Config>,
+        force_update: bool,
+        download_cfg: &DownloadCfg<'_>,
+        toolchain_str: &str,
+    ) -> Result<Option<Update>> {
+        let mut update = Update::build_update(
+            self,
+            new_manifest,
+            changes,
+            config,
+            &download_cfg.notify_handler,
+        )?;
+
+        if update.nothing_changes() {
+            return Ok(None);
+        }
+
+        // Validate that the requested components are available
+        if let Err(e) = update.unavailable_components(new_manifest, toolchain_str) {
+            if !force_update {
+                return Err(e);
+            }
+            if let Ok(RustupError::RequestedComponentsUnavailable { components, .. }) =
+                e.downcast::<RustupError>()
+            {
+                for component in &components {
+                    (download_cfg.notify_handler)(Notification::ForcingUnavailableComponent(
+                        &component.name(new_manifest),
+                    ));
+                }
+                update.drop_components_to_install(&components);
+            }
+        }
+
+        Ok(Some(update))
+    }
+
+    /// The disk space taken up by an installed component, if it is found.
+    fn installed_size(&self, component: &Component) -> Result<Option<u64>> {
+        let installed = match self.installation.find(&component.name_in_manifest())? {
+            Some(c) => Some(c),
+            None => self.installation.find(component.short_name_in_manifest())?,
+        };
+        installed.map(|c| c.disk_usage()).transpose()
+    }
+
     #[cfg(test)]
     pub fn uninstall(
         &self,
@@ -355,6 +515,51 @@ impl Manifestation {
         Ok(())
     }
 
+    async fn install_components<'a>(
+        &self,
+        things_to_install: Vec<(Component, CompressionKind, File)>,
+        new_manifest: &Manifest,
+        download_cfg: &DownloadCfg<'_>,
+        mut tx: Transaction<'a>,
+    ) -> Result<Transaction<'a>> {
+        for (component, format, installer_file) in things_to_install {
+            // For historical reasons, the rust-installer component
+            // names are not the same as the dist manifest component
+            // names. Some are just the component name some are the
+            // component name plus the target triple.
+            let pkg_name = component.name_in_manifest();
+            let short_pkg_name = component.short_name_in_manifest();
+            let short_name = component.short_name(new_manifest);
+
+            (download_cfg.notify_handler)(Notification::InstallingComponent(
+                &short_name,
+                &self.target_triple,
+                component.target.as_ref(),
+            ));
+
+            let notification_converter = |notification: crate::utils::Notification<'_>| {
+                (download_cfg.notify_handler)(notification.into());
+            };
+            let package = TarPackage::unpack(
+                &installer_file,
+                format,
+                download_cfg.tmp_cx,
+                &notification_converter,
+                download_cfg.process,
+            )
+            .await?;
+
+            // If the package doesn't contain the component that the
+            // manifest says it does then somebody must be playing a joke on us.
+            if !package.contains(&pkg_name, Some(short_pkg_name)) {
+                return Err(RustupError::CorruptComponent(short_name).into());
+            }
+
+            tx = package.install(&self.installation, &pkg_name, Some(short_pkg_name), tx)?;
+        }
+        Ok(tx)
+    }
+
     fn uninstall_component<'a>(
         &self,
         component: &Component,
@@ -418,6 +623,19 @@ impl Manifestation {
         }
     }
 
+    /// The hashes of all the archives `manifest` lists for `components`.
+    fn archive_hashes(manifest: &Manifest, components: &[Component]) -> Vec<String> {
+        components
+            .iter()
+            .filter_map(|component| {
+                let package = manifest.get_package(component.short_name_in_manifest());
+                package.ok()?.get_target(component.target.as_ref()).ok()
+            })
+            .flat_map(|target_package| &target_package.bins)
+            .map(|bin| bin.hash.clone())
+            .collect()
+    }
+
     /// Installation using the legacy v1 manifest format
     pub(crate) async fn update_v1(
         &self,
@@ -459,6 +677,7 @@ impl Manifestation {
         let dlcfg = DownloadCfg {
             dist_root: "bogus",
             download_dir: &dld_dir,
+            http_cache: None,
             tmp_cx,
             notify_handler,
             process,
@@ -533,6 +752,98 @@ impl Manifestation {
     }
 }
 
+/// Points `url` at the configured dist server rather than the official one.
+fn rewrite_url(url: String, download_cfg: &DownloadCfg<'_>) -> String {
+    let dist_server = &download_cfg.tmp_cx.dist_server;
+    if dist_server != DEFAULT_DIST_SERVER {
+        url.replace(DEFAULT_DIST_SERVER, dist_server)
+    } else {
+        url
+    }
+}
+
+/// Returns the size `manifest` lists for the archive of `component` with the
+/// given hash, if it lists one.
+fn listed_size(manifest: &Manifest, component: &Component, hash: &str) -> Option<u64> {
+    manifest
+        .get_package(component.short_name_in_manifest())
+        .ok()?
+        .get_target(component.target.as_ref())
+        .ok()?
+        .bins
+        .iter()
+        .find(|bin| bin.hash == hash)?
+        .size
+}
+
+/// Checks, before anything is downloaded, that there is room for the archives
+/// of `components` in the download directory, and for their contents as
+/// estimated from the archive sizes in `prefix` and in the temporary
+/// directory, adding up what goes on the same volume.
+///
+/// Archives whose size neither the manifest nor the download directory gives
+/// are left out; the checks made as each is downloaded and once all are still
+/// apply to them.
+fn ensure_space_for_downloads(
+    components: &[(Component, CompressionKind, String, String, Vec<Patch>)],
+    manifest: &Manifest,
+    download_cfg: &DownloadCfg<'_>,
+    prefix: &Path,
+) -> Result<()> {
+    let mut download: u64 = 0;
+    let mut unpacked: u64 = 0;
+    for (component, format, _, hash, _) in components {
+        let size = match download_cfg.downloaded_size(hash) {
+            Some(size) => size,
+            None => match listed_size(manifest, component, hash) {
+                Some(size) => {
+                    download = download.saturating_add(size);
+                    size
+                }
+                None => continue,
+            },
+        };
+        unpacked = unpacked.saturating_add(estimated_unpacked_size(*format, size));
+    }
+    utils::ensure_disk_space_for(&[
+        (download_cfg.download_dir.as_path(), download),
+        (prefix, unpacked),
+        (download_cfg.tmp_cx.root_directory(), unpacked),
+    ])
+}
+
+/// Checks that there is room for the contents of the archives to install in
+/// `prefix` and in `tmp_dir`, where they are unpacked before being moved into
+/// place, adding up both when they are on the same volume.
+fn ensure_disk_space(
+    things_to_install: &[(Component, CompressionKind, File)],
+    prefix: &Path,
+    tmp_dir: &Path,
+) -> Result<()> {
+    let mut required: u64 = 0;
+    for (_, format, file) in things_to_install {
+        let size = match unpacked_size(*format, file) {
+            Ok(Some(size)) => size,
+            _ => estimated_unpacked_size(*format, utils::file_size(file)?),
+        };
+        required = required.saturating_add(size);
+    }
+    utils::ensure_disk_space_for(&[(prefix, required), (tmp_dir, required)])
+}
+
+/// Estimates the size of the contents of an archive from its own size.
+///
+/// The ratios are those typical of toolchain components, whose
+/// documentation compresses much better than their binaries.
+fn estimated_unpacked_size(format: CompressionKind, archive_size: u64) -> u64 {
+    let ratio = match format {
+        CompressionKind::GZip => 3.0,
+        CompressionKind::XZ => 4.5,
+        CompressionKind::ZStd => 4.0,
+    };
+    (archive_size as f64 * ratio) as u64
+}
+
 #[derive(Debug)]
 struct Update {
     components_to_uninstall: Vec<Component>,
@@ -725,7 +1036,7 @@ impl Update {
     fn components_urls_and_hashes(
         &self,
         new_manifest: &Manifest,
-    ) -> Result<Vec<(Component, CompressionKind, String, String)>> {
+    ) -> Result<Vec<(Component, CompressionKind, String, String, Vec<Patch>)>> {
         let mut components_urls_and_hashes = Vec::new();
         for component in &self.components_to_install {
             let package = new_manifest.get_package(component.short_name_in_manifest())?;
@@ -742,6 +1053,7 @@ impl Update {
                 target_package.bins[0].compression,
                 target_package.bins[0].url.clone(),
                 target_package.bins[0].hash.clone(),
+                target_package.patches.clone(),
             ));
         }
 
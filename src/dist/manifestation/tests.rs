@@ -18,7 +18,7 @@ use crate::{
     dist::{
         download::DownloadCfg,
         manifest::{Component, Manifest},
-        manifestation::{Changes, Manifestation, UpdateStatus},
+        manifestation::{Changes, Manifestation, UpdatePlan, UpdateStatus},
         prefix::InstallPrefix,
         temp, Notification, Profile, TargetTriple, ToolchainDesc, DEFAULT_DIST_SERVER,
     },
@@ -309,7 +309,7 @@ async fn rename_component() {
         ],
     };
 
-    let cx = TestContext::from_dist_server(mock_dist_server, url, GZOnly);
+    let cx = TestContext::from_dist_server(mock_dist_server, url, GZOnly, HashMap::default());
 
     let adds = [Component::new(
         "bonus".to_string(),
@@ -349,7 +349,7 @@ async fn rename_component_new() {
         ],
     };
 
-    let cx = TestContext::from_dist_server(mock_dist_server, url, GZOnly);
+    let cx = TestContext::from_dist_server(mock_dist_server, url, GZOnly, HashMap::default());
 
     let adds = [Component::new(
         "bobo".to_string(),
@@ -412,16 +412,29 @@ struct TestContext {
 
 impl TestContext {
     fn new(edit: Option<&dyn Fn(&str, &mut MockChannel)>, comps: Compressions) -> Self {
+        Self::with_vars(edit, comps, HashMap::default())
+    }
+
+    fn with_vars(
+        edit: Option<&dyn Fn(&str, &mut MockChannel)>,
+        comps: Compressions,
+        vars: HashMap<String, String>,
+    ) -> Self {
         let dist_tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
         let mock_dist_server = create_mock_dist_server(dist_tempdir.path(), edit);
         let url = Url::parse(&format!("file://{}", dist_tempdir.path().to_string_lossy())).unwrap();
 
-        let mut cx = Self::from_dist_server(mock_dist_server, url, comps);
+        let mut cx = Self::from_dist_server(mock_dist_server, url, comps, vars);
         cx._tempdirs.push(dist_tempdir);
         cx
     }
 
-    fn from_dist_server(server: MockDistServer, url: Url, comps: Compressions) -> Self {
+    fn from_dist_server(
+        server: MockDistServer,
+        url: Url,
+        comps: Compressions,
+        vars: HashMap<String, String>,
+    ) -> Self {
         server.write(
             &[MockManifestVersion::V2],
             comps.enable_xz(),
@@ -439,12 +452,7 @@ impl TestContext {
 
         let toolchain = ToolchainDesc::from_str("nightly-x86_64-apple-darwin").unwrap();
         let prefix = InstallPrefix::from(prefix_tempdir.path());
-        let tp = TestProcess::new(
-            env::current_dir().unwrap(),
-            &["rustup"],
-            HashMap::default(),
-            "",
-        );
+        let tp = TestProcess::new(env::current_dir().unwrap(), &["rustup"], vars, "");
 
         Self {
             url,
@@ -462,6 +470,7 @@ impl TestContext {
             dist_root: "phony",
             tmp_cx: &self.tmp_cx,
             download_dir: &self.download_dir,
+            http_cache: None,
             notify_handler: &|event| println!("{event}"),
             process: &self.tp.process,
         }
@@ -488,6 +497,39 @@ impl TestContext {
         force: bool,
         dl_cfg: &DownloadCfg<'_>,
     ) -> Result<UpdateStatus> {
+        let (manifestation, manifest, changes) = self.prepare_update(add, remove, dl_cfg).await?;
+        manifestation
+            .update(
+                &manifest,
+                changes,
+                force,
+                dl_cfg,
+                &self.toolchain.manifest_name(),
+                true,
+            )
+            .await
+    }
+
+    async fn plan_from_dist(&self, add: &[Component], remove: &[Component]) -> Result<UpdatePlan> {
+        let dl_cfg = self.default_dl_cfg();
+        let (manifestation, manifest, changes) = self.prepare_update(add, remove, &dl_cfg).await?;
+        manifestation
+            .plan(
+                &manifest,
+                changes,
+                false,
+                &dl_cfg,
+                &self.toolchain.manifest_name(),
+            )
+            .await
+    }
+
+    async fn prepare_update(
+        &self,
+        add: &[Component],
+        remove: &[Component],
+        dl_cfg: &DownloadCfg<'_>,
+    ) -> Result<(Manifestation, Manifest, Changes)> {
         // Download the dist manifest and place it into the installation prefix
         let manifest_url = make_manifest_url(&self.url, &self.toolchain)?;
         let manifest_file = self.tmp_cx.new_file()?;
@@ -509,16 +551,7 @@ impl TestContext {
             remove_components: remove.to_owned(),
         };
 
-        manifestation
-            .update(
-                &manifest,
-                changes,
-                force,
-                dl_cfg,
-                &self.toolchain.manifest_name(),
-                true,
-            )
-            .await
+        Ok((manifestation, manifest, changes))
     }
 
     fn uninstall(&self) -> Result<()> {
@@ -597,6 +630,56 @@ async fn upgrade() {
     );
 }
 
+#[tokio::test]
+async fn plan_upgrade() {
+    let cx = TestContext::new(None, GZOnly);
+    change_channel_date(&cx.url, "nightly", "2016-02-01");
+    cx.update_from_dist(&[], &[], false).await.unwrap();
+    change_channel_date(&cx.url, "nightly", "2016-02-02");
+
+    let plan = cx.plan_from_dist(&[], &[]).await.unwrap();
+    assert!(plan.add.is_empty());
+    assert!(plan.remove.is_empty());
+    let rustc = plan
+        .upgrade
+        .iter()
+        .find(|c| c.name == "rustc-x86_64-apple-darwin")
+        .unwrap();
+    assert_eq!(rustc.old_version.as_deref(), Some("1.0.0"));
+    assert_eq!(rustc.new_version.as_deref(), Some("1.0.0"));
+    assert!(rustc.download_size.unwrap() > 0);
+    assert!(rustc.installed_size.unwrap() > 0);
+    assert!(plan.download_size().is_some());
+    assert!(plan.disk_usage_change().is_some());
+
+    // Nothing was downloaded or installed.
+    assert_eq!(
+        "2016-02-01",
+        fs::read_to_string(cx.prefix.path().join("bin/rustc")).unwrap()
+    );
+    assert!(!cx.download_dir.exists() || cx.download_dir.read_dir().unwrap().next().is_none());
+}
+
+#[tokio::test]
+async fn plan_removing_component() {
+    let cx = TestContext::new(None, GZOnly);
+    let adds = vec![Component::new(
+        "bonus".to_string(),
+        Some(TargetTriple::new("x86_64-apple-darwin")),
+        false,
+    )];
+    cx.update_from_dist(&adds, &[], false).await.unwrap();
+
+    let plan = cx.plan_from_dist(&[], &adds).await.unwrap();
+    assert!(plan.add.is_empty());
+    assert!(plan.upgrade.is_empty());
+    assert_eq!(plan.remove.len(), 1);
+    assert_eq!(plan.remove[0].name, "bonus-x86_64-apple-darwin");
+    assert_eq!(plan.download_size(), Some(0));
+    assert!(plan.disk_usage_change().unwrap() < 0);
+    assert!(utils::path_exists(cx.prefix.path().join("bin/bonus")));
+}
+
 #[tokio::test]
 async fn unavailable_component() {
     // On day 2 the bonus component is no longer available
@@ -1528,3 +1611,192 @@ async fn handle_corrupt_partial_downloads() {
     assert!(utils::path_exists(cx.prefix.path().join("bin/rustc")));
     assert!(utils::path_exists(cx.prefix.path().join("lib/libstd.rlib")));
 }
+
+/// A context whose dist server also serves patches between consecutive
+/// nightlies, with its process opting into delta updates if `enabled`.
+fn delta_update_context(enabled: bool) -> TestContext {
+    let mut vars = HashMap::default();
+    if enabled {
+        vars.insert("RUSTUP_DELTA_UPDATES".to_owned(), "1".to_owned());
+    }
+    let cx = TestContext::with_vars(None, GZOnly, vars);
+    create_mock_dist_server(&cx.url.to_file_path().unwrap(), None).write_with_patches(
+        &[MockManifestVersion::V2],
+        false,
+        false,
+    );
+    cx
+}
+
+fn dist_files(cx: &TestContext, date: &str, suffix: &str) -> Vec<PathBuf> {
+    let dir = cx.url.to_file_path().unwrap().join("dist").join(date);
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(suffix))
+        .collect()
+}
+
+fn downloaded_files(cx: &TestContext) -> Vec<String> {
+    let mut files: Vec<_> = fs::read_dir(&cx.download_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    files
+}
+
+#[tokio::test]
+async fn delta_update_patches_previous_archives() {
+    let cx = delta_update_context(true);
+    let patched = Cell::new(false);
+    let dl_cfg = DownloadCfg {
+        notify_handler: &|n| match n {
+            Notification::DownloadingPatch(_) => patched.set(true),
+            Notification::PatchFailed(url, e) => panic!("failed to apply {url}: {e}"),
+            _ => {}
+        },
+        ..cx.default_dl_cfg()
+    };
+
+    change_channel_date(&cx.url, "nightly", "2016-02-01");
+    cx.update_from_dist_with_dl_cfg(&[], &[], false, &dl_cfg)
+        .await
+        .unwrap();
+    let old_archives = downloaded_files(&cx);
+    assert!(!old_archives.is_empty());
+    assert!(!patched.get());
+
+    // Only the patches are available to update with.
+    for archive in dist_files(&cx, "2016-02-02", ".tar.gz") {
+        fs::remove_file(archive).unwrap();
+    }
+    change_channel_date(&cx.url, "nightly", "2016-02-02");
+    cx.update_from_dist_with_dl_cfg(&[], &[], false, &dl_cfg)
+        .await
+        .unwrap();
+    assert!(patched.get());
+    assert_eq!(
+        "2016-02-02",
+        fs::read_to_string(cx.prefix.path().join("bin/rustc")).unwrap()
+    );
+
+    // The archives just installed replace the previous ones.
+    let new_archives = downloaded_files(&cx);
+    assert_eq!(new_archives.len(), old_archives.len());
+    assert!(new_archives.iter().all(|hash| !old_archives.contains(hash)));
+}
+
+#[tokio::test]
+async fn delta_update_keeps_archives_of_unchanged_components() {
+    let cx = delta_update_context(true);
+    let dl_cfg = DownloadCfg {
+        notify_handler: &|n| {
+            if let Notification::PatchFailed(url, e) = n {
+                panic!("failed to apply {url}: {e}");
+            }
+        },
+        ..cx.default_dl_cfg()
+    };
+
+    change_channel_date(&cx.url, "nightly", "2016-02-01");
+    cx.update_from_dist_with_dl_cfg(&[], &[], false, &dl_cfg)
+        .await
+        .unwrap();
+    let installed_archives = downloaded_files(&cx);
+
+    // Adding a component leaves the archives of the others in place.
+    let adds = [Component::new(
+        "rust-std".to_string(),
+        Some(TargetTriple::new("i686-apple-darwin")),
+        false,
+    )];
+    cx.update_from_dist_with_dl_cfg(&adds, &[], false, &dl_cfg)
+        .await
+        .unwrap();
+    let archives = downloaded_files(&cx);
+    assert_eq!(archives.len(), installed_archives.len() + 1);
+    assert!(installed_archives
+        .iter()
+        .all(|hash| archives.contains(hash)));
+
+    // So all of them can still be patched.
+    for archive in dist_files(&cx, "2016-02-02", ".tar.gz") {
+        fs::remove_file(archive).unwrap();
+    }
+    change_channel_date(&cx.url, "nightly", "2016-02-02");
+    cx.update_from_dist_with_dl_cfg(&[], &[], false, &dl_cfg)
+        .await
+        .unwrap();
+    assert_eq!(
+        "2016-02-02",
+        fs::read_to_string(cx.prefix.path().join("bin/rustc")).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn delta_update_falls_back_to_full_archives() {
+    let cx = delta_update_context(true);
+    let failed = Cell::new(false);
+    let dl_cfg = DownloadCfg {
+        notify_handler: &|n| {
+            if let Notification::PatchFailed(..) = n {
+                failed.set(true);
+            }
+        },
+        ..cx.default_dl_cfg()
+    };
+
+    change_channel_date(&cx.url, "nightly", "2016-02-01");
+    cx.update_from_dist_with_dl_cfg(&[], &[], false, &dl_cfg)
+        .await
+        .unwrap();
+
+    for patch in dist_files(&cx, "2016-02-02", ".patch.zst") {
+        fs::write(patch, "not a patch").unwrap();
+    }
+    change_channel_date(&cx.url, "nightly", "2016-02-02");
+    cx.update_from_dist_with_dl_cfg(&[], &[], false, &dl_cfg)
+        .await
+        .unwrap();
+    assert!(failed.get());
+    assert_eq!(
+        "2016-02-02",
+        fs::read_to_string(cx.prefix.path().join("bin/rustc")).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn update_without_delta_updates_ignores_patches() {
+    let cx = delta_update_context(false);
+    change_channel_date(&cx.url, "nightly", "2016-02-01");
+    cx.update_from_dist(&[], &[], false).await.unwrap();
+    assert!(downloaded_files(&cx).is_empty());
+
+    change_channel_date(&cx.url, "nightly", "2016-02-02");
+    cx.update_from_dist(&[], &[], false).await.unwrap();
+    assert!(downloaded_files(&cx).is_empty());
+}
+
+#[tokio::test]
+async fn concurrent_downloads_share_archives() {
+    let cx = TestContext::new(None, GZOnly);
+    let dl_cfg = cx.default_dl_cfg();
+    let archive = dist_files(&cx, "2016-02-02", ".tar.gz").remove(0);
+    let url = Url::from_file_path(&archive).unwrap();
+    let hash = fs::read_to_string(archive.with_extension("gz.sha256")).unwrap()[..SHA256_HASH_LEN]
+        .to_owned();
+
+    let (first, second) = tokio::join!(dl_cfg.download(&url, &hash), dl_cfg.download(&url, &hash));
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert_eq!(downloaded_files(&cx), [hash.as_str()]);
+
+    // An archive isn't cleaned up while an update still needs it.
+    drop(first);
+    dl_cfg.clean(std::slice::from_ref(&hash)).unwrap();
+    assert!(second.is_file());
+
+    drop(second);
+    dl_cfg.clean(&[hash]).unwrap();
+    assert!(downloaded_files(&cx).is_empty());
+}
@@ -1,7 +1,13 @@
 //! Installation from a Rust distribution server
 
 use std::{
-    collections::HashSet, env, fmt, io::Write, ops::Deref, path::Path, str::FromStr, sync::LazyLock,
+    collections::HashSet,
+    env, fmt,
+    io::Write,
+    ops::Deref,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::LazyLock,
 };
 
 use anyhow::{anyhow, bail, Context, Result};
@@ -21,17 +27,21 @@ use crate::{
     utils::utils,
 };
 
+pub(crate) mod cache_server;
+
 pub mod component;
 pub(crate) mod config;
 
 pub mod download;
 use download::DownloadCfg;
 
+pub(crate) mod http_cache;
+
 pub mod manifest;
 use manifest::{Component, Manifest as ManifestV2};
 
 pub mod manifestation;
-use manifestation::{Changes, Manifestation, UpdateStatus};
+use manifestation::{Changes, Manifestation, UpdatePlan, UpdateStatus};
 
 pub(crate) mod notifications;
 pub use notifications::Notification;
@@ -239,21 +249,6 @@ impl FromStr for PartialVersion {
 #[serde(transparent)]
 pub struct TargetTriple(String);
 
-// Linux hosts don't indicate clib in uname, however binaries only
-// run on boxes with the same clib, as expected.
-#[cfg(all(not(windows), not(target_env = "musl")))]
-const TRIPLE_X86_64_UNKNOWN_LINUX: &str = "x86_64-unknown-linux-gnu";
-#[cfg(all(not(windows), target_env = "musl"))]
-const TRIPLE_X86_64_UNKNOWN_LINUX: &str = "x86_64-unknown-linux-musl";
-#[cfg(all(not(windows), not(target_env = "musl")))]
-const TRIPLE_AARCH64_UNKNOWN_LINUX: &str = "aarch64-unknown-linux-gnu";
-#[cfg(all(not(windows), target_env = "musl"))]
-const TRIPLE_AARCH64_UNKNOWN_LINUX: &str = "aarch64-unknown-linux-musl";
-#[cfg(all(not(windows), not(target_env = "musl")))]
-const TRIPLE_LOONGARCH64_UNKNOWN_LINUX: &str = "loongarch64-unknown-linux-gnu";
-#[cfg(all(not(windows), target_env = "musl"))]
-const TRIPLE_LOONGARCH64_UNKNOWN_LINUX: &str = "loongarch64-unknown-linux-musl";
-
 // MIPS platforms don't indicate endianness in uname, however binaries only
 // run on boxes with the same endianness, as expected.
 // Hence we could distinguish between the variants with compile-time cfg()
@@ -360,6 +355,283 @@ fn is_32bit_userspace() -> bool {
     inner().unwrap_or(cfg!(target_pointer_width = "32"))
 }
 
+/// The C library of a Linux host.
+#[cfg(not(windows))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Libc {
+    Gnu,
+    Musl,
+}
+
+/// Linux hosts don't indicate their C library in uname, however binaries
+/// only run on boxes with the same one, as expected. So this reads it from
+/// the ELF interpreter of `sh`, looked up in `PATH` and then as `/bin/sh`,
+/// which is dynamically linked against the C library of the host.
+/// If that fails, fall back to _our_ C library.
+#[cfg(not(windows))]
+fn host_libc(process: &Process) -> Libc {
+    let path = process
+        .var_os("PATH")
+        .into_iter()
+        .flat_map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+        .map(|dir| dir.join("sh"))
+        .chain([PathBuf::from("/bin/sh")]);
+
+    path.filter_map(|path| std::fs::read(path).ok())
+        .find_map(|elf| {
+            let interpreter = elf_interpreter(&elf)?;
+            let name = interpreter.rsplit('/').next()?;
+            if name.starts_with("ld-musl") {
+                Some(Libc::Musl)
+            } else if name.starts_with("ld-linux") {
+                Some(Libc::Gnu)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(if cfg!(target_env = "musl") {
+            Libc::Musl
+        } else {
+            Libc::Gnu
+        })
+}
+
+/// The path in the `PT_INTERP` program header of an ELF binary, if any.
+#[cfg(not(windows))]
+fn elf_interpreter(elf: &[u8]) -> Option<&str> {
+    const PT_INTERP: u32 = 3;
+
+    if elf.get(..4)? != b"\x7fELF" {
+        return None;
+    }
+    let is_64bit = match elf.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let is_big_endian = match elf.get(5)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let read = |offset: usize, len: usize| -> Option<u64> {
+        let bytes = elf.get(offset..offset.checked_add(len)?)?;
+        let mut buf = [0; 8];
+        if is_big_endian {
+            buf[8 - len..].copy_from_slice(bytes);
+            Some(u64::from_be_bytes(buf))
+        } else {
+            buf[..len].copy_from_slice(bytes);
+            Some(u64::from_le_bytes(buf))
+        }
+    };
+
+    // The offsets of the fields of the ELF header and program headers
+    // differ between 32-bit and 64-bit binaries.
+    let (phoff, phentsize, phnum) = match is_64bit {
+        true => (read(0x20, 8)?, read(0x36, 2)?, read(0x38, 2)?),
+        false => (read(0x1c, 4)?, read(0x2a, 2)?, read(0x2c, 2)?),
+    };
+    (0..phnum).find_map(|i| {
+        let header = i.checked_mul(phentsize)?.checked_add(phoff)?;
+        let header = usize::try_from(header).ok()?;
+        if read(header, 4)? != u64::from(PT_INTERP) {
+            return None;
+        }
+        let (offset, size) = match is_64bit {
+            true => (
+                read(header.checked_add(0x08)?, 8)?,
+                read(header.checked_add(0x20)?, 8)?,
+            ),
+            false => (
+                read(header.checked_add(0x04)?, 4)?,
+                read(header.checked_add(0x10)?, 4)?,
+            ),
+        };
+        let offset = usize::try_from(offset).ok()?;
+        let path = elf.get(offset..offset.checked_add(usize::try_from(size).ok()?)?)?;
+        std::str::from_utf8(path.strip_suffix(b"\0").unwrap_or(path)).ok()
+    })
+}
+
+/// How binaries for a target can run on a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RunSupport {
+    /// The host runs them natively.
+    Native,
+    /// A binfmt_misc interpreter, such as qemu-user, runs them.
+    Emulated { interpreter: PathBuf },
+    /// They most likely won't run, for the reason given.
+    Unsupported { reason: String },
+}
+
+/// The root of the file system probed for multi-arch C libraries and
+/// binfmt_misc interpreters.
+fn host_root(process: &Process) -> PathBuf {
+    // Tests point this away from the file system of the machine they run on.
+    match process.var_os("RUSTUP_OVERRIDE_HOST_ROOT") {
+        Some(root) => PathBuf::from(root),
+        None => PathBuf::from("/"),
+    }
+}
+
+/// The dynamic linker, relative to the root, of the C library that `target`
+/// binaries need to run natively on a Linux `host` of another architecture,
+/// if the host can run them at all.
+fn multiarch_loader(
+    host: &ParsedTargetTriple,
+    target: &ParsedTargetTriple,
+) -> Option<&'static str> {
+    if host.env != target.env {
+        return None;
+    }
+    let native = match (host.arch.as_str(), target.arch.as_str()) {
+        ("x86_64", "i586" | "i686") => true,
+        ("aarch64", "arm" | "armv7") => {
+            target.abi.as_deref() == Some("eabihf") && supports_aarch32()
+        }
+        _ => false,
+    };
+    if !native {
+        return None;
+    }
+    match (target.arch.as_str(), target.env.as_deref()?) {
+        ("i586" | "i686", "gnu") => Some("lib/ld-linux.so.2"),
+        ("i586" | "i686", "musl") => Some("lib/ld-musl-i386.so.1"),
+        ("arm" | "armv7", "gnu") => Some("lib/ld-linux-armhf.so.3"),
+        ("arm" | "armv7", "musl") => Some("lib/ld-musl-armhf.so.1"),
+        _ => None,
+    }
+}
+
+/// Whether the CPU and kernel of an aarch64 host run 32-bit Arm binaries,
+/// which not all do.
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+fn supports_aarch32() -> bool {
+    const PER_LINUX32: libc::c_ulong = 0x0008;
+
+    // The kernel refuses the 32-bit personality without AArch32 support.
+    // Switching to it is tried in a child process, so that rustup's own
+    // personality is left alone whatever happens.
+    // SAFETY: the child only makes async-signal-safe calls before exiting.
+    unsafe {
+        let pid = libc::fork();
+        if pid == 0 {
+            let supported = libc::personality(PER_LINUX32) != -1;
+            libc::_exit(if supported { 0 } else { 1 });
+        }
+        if pid == -1 {
+            return false;
+        }
+        let mut status = 0;
+        while libc::waitpid(pid, &mut status, 0) == -1 {
+            if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                return false;
+            }
+        }
+        libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0
+    }
+}
+
+/// Whether the CPU and kernel of an aarch64 host run 32-bit Arm binaries,
+/// which we can only tell from an aarch64 build.
+#[cfg(not(all(target_os = "linux", target_arch = "aarch64")))]
+fn supports_aarch32() -> bool {
+    false
+}
+
+/// The start of the ELF header of an executable for `target`, up to and
+/// including `e_machine`.
+fn elf_header(target: &ParsedTargetTriple) -> Option<[u8; 20]> {
+    let (is_64bit, is_big_endian, machine): (bool, bool, u16) = match target.arch.as_str() {
+        "x86_64" => (true, false, 62),
+        "i386" | "i586" | "i686" => (false, false, 3),
+        "aarch64" => (true, false, 183),
+        "aarch64_be" => (true, true, 183),
+        "arm" | "armv4t" | "armv5te" | "armv6" | "armv7" => (false, false, 40),
+        "loongarch64" => (true, false, 258),
+        "mips" => (false, true, 8),
+        "mipsel" => (false, false, 8),
+        "mips64" => (true, true, 8),
+        "mips64el" => (true, false, 8),
+        "powerpc" => (false, true, 20),
+        "powerpc64" => (true, true, 21),
+        "powerpc64le" => (true, false, 21),
+        "riscv64gc" | "riscv64" => (true, false, 243),
+        "s390x" => (true, true, 22),
+        "sparc64" => (true, true, 43),
+        _ => return None,
+    };
+    const ET_EXEC: u16 = 2;
+
+    let mut header = [0; 20];
+    header[..4].copy_from_slice(b"\x7fELF");
+    header[4] = if is_64bit { 2 } else { 1 };
+    header[5] = if is_big_endian { 2 } else { 1 };
+    header[6] = 1;
+    let (e_type, e_machine) = match is_big_endian {
+        true => (ET_EXEC.to_be_bytes(), machine.to_be_bytes()),
+        false => (ET_EXEC.to_le_bytes(), machine.to_le_bytes()),
+    };
+    header[16..18].copy_from_slice(&e_type);
+    header[18..20].copy_from_slice(&e_machine);
+    Some(header)
+}
+
+/// The interpreter registered in the binfmt_misc directory `dir` for the
+/// executables of `target`, such as `/usr/bin/qemu-aarch64-static`.
+fn binfmt_interpreter(dir: &Path, target: &ParsedTargetTriple) -> Option<PathBuf> {
+    let header = elf_header(target)?;
+    if std::fs::read_to_string(dir.join("status")).ok()?.trim() != "enabled" {
+        return None;
+    }
+
+    let entries = std::fs::read_dir(dir).ok()?;
+    entries.filter_map(|entry| entry.ok()).find_map(|entry| {
+        if ["register", "status"].contains(&&*entry.file_name().to_string_lossy()) {
+            return None;
+        }
+        // An entry reads like
+        //
+        // enabled
+        // interpreter /usr/bin/qemu-aarch64-static
+        // flags: F
+        // offset 0
+        // magic 7f454c460201010000000000000000000200b700
+        // mask ffffffffffffff00fffffffffffffffffeffffff
+        let text = std::fs::read_to_string(entry.path()).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != "enabled" {
+            return None;
+        }
+        let (mut interpreter, mut offset, mut magic, mut mask) = (None, 0, None, None);
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("interpreter", value)) => interpreter = Some(PathBuf::from(value)),
+                Some(("offset", value)) => offset = value.parse().ok()?,
+                Some(("magic", value)) => magic = utils::decode_hex(value),
+                Some(("mask", value)) => mask = utils::decode_hex(value),
+                _ => {}
+            }
+        }
+        let magic = magic?;
+        let mask = mask.unwrap_or_else(|| vec![0xff; magic.len()]);
+        let matches = magic
+            .iter()
+            .zip(&mask)
+            .enumerate()
+            .all(|(i, (magic, mask))| {
+                header
+                    .get(offset + i)
+                    .is_some_and(|byte| byte & mask == magic & mask)
+            });
+        match matches {
+            true => interpreter,
+            false => None,
+        }
+    })
+}
+
 impl TargetTriple {
     pub fn new(name: impl Into<String>) -> Self {
         Self(name.into())
@@ -397,7 +669,7 @@ impl TargetTriple {
 
     pub(crate) fn from_host(process: &Process) -> Option<Self> {
         #[cfg(windows)]
-        fn inner() -> Option<TargetTriple> {
+        fn inner(_: &Process) -> Option<TargetTriple> {
             use std::mem;
 
             /// Get the host architecture using `IsWow64Process2`. This function
@@ -473,7 +745,7 @@ impl TargetTriple {
         }
 
         #[cfg(not(windows))]
-        fn inner() -> Option<TargetTriple> {
+        fn inner(process: &Process) -> Option<TargetTriple> {
             use std::ffi::CStr;
             use std::mem;
 
@@ -492,7 +764,7 @@ impl TargetTriple {
 
             #[cfg(not(target_os = "android"))]
             let host_triple = match (sysname, machine) {
-                (b"Linux", b"x86_64") => Some(TRIPLE_X86_64_UNKNOWN_LINUX),
+                (b"Linux", b"x86_64") => Some("x86_64-unknown-linux-gnu"),
                 (b"Linux", b"i686") => Some("i686-unknown-linux-gnu"),
                 (b"Linux", b"mips") => Some(TRIPLE_MIPS_UNKNOWN_LINUX_GNU),
                 (b"Linux", b"mips64") => Some(TRIPLE_MIPS64_UNKNOWN_LINUX_GNUABI64),
@@ -502,9 +774,9 @@ impl TargetTriple {
                 (b"Linux", b"aarch64") => Some(if is_32bit_userspace() {
                     "armv7-unknown-linux-gnueabihf"
                 } else {
-                    TRIPLE_AARCH64_UNKNOWN_LINUX
+                    "aarch64-unknown-linux-gnu"
                 }),
-                (b"Linux", b"loongarch64") => Some(TRIPLE_LOONGARCH64_UNKNOWN_LINUX),
+                (b"Linux", b"loongarch64") => Some("loongarch64-unknown-linux-gnu"),
                 (b"Darwin", b"x86_64") => Some("x86_64-apple-darwin"),
                 (b"Darwin", b"i686") => Some("i686-apple-darwin"),
                 (b"FreeBSD", b"x86_64") => Some("x86_64-unknown-freebsd"),
@@ -529,13 +801,21 @@ impl TargetTriple {
                 _ => None,
             };
 
+            #[cfg(not(target_os = "android"))]
+            let host_triple = host_triple.map(|triple| match sysname {
+                b"Linux" if host_libc(process) == Libc::Musl => {
+                    triple.replace("-linux-gnu", "-linux-musl")
+                }
+                _ => triple.to_owned(),
+            });
+
             host_triple.map(TargetTriple::new)
         }
 
         if let Ok(triple) = process.var("RUSTUP_OVERRIDE_HOST_TRIPLE") {
             Some(Self(triple))
         } else {
-            inner()
+            inner(process)
         }
     }
 
@@ -543,32 +823,71 @@ impl TargetTriple {
         Self::from_host(process).unwrap_or_else(Self::from_build)
     }
 
-    pub(crate) fn can_run(&self, other: &TargetTriple) -> Result<bool> {
+    /// Splits the triple into its parts.
+    pub(crate) fn parse(&self) -> Result<ParsedTargetTriple> {
+        Ok(ParsedTargetTriple::new(&self.0)?)
+    }
+
+    /// Whether binaries for `other` can run on this host, and how.
+    pub(crate) fn run_support(
+        &self,
+        other: &TargetTriple,
+        process: &Process,
+    ) -> Result<RunSupport> {
         // Most trivial shortcut of all
         if self == other {
-            return Ok(true);
+            return Ok(RunSupport::Native);
         }
         // Otherwise we need to parse things
-        let partial_self = PartialTargetTriple::new(&self.0)
-            .ok_or_else(|| anyhow!(format!("Unable to parse target triple: {}", self.0)))?;
-        let partial_other = PartialTargetTriple::new(&other.0)
-            .ok_or_else(|| anyhow!(format!("Unable to parse target triple: {}", other.0)))?;
+        let host = self.parse()?;
+        let target = other.parse()?;
         // First obvious check is OS, if that doesn't match there's no chance
-        let ret = if partial_self.os != partial_other.os {
-            false
-        } else if partial_self.os.as_deref() == Some("pc-windows") {
+        if (&host.vendor, &host.os) != (&target.vendor, &target.os) {
+            return Ok(RunSupport::Unsupported {
+                reason: "it is for another operating system".to_owned(),
+            });
+        }
+
+        let cannot_run = format!("{self} cannot run {} binaries", target.arch);
+        if host.os == "windows" {
             // Windows is a special case here: we can run gnu and msvc on the same system,
             // x86_64 can run i686, and aarch64 can run i686 through emulation
-            (partial_self.arch == partial_other.arch)
-                || (partial_self.arch.as_deref() == Some("x86_64")
-                    && partial_other.arch.as_deref() == Some("i686"))
-                || (partial_self.arch.as_deref() == Some("aarch64")
-                    && partial_other.arch.as_deref() == Some("i686"))
-        } else {
+            return Ok(
+                match (host.arch == target.arch)
+                    || (host.arch == "x86_64" && target.arch == "i686")
+                    || (host.arch == "aarch64" && target.arch == "i686")
+                {
+                    true => RunSupport::Native,
+                    false => RunSupport::Unsupported { reason: cannot_run },
+                },
+            );
+        }
+        if host.os != "linux" {
             // For other OSes, for now, we assume other toolchains won't run
-            false
+            return Ok(RunSupport::Unsupported { reason: cannot_run });
+        }
+
+        // Linux runs some 32-bit binaries natively, given their C library
+        // is installed alongside the native one.
+        let root = host_root(process);
+        let loader = multiarch_loader(&host, &target);
+        if let Some(loader) = loader {
+            if root.join(loader).exists() {
+                return Ok(RunSupport::Native);
+            }
+        }
+        if let Some(interpreter) =
+            binfmt_interpreter(&root.join("proc/sys/fs/binfmt_misc"), &target)
+        {
+            return Ok(RunSupport::Emulated { interpreter });
+        }
+        let reason = match loader {
+            Some(loader) => format!("its C library is not installed (/{loader} is missing)"),
+            None => format!(
+                "{cannot_run} natively, and no binfmt_misc interpreter is registered for them"
+            ),
         };
-        Ok(ret)
+        Ok(RunSupport::Unsupported { reason })
     }
 }
 
@@ -758,6 +1077,63 @@ impl FromStr for Profile {
     }
 }
 
+/// A profile defined in the settings file, as one of the built-in profiles
+/// with more components.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CustomProfile {
+    #[serde(default = "CustomProfile::default_base")]
+    pub base: Profile,
+    #[serde(default)]
+    pub components: Vec<String>,
+}
+
+impl CustomProfile {
+    fn default_base() -> Profile {
+        Profile::Minimal
+    }
+}
+
+/// The profile to install a toolchain with: one of the built-in profiles, or
+/// a custom one resolved from its definition.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ResolvedProfile {
+    name: String,
+    pub(crate) base: Profile,
+    /// The components added to those of `base`.
+    pub(crate) components: Vec<String>,
+}
+
+impl ResolvedProfile {
+    pub(crate) fn custom(name: &str, profile: &CustomProfile) -> Self {
+        Self {
+            name: name.to_owned(),
+            base: profile.base,
+            components: profile.components.clone(),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl From<Profile> for ResolvedProfile {
+    fn from(profile: Profile) -> Self {
+        Self {
+            name: profile.as_str().to_owned(),
+            base: profile,
+            components: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for ResolvedProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
 impl fmt::Display for TargetTriple {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -808,7 +1184,7 @@ impl fmt::Display for Profile {
 pub(crate) struct DistOptions<'a> {
     pub(crate) cfg: &'a Cfg<'a>,
     pub(crate) toolchain: &'a ToolchainDesc,
-    pub(crate) profile: Profile,
+    pub(crate) profile: &'a ResolvedProfile,
     pub(crate) update_hash: Option<&'a Path>,
     pub(crate) dl_cfg: DownloadCfg<'a>,
     /// --force bool is whether to force an update/install
@@ -825,20 +1201,56 @@ pub(crate) struct DistOptions<'a> {
     pub(crate) targets: &'a [&'a str],
 }
 
+/// What [`update_from_dist_`] did to a toolchain that wasn't up to date.
+enum DistUpdate {
+    /// The toolchain was updated to the manifest with this hash.
+    Updated(String),
+    /// The changes an update would make, computed in a dry run.
+    Planned(UpdatePlan),
+}
+
 // Installs or updates a toolchain from a dist server. If an initial
 // install then it will be installed with the default components. If
 // an upgrade then all the existing components will be upgraded.
 //
-// Returns the manifest's hash if anything changed.
+// Returns the hash of the new manifest, if anything changed.
 #[tracing::instrument(level = "trace", err(level = "trace"), skip_all, fields(profile=format!("{:?}", opts.profile), prefix=prefix.path().to_string_lossy().to_string()))]
 pub(crate) async fn update_from_dist(
     prefix: &InstallPrefix,
     opts: &DistOptions<'_>,
 ) -> Result<Option<String>> {
+    match update_from_dist_(prefix, opts, false).await? {
+        Some(DistUpdate::Updated(hash)) => Ok(Some(hash)),
+        Some(DistUpdate::Planned(_)) => bail!("update of '{}' was only planned", opts.toolchain),
+        None => Ok(None),
+    }
+}
+
+/// Computes the changes [`update_from_dist`] would make, leaving the
+/// toolchain untouched.
+///
+/// Returns `None` if it is up to date.
+#[tracing::instrument(level = "trace", err(level = "trace"), skip_all, fields(profile=format!("{:?}", opts.profile), prefix=prefix.path().to_string_lossy().to_string()))]
+pub(crate) async fn plan_from_dist(
+    prefix: &InstallPrefix,
+    opts: &DistOptions<'_>,
+) -> Result<Option<UpdatePlan>> {
+    match update_from_dist_(prefix, opts, true).await? {
+        Some(DistUpdate::Planned(plan)) => Ok(Some(plan)),
+        Some(DistUpdate::Updated(_)) => bail!("dry run of '{}' updated it", opts.toolchain),
+        None => Ok(None),
+    }
+}
+
+async fn update_from_dist_(
+    prefix: &InstallPrefix,
+    opts: &DistOptions<'_>,
+    dry_run: bool,
+) -> Result<Option<DistUpdate>> {
     let fresh_install = !prefix.path().exists();
     if let Some(hash) = opts.update_hash {
         // fresh_install means the toolchain isn't present, but hash_exists means there is a stray hash file
-        if fresh_install && Path::exists(hash) {
+        if fresh_install && Path::exists(hash) && !dry_run {
             (opts.dl_cfg.notify_handler)(Notification::StrayHash(hash));
             std::fs::remove_file(hash)?;
         }
@@ -904,6 +1316,7 @@ pub(crate) async fn update_from_dist(
             opts.force,
             opts.components,
             opts.targets,
+            dry_run,
             &mut fetched,
         )
         .await;
@@ -975,7 +1388,7 @@ pub(crate) async fn update_from_dist(
     };
 
     // Don't leave behind an empty / broken installation directory
-    if res.is_err() && fresh_install {
+    if res.is_err() && fresh_install && !dry_run {
         // FIXME Ignoring cascading errors
         let _ = utils::remove_dir("toolchain", prefix.path(), opts.dl_cfg.notify_handler);
     }
@@ -988,13 +1401,14 @@ async fn try_update_from_dist_(
     download: DownloadCfg<'_>,
     update_hash: Option<&Path>,
     toolchain: &ToolchainDesc,
-    profile: Option<Profile>,
+    profile: Option<&ResolvedProfile>,
     prefix: &InstallPrefix,
     force_update: bool,
     components: &[&str],
     targets: &[&str],
+    dry_run: bool,
     fetched: &mut String,
-) -> Result<Option<String>> {
+) -> Result<Option<DistUpdate>> {
     let toolchain_str = toolchain.to_string();
     let manifestation = Manifestation::open(prefix.clone(), toolchain.target.clone())?;
 
@@ -1021,7 +1435,7 @@ async fn try_update_from_dist_(
             ));
 
             let profile_components = match profile {
-                Some(profile) => m.get_profile_components(profile, &toolchain.target)?,
+                Some(profile) => m.get_profile_components(profile.base, &toolchain.target)?,
                 None => Vec::new(),
             };
 
@@ -1030,7 +1444,35 @@ async fn try_update_from_dist_(
             let rust_package = m.get_package("rust")?;
             let rust_target_package = rust_package.get_target(Some(&toolchain.target.clone()))?;
 
-            for component in components {
+            // The components a custom profile adds must at least be known to
+            // the manifest: one that isn't is most likely misspelt, or newer
+            // than the release, so trying older nightlies would not help.
+            let extra_components = profile.map(|p| &p.components[..]).unwrap_or_default();
+            for name in extra_components {
+                let mut component =
+                    Component::new(name.clone(), Some(toolchain.target.clone()), false);
+                if let Some(renamed) = m.rename_component(&component) {
+                    component = renamed;
+                }
+                let known = rust_target_package
+                    .components
+                    .iter()
+                    .any(|c| c.short_name_in_manifest() == component.short_name_in_manifest());
+                if !known {
+                    let profile = profile.map(ResolvedProfile::name).unwrap_or_default();
+                    bail!(
+                        "component '{name}' of profile '{profile}' is not in the manifest of '{toolchain}' dated {}\n\
+                        help: remove it from the profile's components in the settings file, or install a toolchain that has it",
+                        m.date
+                    );
+                }
+            }
+
+            for component in components
+                .iter()
+                .copied()
+                .chain(extra_components.iter().map(String::as_str))
+            {
                 let mut component =
                     Component::new(component.to_string(), Some(toolchain.target.clone()), false);
                 if let Some(renamed) = m.rename_component(&component) {
@@ -1066,21 +1508,36 @@ async fn try_update_from_dist_(
 
             fetched.clone_from(&m.date);
 
-            return match manifestation
-                .update(
-                    &m,
-                    changes,
-                    force_update,
-                    &download,
-                    &toolchain.manifest_name(),
-                    true,
-                )
-                .await
-            {
-                Ok(status) => match status {
-                    UpdateStatus::Unchanged => Ok(None),
-                    UpdateStatus::Changed => Ok(Some(hash)),
-                },
+            let result = if dry_run {
+                manifestation
+                    .plan(
+                        &m,
+                        changes,
+                        force_update,
+                        &download,
+                        &toolchain.manifest_name(),
+                    )
+                    .await
+                    .map(|plan| Some(DistUpdate::Planned(plan)))
+            } else {
+                manifestation
+                    .update(
+                        &m,
+                        changes,
+                        force_update,
+                        &download,
+                        &toolchain.manifest_name(),
+                        true,
+                    )
+                    .await
+                    .map(|status| match status {
+                        UpdateStatus::Unchanged => None,
+                        UpdateStatus::Changed => Some(DistUpdate::Updated(hash)),
+                    })
+            };
+
+            return match result {
+                Ok(update) => Ok(update),
                 Err(err) => match err.downcast_ref::<RustupError>() {
                     Some(RustupError::RequestedComponentsUnavailable {
                         components,
@@ -1129,6 +1586,13 @@ async fn try_update_from_dist_(
         },
     };
 
+    if dry_run {
+        bail!(
+            "dry runs are not supported for '{}', which only has a v1 manifest",
+            toolchain.manifest_name()
+        );
+    }
+
     let result = manifestation
         .update_v1(
             &manifest,
@@ -1137,7 +1601,8 @@ async fn try_update_from_dist_(
             &download.notify_handler,
             download.process,
         )
-        .await;
+        .await
+        .map(|hash| hash.map(DistUpdate::Updated));
 
     // inspect, determine what context to add, then process afterwards.
     if let Err(e) = &result {
@@ -1396,7 +1861,7 @@ mod tests {
                 "x86_64-unknown-linux-gnu",
                 // Not compatible beyond itself
                 &[],
-                // Even 32bit linux is considered not compatible by default
+                // Even 32bit linux is considered not compatible without its C library
                 &["i686-unknown-linux-gnu"],
             ),
             (
@@ -1432,15 +1897,31 @@ mod tests {
             ),
         ];
 
+        let root = tempfile::tempdir().unwrap();
+        let tp = crate::process::TestProcess::with_vars(
+            [(
+                "RUSTUP_OVERRIDE_HOST_ROOT".to_owned(),
+                root.path().display().to_string(),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let can_run = |host: &TargetTriple, other: &TargetTriple| {
+            !matches!(
+                host.run_support(other, &tp.process).unwrap(),
+                RunSupport::Unsupported { .. }
+            )
+        };
+
         for &(host, compatible, incompatible) in CASES {
             println!("host={host}");
             let host = TargetTriple::new(host);
-            assert!(host.can_run(&host).unwrap(), "host wasn't self-compatible");
+            assert!(can_run(&host, &host), "host wasn't self-compatible");
             for &other in compatible.iter() {
                 println!("compatible with {other}");
                 let other = TargetTriple::new(other);
                 assert!(
-                    host.can_run(&other).unwrap(),
+                    can_run(&host, &other),
                     "host and other were unexpectedly incompatible"
                 );
             }
@@ -1448,10 +1929,200 @@ mod tests {
                 println!("incompatible with {other}");
                 let other = TargetTriple::new(other);
                 assert!(
-                    !host.can_run(&other).unwrap(),
+                    !can_run(&host, &other),
                     "host and other were unexpectedly compatible"
                 );
             }
         }
     }
+
+    #[test]
+    fn linux_multiarch_and_binfmt() {
+        let root = tempfile::tempdir().unwrap();
+        let tp = crate::process::TestProcess::with_vars(
+            [(
+                "RUSTUP_OVERRIDE_HOST_ROOT".to_owned(),
+                root.path().display().to_string(),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let host = TargetTriple::new("x86_64-unknown-linux-gnu");
+        let i686 = TargetTriple::new("i686-unknown-linux-gnu");
+        let aarch64 = TargetTriple::new("aarch64-unknown-linux-gnu");
+
+        assert_eq!(
+            host.run_support(&i686, &tp.process).unwrap(),
+            RunSupport::Unsupported {
+                reason: "its C library is not installed (/lib/ld-linux.so.2 is missing)".to_owned()
+            }
+        );
+        assert_eq!(
+            host.run_support(&aarch64, &tp.process).unwrap(),
+            RunSupport::Unsupported {
+                reason: "x86_64-unknown-linux-gnu cannot run aarch64 binaries natively, \
+                         and no binfmt_misc interpreter is registered for them"
+                    .to_owned()
+            }
+        );
+
+        std::fs::create_dir(root.path().join("lib")).unwrap();
+        std::fs::write(root.path().join("lib/ld-linux.so.2"), "").unwrap();
+        assert_eq!(
+            host.run_support(&i686, &tp.process).unwrap(),
+            RunSupport::Native
+        );
+
+        let binfmt = root.path().join("proc/sys/fs/binfmt_misc");
+        std::fs::create_dir_all(&binfmt).unwrap();
+        std::fs::write(binfmt.join("status"), "enabled\n").unwrap();
+        std::fs::write(binfmt.join("register"), "").unwrap();
+        std::fs::write(
+            binfmt.join("qemu-aarch64"),
+            "enabled
+interpreter /usr/bin/qemu-aarch64-static
+flags: F
+offset 0
+magic 7f454c460201010000000000000000000200b700
+mask ffffffffffffff00fffffffffffffffffeffffff
+",
+        )
+        .unwrap();
+        assert_eq!(
+            host.run_support(&aarch64, &tp.process).unwrap(),
+            RunSupport::Emulated {
+                interpreter: PathBuf::from("/usr/bin/qemu-aarch64-static")
+            }
+        );
+        // The interpreter doesn't match other architectures.
+        assert!(matches!(
+            host.run_support(
+                &TargetTriple::new("riscv64gc-unknown-linux-gnu"),
+                &tp.process
+            )
+            .unwrap(),
+            RunSupport::Unsupported { .. }
+        ));
+
+        std::fs::write(binfmt.join("status"), "disabled\n").unwrap();
+        assert!(matches!(
+            host.run_support(&aarch64, &tp.process).unwrap(),
+            RunSupport::Unsupported { .. }
+        ));
+    }
+
+    /// A minimal ELF binary with `interpreter` in its `PT_INTERP` header.
+    #[cfg(not(windows))]
+    fn mock_elf(is_64bit: bool, is_big_endian: bool, interpreter: &str) -> Vec<u8> {
+        let mut elf = vec![0; 0x100];
+        let mut write = |offset: usize, len: usize, value: u64| {
+            let bytes = match is_big_endian {
+                true => value.to_be_bytes()[8 - len..].to_vec(),
+                false => value.to_le_bytes()[..len].to_vec(),
+            };
+            elf[offset..offset + len].copy_from_slice(&bytes);
+        };
+        let interp_offset = 0x80;
+        let interp_len = interpreter.len() as u64 + 1;
+        if is_64bit {
+            write(0x20, 8, 0x40);
+            write(0x36, 2, 0x38);
+            write(0x38, 2, 1);
+            write(0x40, 4, 3);
+            write(0x48, 8, interp_offset);
+            write(0x60, 8, interp_len);
+        } else {
+            write(0x1c, 4, 0x34);
+            write(0x2a, 2, 0x20);
+            write(0x2c, 2, 1);
+            write(0x34, 4, 3);
+            write(0x38, 4, interp_offset);
+            write(0x44, 4, interp_len);
+        }
+        elf[..4].copy_from_slice(b"\x7fELF");
+        elf[4] = if is_64bit { 2 } else { 1 };
+        elf[5] = if is_big_endian { 2 } else { 1 };
+        let interp_offset = interp_offset as usize;
+        elf[interp_offset..interp_offset + interpreter.len()]
+            .copy_from_slice(interpreter.as_bytes());
+        elf
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_elf_interpreter() {
+        for (is_64bit, is_big_endian, interpreter) in [
+            (true, false, "/lib64/ld-linux-x86-64.so.2"),
+            (true, true, "/lib/ld64.so.1"),
+            (false, false, "/lib/ld-musl-armhf.so.1"),
+            (false, true, "/lib/ld.so.1"),
+        ] {
+            let elf = mock_elf(is_64bit, is_big_endian, interpreter);
+            assert_eq!(elf_interpreter(&elf), Some(interpreter));
+        }
+
+        // Statically linked binaries have no interpreter.
+        let mut elf = mock_elf(true, false, "");
+        elf[0x40] = 1;
+        assert_eq!(elf_interpreter(&elf), None);
+        assert_eq!(elf_interpreter(b"#!/bin/sh\n"), None);
+        assert_eq!(elf_interpreter(&elf[..0x30]), None);
+
+        // Program header offsets that overflow are rejected, not wrapped.
+        let mut elf = mock_elf(true, false, "/lib64/ld-linux-x86-64.so.2");
+        elf[0x20..0x28].fill(0xff);
+        elf[0x38] = 2;
+        assert_eq!(elf_interpreter(&elf), None);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_host_libc() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().display().to_string();
+        let tp = crate::process::TestProcess::with_vars(
+            [("PATH".to_owned(), path.clone())].into_iter().collect(),
+        );
+
+        std::fs::write(
+            dir.path().join("sh"),
+            mock_elf(true, false, "/lib/ld-musl-x86_64.so.1"),
+        )
+        .unwrap();
+        assert_eq!(host_libc(&tp.process), Libc::Musl);
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        assert_eq!(
+            TargetTriple::from_host(&tp.process).unwrap(),
+            TargetTriple::new("x86_64-unknown-linux-musl")
+        );
+
+        std::fs::write(
+            dir.path().join("sh"),
+            mock_elf(true, false, "/lib64/ld-linux-x86-64.so.2"),
+        )
+        .unwrap();
+        assert_eq!(host_libc(&tp.process), Libc::Gnu);
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        assert_eq!(
+            TargetTriple::from_host(&tp.process).unwrap(),
+            TargetTriple::new("x86_64-unknown-linux-gnu")
+        );
+
+        // The override takes precedence over any detection.
+        let tp = crate::process::TestProcess::with_vars(
+            [
+                ("PATH".to_owned(), path),
+                (
+                    "RUSTUP_OVERRIDE_HOST_TRIPLE".to_owned(),
+                    "aarch64-unknown-linux-gnu".to_owned(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(
+            TargetTriple::from_host(&tp.process).unwrap(),
+            TargetTriple::new("aarch64-unknown-linux-gnu")
+        );
+    }
 }
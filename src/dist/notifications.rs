@@ -18,6 +18,7 @@ pub enum Notification<'a> {
     NoUpdateHash(&'a Path),
     ChecksumValid(&'a str),
     FileAlreadyDownloaded,
+    FileNotModified(&'a str),
     CachedFileChecksumFailed,
     RollingBack,
     ExtensionNotInstalled(&'a str),
@@ -36,6 +37,8 @@ pub enum Notification<'a> {
     StrayHash(&'a Path),
     SignatureInvalid(&'a str),
     RetryingDownload(&'a str),
+    DownloadingPatch(&'a str),
+    PatchFailed(&'a str, &'a anyhow::Error),
 }
 
 impl<'a> From<crate::utils::Notification<'a>> for Notification<'a> {
@@ -59,6 +62,9 @@ impl<'a> Notification<'a> {
             ChecksumValid(_)
             | NoUpdateHash(_)
             | FileAlreadyDownloaded
+            | FileNotModified(_)
+            | DownloadingPatch(_)
+            | PatchFailed(_, _)
             | DownloadingLegacyManifest => NotificationLevel::Debug,
             Extracting(_, _)
             | DownloadingComponent(_, _, _)
@@ -100,6 +106,7 @@ impl<'a> Display for Notification<'a> {
             NoUpdateHash(path) => write!(f, "no update hash at: '{}'", path.display()),
             ChecksumValid(_) => write!(f, "checksum passed"),
             FileAlreadyDownloaded => write!(f, "reusing previously downloaded file"),
+            FileNotModified(url) => write!(f, "'{url}' is unchanged since it was last downloaded"),
             CachedFileChecksumFailed => write!(f, "bad checksum for cached download"),
             RollingBack => write!(f, "rolling back changes"),
             ExtensionNotInstalled(c) => write!(f, "extension '{c}' was not installed"),
@@ -181,6 +188,13 @@ impl<'a> Display for Notification<'a> {
             }
             SignatureInvalid(url) => write!(f, "Signature verification failed for '{url}'"),
             RetryingDownload(url) => write!(f, "retrying download for '{url}'"),
+            DownloadingPatch(url) => write!(f, "downloading patch from '{url}'"),
+            PatchFailed(url, e) => {
+                write!(
+                    f,
+                    "failed to apply patch '{url}', downloading the full archive instead: {e:#}"
+                )
+            }
         }
     }
 }
@@ -139,6 +139,10 @@ impl Context {
         }
     }
 
+    pub(crate) fn root_directory(&self) -> &Path {
+        &self.root_directory
+    }
+
     pub(crate) fn create_root(&self) -> Result<bool> {
         raw::ensure_dir_exists(&self.root_directory, |p| {
             (self.notify_handler)(Notification::CreatingRoot(p));
@@ -1,6 +1,11 @@
+use std::fmt;
 use std::sync::LazyLock;
 
 use regex::Regex;
+use strsim::damerau_levenshtein;
+
+use super::TargetTriple;
+use crate::errors::RustupError;
 
 pub mod known;
 
@@ -53,6 +58,180 @@ impl PartialTargetTriple {
     }
 }
 
+/// A complete target triple, such as `armv7-unknown-linux-gnueabihf`, split
+/// into its parts. The vendor, environment and ABI are optional, as in
+/// `aarch64-linux-android` or `wasm32-wasip1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedTargetTriple {
+    pub arch: String,
+    pub vendor: Option<String>,
+    pub os: String,
+    pub env: Option<String>,
+    pub abi: Option<String>,
+}
+
+/// The environments which may be followed by an ABI, as in `gnueabihf`.
+const ENVS_WITH_ABI: &[&str] = &["gnu", "musl", "uclibc", "android", "newlib"];
+
+/// The ABIs an environment may end with, or which may stand for it.
+const ABIS: &[&str] = &[
+    "eabi",
+    "eabihf",
+    "abi64",
+    "abiv2",
+    "abiv2hf",
+    "spe",
+    "x32",
+    "ilp32",
+    "softfloat",
+];
+
+impl ParsedTargetTriple {
+    pub(crate) fn new(name: &str) -> Result<Self, RustupError> {
+        let invalid = || RustupError::InvalidTargetTriple {
+            triple: name.to_owned(),
+            suggestion: suggest_triple(name),
+        };
+        let partial = PartialTargetTriple::new(name).ok_or_else(invalid)?;
+        let (Some(arch), Some(os)) = (partial.arch, partial.os) else {
+            return Err(invalid());
+        };
+
+        let (vendor, os) = match os.split_once('-') {
+            Some((vendor, os)) => (Some(vendor.to_owned()), os.to_owned()),
+            None => (None, os),
+        };
+        let (env, abi) = match partial.env.as_deref() {
+            None => (None, None),
+            Some(env) if ABIS.contains(&env) => (None, Some(env.to_owned())),
+            Some(env) => ENVS_WITH_ABI
+                .iter()
+                .find_map(|prefix| {
+                    let abi = env.strip_prefix(prefix)?.trim_start_matches('_');
+                    ABIS.contains(&abi)
+                        .then(|| (Some(prefix.to_string()), Some(abi.to_owned())))
+                })
+                .unwrap_or_else(|| (Some(env.to_owned()), None)),
+        };
+
+        Ok(Self {
+            arch,
+            vendor,
+            os,
+            env,
+            abi,
+        })
+    }
+}
+
+/// Suggests the known triple closest to `name`, correcting each of its
+/// parts by a few characters at most.
+fn suggest_triple(name: &str) -> Option<String> {
+    // Suggest only for very small differences
+    const MAX_DISTANCE: usize = 3;
+
+    fn closest<'a>(list: &[&'a str], part: &str) -> Option<(usize, &'a str)> {
+        list.iter()
+            .map(|known| (damerau_levenshtein(part, known), *known))
+            .min()
+            .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+    }
+
+    let parts = name.split('-').collect::<Vec<_>>();
+    let (arch_distance, arch) = closest(known::LIST_ARCHS, parts.first()?)?;
+    // The OS may have a vendor, so try it as both one and two parts.
+    let (distance, suggestion) = (1..parts.len().min(3))
+        .filter_map(|os_len| {
+            let (os_distance, os) = closest(known::LIST_OSES, &parts[1..1 + os_len].join("-"))?;
+            let env = parts[1 + os_len..].join("-");
+            let (env_distance, env) = match env.is_empty() {
+                true => (0, None),
+                false => closest(known::LIST_ENVS, &env).map(|(d, env)| (d, Some(env)))?,
+            };
+            let suggestion = match env {
+                Some(env) => format!("{arch}-{os}-{env}"),
+                None => format!("{arch}-{os}"),
+            };
+            Some((arch_distance + os_distance + env_distance, suggestion))
+        })
+        .min()?;
+    (distance <= MAX_DISTANCE && suggestion != name).then_some(suggestion)
+}
+
+/// The tier 1 targets, which are guaranteed to work and ship host tools.
+const TIER_1: &[&str] = &[
+    "aarch64-apple-darwin",
+    "aarch64-pc-windows-msvc",
+    "aarch64-unknown-linux-gnu",
+    "i686-pc-windows-msvc",
+    "i686-unknown-linux-gnu",
+    "x86_64-pc-windows-gnu",
+    "x86_64-pc-windows-msvc",
+    "x86_64-unknown-linux-gnu",
+];
+
+/// The tier 2 targets which ship host tools, as opposed to only `rust-std`.
+const TIER_2_HOST: &[&str] = &[
+    "aarch64-pc-windows-gnullvm",
+    "aarch64-unknown-linux-musl",
+    "arm-unknown-linux-gnueabi",
+    "arm-unknown-linux-gnueabihf",
+    "armv7-unknown-linux-gnueabihf",
+    "i686-pc-windows-gnu",
+    "loongarch64-unknown-linux-gnu",
+    "loongarch64-unknown-linux-musl",
+    "powerpc-unknown-linux-gnu",
+    "powerpc64-unknown-linux-gnu",
+    "powerpc64le-unknown-linux-gnu",
+    "powerpc64le-unknown-linux-musl",
+    "riscv64gc-unknown-linux-gnu",
+    "riscv64gc-unknown-linux-musl",
+    "s390x-unknown-linux-gnu",
+    "x86_64-apple-darwin",
+    "x86_64-pc-windows-gnullvm",
+    "x86_64-unknown-freebsd",
+    "x86_64-unknown-illumos",
+    "x86_64-unknown-linux-musl",
+    "x86_64-unknown-netbsd",
+];
+
+/// How well the Rust project supports a target, see
+/// <https://doc.rust-lang.org/nightly/rustc/platform-support.html>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Tier {
+    One,
+    TwoWithHostTools,
+    Two,
+    Three,
+}
+
+impl Tier {
+    /// The tier of `triple`, whose standard library is `distributed` in
+    /// release channels for tier 2 targets and up.
+    pub(crate) fn of(triple: &TargetTriple, distributed: bool) -> Self {
+        if TIER_1.contains(&&**triple) {
+            Self::One
+        } else if TIER_2_HOST.contains(&&**triple) {
+            Self::TwoWithHostTools
+        } else if distributed {
+            Self::Two
+        } else {
+            Self::Three
+        }
+    }
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::One => "1 (with host tools)",
+            Self::TwoWithHostTools => "2 (with host tools)",
+            Self::Two => "2",
+            Self::Three => "3",
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -111,4 +290,74 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_parsed_target_triple_new() {
+        let cases = [
+            (
+                "x86_64-unknown-linux-gnu",
+                ("x86_64", Some("unknown"), "linux", Some("gnu"), None),
+            ),
+            (
+                "armv7-unknown-linux-gnueabihf",
+                (
+                    "armv7",
+                    Some("unknown"),
+                    "linux",
+                    Some("gnu"),
+                    Some("eabihf"),
+                ),
+            ),
+            (
+                "aarch64-linux-android",
+                ("aarch64", None, "linux", Some("android"), None),
+            ),
+            (
+                "thumbv7em-none-eabihf",
+                ("thumbv7em", None, "none", None, Some("eabihf")),
+            ),
+            ("wasm32-wasip1", ("wasm32", None, "wasip1", None, None)),
+            (
+                "x86_64-pc-windows-gnullvm",
+                ("x86_64", Some("pc"), "windows", Some("gnullvm"), None),
+            ),
+        ];
+        for (input, (arch, vendor, os, env, abi)) in cases {
+            let expected = ParsedTargetTriple {
+                arch: arch.to_owned(),
+                vendor: vendor.map(String::from),
+                os: os.to_owned(),
+                env: env.map(String::from),
+                abi: abi.map(String::from),
+            };
+            assert_eq!(ParsedTargetTriple::new(input).unwrap(), expected);
+        }
+
+        for input in [
+            "",
+            "x86_64",
+            "unknown-linux-gnu",
+            "x86_64-unknown-linux-gnu-",
+        ] {
+            assert!(ParsedTargetTriple::new(input).is_err(), "input: `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_suggest_triple() {
+        assert_eq!(
+            suggest_triple("x68_64-unknown-linux-gnu").as_deref(),
+            Some("x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            suggest_triple("aarch64-apple-drawin").as_deref(),
+            Some("aarch64-apple-darwin")
+        );
+        assert_eq!(
+            suggest_triple("x86_64-pc-windows-mscv").as_deref(),
+            Some("x86_64-pc-windows-msvc")
+        );
+        assert_eq!(suggest_triple("anything"), None);
+        assert_eq!(suggest_triple("sparc-foo-bar-baz"), None);
+    }
 }
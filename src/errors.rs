@@ -16,6 +16,7 @@ use crate::{
         Channel, TargetTriple, ToolchainDesc,
     },
     toolchain::{PathBasedToolchainName, ToolchainName},
+    utils::units::{Size, Unit, UnitMode},
 };
 
 /// A type erasing thunk for the retry crate to permit use with anyhow. See <https://github.com/dtolnay/anyhow/issues/149>
@@ -35,6 +36,11 @@ pub enum RustupError {
     ComponentMissingFile { name: String, path: PathBuf },
     #[error("could not create {name} directory: '{}'", .path.display())]
     CreatingDirectory { name: &'static str, path: PathBuf },
+    #[error("invalid target triple: '{}'{}", .triple, suggest_message(.suggestion))]
+    InvalidTargetTriple {
+        triple: String,
+        suggestion: Option<String>,
+    },
     #[error("invalid toolchain name: '{0}'")]
     InvalidToolchainName(String),
     #[error("could not create link from '{}' to '{}'", .src.display(), .dest.display())]
@@ -50,6 +56,8 @@ pub enum RustupError {
         expected: String,
         calculated: String,
     },
+    #[error("signature verification failed for '{url}'")]
+    SignatureFailed { url: String },
     #[error("failed to install component: '{name}', detected conflict: '{}'", .path.display())]
     ComponentConflict { name: String, path: PathBuf },
     #[error("toolchain '{0}' does not support components")]
@@ -62,6 +70,17 @@ pub enum RustupError {
     DownloadingFile { url: Url, path: PathBuf },
     #[error("could not download file from '{url}' to '{}'", .path.display())]
     DownloadNotExists { url: Url, path: PathBuf },
+    #[error(
+        "not enough disk space in '{}': {} needed, but only {} available\n\
+        help: free up some space, e.g. by removing unused toolchains with `rustup toolchain uninstall`, \
+        or set RUSTUP_HOME to a directory on a larger volume",
+        .path.display(), display_size(*.required), display_size(*.available)
+    )]
+    InsufficientDiskSpace {
+        path: PathBuf,
+        required: u64,
+        available: u64,
+    },
     #[error("Missing manifest in toolchain '{}'", .0)]
     MissingManifest(ToolchainDesc),
     #[error("server sent a broken manifest: missing package for component {0}")]
@@ -138,6 +157,13 @@ pub enum RustupError {
     IOError(#[from] std::io::Error),
 }
 
+fn display_size(bytes: u64) -> String {
+    Size::new(bytes as usize, Unit::B, UnitMode::Norm)
+        .to_string()
+        .trim_start()
+        .to_owned()
+}
+
 fn suggest_message(suggestion: &Option<String>) -> String {
     if let Some(suggestion) = suggestion {
         format!("; did you mean '{}'?", suggestion)
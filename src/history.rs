@@ -0,0 +1,358 @@
+//! A log of the changes made to the installed toolchains and to which
+//! toolchains are used by default or in overridden directories.
+//!
+//! Before a toolchain installed from a channel manifest is updated or
+//! uninstalled, a snapshot of its manifest and component list is kept with
+//! the log entry, so that it can be reinstalled exactly as it was.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::dist::config::Config;
+use crate::dist::manifest::Manifest;
+use crate::dist::manifestation::{CONFIG_FILE, DIST_MANIFEST};
+use crate::dist::prefix::InstallPrefix;
+use crate::errors::RustupError;
+use crate::utils::utils;
+
+const LOG_FILE: &str = "history.toml";
+
+/// The number of entries kept. Older ones are dropped along with their
+/// snapshots, which are the size of a channel manifest each.
+const MAX_ENTRIES: usize = 50;
+
+#[derive(Debug)]
+pub(crate) struct History {
+    dir: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Action {
+    Install,
+    Update,
+    Uninstall,
+    Rollback,
+    Default,
+    Override,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Install => "install",
+            Self::Update => "update",
+            Self::Uninstall => "uninstall",
+            Self::Rollback => "rollback",
+            Self::Default => "default",
+            Self::Override => "override",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Entry {
+    pub(crate) id: u64,
+    /// Seconds since the Unix epoch.
+    pub(crate) time: u64,
+    pub(crate) action: Action,
+    /// The toolchain changed, or made the default or an override. It is
+    /// missing when the default or an override is unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) toolchain: Option<String>,
+    /// The directory an override applies to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<PathBuf>,
+    /// The toolchain that was the default or override before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) previous_toolchain: Option<String>,
+    /// The date of the manifest the toolchain was installed from before the
+    /// change, set when a snapshot was kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) previous_date: Option<String>,
+    /// The components the toolchain had before the change.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) previous_components: Vec<String>,
+    /// The entry whose earlier state a rollback restored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) restored: Option<u64>,
+}
+
+impl Entry {
+    /// The time of the change, formatted for display.
+    pub(crate) fn time(&self) -> String {
+        i64::try_from(self.time)
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_else(|| self.time.to_string())
+    }
+
+    pub(crate) fn has_snapshot(&self) -> bool {
+        self.previous_date.is_some()
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Log {
+    #[serde(default)]
+    entries: Vec<Entry>,
+}
+
+/// A change to record in the history.
+#[derive(Debug)]
+pub(crate) struct Change {
+    action: Action,
+    toolchain: Option<String>,
+    path: Option<PathBuf>,
+    previous_toolchain: Option<String>,
+    snapshot: Option<Snapshot>,
+    restored: Option<u64>,
+}
+
+impl Change {
+    pub(crate) fn new(action: Action, toolchain: Option<String>) -> Self {
+        Self {
+            action,
+            toolchain,
+            path: None,
+            previous_toolchain: None,
+            snapshot: None,
+            restored: None,
+        }
+    }
+
+    pub(crate) fn path(mut self, path: PathBuf) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    pub(crate) fn previous_toolchain(mut self, toolchain: Option<String>) -> Self {
+        self.previous_toolchain = toolchain;
+        self
+    }
+
+    pub(crate) fn snapshot(mut self, snapshot: Option<Snapshot>) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    pub(crate) fn restored(mut self, id: u64) -> Self {
+        self.restored = Some(id);
+        self
+    }
+}
+
+/// The state of a toolchain installed from a channel manifest.
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+    manifest: String,
+    config: String,
+    date: String,
+    components: Vec<String>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the toolchain installed at `prefix`, unless it
+    /// wasn't installed from a v2 manifest. A toolchain whose manifest can't
+    /// be read is left out of the history rather than failing the change.
+    pub(crate) fn take(prefix: &InstallPrefix) -> Option<Self> {
+        Self::read(prefix).unwrap_or_else(|e| {
+            warn!(
+                "could not record the state of '{}' in the history: {e:#}",
+                prefix.path().display()
+            );
+            None
+        })
+    }
+
+    fn read(prefix: &InstallPrefix) -> Result<Option<Self>> {
+        let manifest_path = prefix.manifest_file(DIST_MANIFEST);
+        let config_path = prefix.manifest_file(CONFIG_FILE);
+        if !utils::is_file(&manifest_path) || !utils::is_file(&config_path) {
+            return Ok(None);
+        }
+
+        let manifest = utils::read_file("installed manifest", &manifest_path)?;
+        let config = utils::read_file("dist config", &config_path)?;
+        let parsed_manifest =
+            Manifest::parse(&manifest).with_context(|| RustupError::ParsingFile {
+                name: "manifest",
+                path: manifest_path,
+            })?;
+        let parsed_config = Config::parse(&config).with_context(|| RustupError::ParsingFile {
+            name: "config",
+            path: config_path,
+        })?;
+        let components = parsed_config
+            .components
+            .iter()
+            .map(|c| c.name(&parsed_manifest))
+            .collect();
+
+        Ok(Some(Self {
+            manifest,
+            config,
+            date: parsed_manifest.date,
+            components,
+        }))
+    }
+}
+
+impl History {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// All the recorded entries, oldest first.
+    pub(crate) fn entries(&self) -> Result<Vec<Entry>> {
+        Ok(self.read_log()?.entries)
+    }
+
+    pub(crate) fn record(&self, change: Change) -> Result<()> {
+        let mut log = self.read_log()?;
+        let id = log.entries.last().map_or(1, |e| e.id + 1);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        let (previous_date, previous_components) = match change.snapshot {
+            Some(snapshot) => {
+                let dir = self.snapshot_dir(id);
+                fs::create_dir_all(&dir).with_context(|| RustupError::CreatingDirectory {
+                    name: "history",
+                    path: dir.clone(),
+                })?;
+                utils::write_file(
+                    "manifest snapshot",
+                    &dir.join(DIST_MANIFEST),
+                    &snapshot.manifest,
+                )?;
+                utils::write_file("config snapshot", &dir.join(CONFIG_FILE), &snapshot.config)?;
+                (Some(snapshot.date), snapshot.components)
+            }
+            None => (None, Vec::new()),
+        };
+
+        log.entries.push(Entry {
+            id,
+            time,
+            action: change.action,
+            toolchain: change.toolchain,
+            path: change.path,
+            previous_toolchain: change.previous_toolchain,
+            previous_date,
+            previous_components,
+            restored: change.restored,
+        });
+        let excess = log.entries.len().saturating_sub(MAX_ENTRIES);
+        for entry in log.entries.drain(..excess) {
+            let dir = self.snapshot_dir(entry.id);
+            if dir.exists() {
+                fs::remove_dir_all(&dir).with_context(|| RustupError::RemovingDirectory {
+                    name: "history",
+                    path: dir.clone(),
+                })?;
+            }
+        }
+
+        fs::create_dir_all(&self.dir).with_context(|| RustupError::CreatingDirectory {
+            name: "history",
+            path: self.dir.clone(),
+        })?;
+        let log = toml::to_string(&log).context("failed to serialize the history")?;
+        utils::write_file("history", &self.dir.join(LOG_FILE), &log)
+    }
+
+    /// Loads the manifest and configuration kept with `entry`.
+    pub(crate) fn snapshot(&self, entry: &Entry) -> Result<(Manifest, Config)> {
+        let dir = self.snapshot_dir(entry.id);
+        let manifest_path = dir.join(DIST_MANIFEST);
+        let manifest = utils::read_file("manifest snapshot", &manifest_path)?;
+        let manifest = Manifest::parse(&manifest).with_context(|| RustupError::ParsingFile {
+            name: "manifest snapshot",
+            path: manifest_path,
+        })?;
+        let config_path = dir.join(CONFIG_FILE);
+        let config = utils::read_file("config snapshot", &config_path)?;
+        let config = Config::parse(&config).with_context(|| RustupError::ParsingFile {
+            name: "config snapshot",
+            path: config_path,
+        })?;
+        Ok((manifest, config))
+    }
+
+    fn read_log(&self) -> Result<Log> {
+        let path = self.dir.join(LOG_FILE);
+        if !utils::is_file(&path) {
+            return Ok(Log::default());
+        }
+        let log = utils::read_file("history", &path)?;
+        toml::from_str(&log).with_context(|| RustupError::ParsingFile {
+            name: "history",
+            path,
+        })
+    }
+
+    fn snapshot_dir(&self, id: u64) -> PathBuf {
+        self.dir.join(id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn entry_ids(history: &History) -> Vec<u64> {
+        history.entries().unwrap().iter().map(|e| e.id).collect()
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped() {
+        let dir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        let history = History::new(dir.path().join("history"));
+
+        for _ in 0..MAX_ENTRIES + 2 {
+            history
+                .record(Change::new(Action::Default, Some("stable".to_owned())))
+                .unwrap();
+        }
+
+        let ids = entry_ids(&history);
+        assert_eq!(ids.len(), MAX_ENTRIES);
+        assert_eq!(ids[0], 3);
+        assert_eq!(*ids.last().unwrap(), MAX_ENTRIES as u64 + 2);
+    }
+
+    #[test]
+    fn overrides_keep_their_path() {
+        let dir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        let history = History::new(dir.path().join("history"));
+        let path = Path::new("/some/project").to_owned();
+
+        history
+            .record(
+                Change::new(Action::Override, None)
+                    .path(path.clone())
+                    .previous_toolchain(Some("nightly".to_owned())),
+            )
+            .unwrap();
+
+        let entries = history.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path.as_ref(), Some(&path));
+        assert_eq!(entries[0].toolchain, None);
+        assert_eq!(entries[0].previous_toolchain.as_deref(), Some("nightly"));
+        assert!(!entries[0].has_snapshot());
+    }
+}
@@ -8,6 +8,7 @@ use crate::{
     config::Cfg,
     dist::{self, prefix::InstallPrefix, DistOptions, Notification},
     errors::RustupError,
+    history::{Action, Change, Snapshot},
     notifications::Notification as RootNotification,
     toolchain::{CustomToolchainName, LocalToolchainName, Toolchain},
     utils::utils,
@@ -51,6 +52,11 @@ impl<'a> InstallMethod<'a> {
         }
 
         nh(RootNotification::ToolchainDirectory(&self.dest_path()));
+        let existed = self.dest_path().exists();
+        let snapshot = match self {
+            InstallMethod::Dist(_) => Snapshot::take(&InstallPrefix::from(self.dest_path())),
+            _ => None,
+        };
         let updated = self.run(&self.dest_path(), &|n| nh(n.into())).await?;
 
         let status = match updated {
@@ -73,10 +79,20 @@ impl<'a> InstallMethod<'a> {
         };
 
         // Final check, to ensure we're installed
-        match Toolchain::exists(self.cfg(), &self.local_name())? {
-            true => Ok(status),
-            false => Err(RustupError::ToolchainNotInstallable(self.dest_basename()).into()),
+        if !Toolchain::exists(self.cfg(), &self.local_name())? {
+            return Err(RustupError::ToolchainNotInstallable(self.dest_basename()).into());
+        }
+
+        if updated {
+            let action = match existed {
+                true => Action::Update,
+                false => Action::Install,
+            };
+            self.cfg()
+                .history
+                .record(Change::new(action, Some(self.dest_basename())).snapshot(snapshot))?;
         }
+        Ok(status)
     }
 
     async fn run(&self, path: &Path, notify_handler: &dyn Fn(Notification<'_>)) -> Result<bool> {
@@ -101,16 +117,15 @@ impl<'a> InstallMethod<'a> {
             }
             InstallMethod::Dist(opts) => {
                 let prefix = &InstallPrefix::from(path.to_owned());
-                let maybe_new_hash = dist::update_from_dist(prefix, opts).await?;
+                match dist::update_from_dist(prefix, opts).await? {
+                    Some(hash) => {
+                        if let Some(hash_file) = opts.update_hash {
+                            utils::write_file("update hash", hash_file, &hash)?;
+                        }
 
-                if let Some(hash) = maybe_new_hash {
-                    if let Some(hash_file) = opts.update_hash {
-                        utils::write_file("update hash", hash_file, &hash)?;
+                        Ok(true)
                     }
-
-                    Ok(true)
-                } else {
-                    Ok(false)
+                    None => Ok(false),
                 }
             }
         }
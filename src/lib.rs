@@ -77,6 +77,7 @@ pub mod dist;
 pub mod env_var;
 pub mod errors;
 mod fallback_settings;
+mod history;
 mod install;
 pub mod notifications;
 pub mod process;
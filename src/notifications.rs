@@ -18,6 +18,9 @@ pub(crate) enum Notification<'a> {
     SetOverrideToolchain(&'a Path, &'a str),
     SetProfile(&'a str),
     SetSelfUpdate(&'a str),
+    SetAutoInstallComponents(&'a str),
+    SetReleaseNotes(bool),
+    SetSelfUpdateVersion(Option<&'a str>),
     LookingForToolchain(&'a ToolchainDesc),
     ToolchainDirectory(&'a Path),
     UpdatingToolchain(&'a str),
@@ -26,6 +29,10 @@ pub(crate) enum Notification<'a> {
     UsingExistingToolchain(&'a ToolchainDesc),
     UninstallingToolchain(&'a ToolchainName),
     UninstalledToolchain(&'a ToolchainName),
+    HeldToolchain(&'a ToolchainDesc),
+    UnheldToolchain(&'a ToolchainDesc),
+    SkippingHeldToolchain(&'a ToolchainDesc),
+    HeldToolchainNotModified(&'a ToolchainDesc),
     UpdateHashMatches,
     UpgradingMetadata(MetadataVersion, MetadataVersion),
     MetadataUpgradeNotNeeded(MetadataVersion),
@@ -73,13 +80,21 @@ impl<'a> Notification<'a> {
             | SetOverrideToolchain(_, _)
             | SetProfile(_)
             | SetSelfUpdate(_)
+            | SetAutoInstallComponents(_)
+            | SetReleaseNotes(_)
+            | SetSelfUpdateVersion(_)
             | UsingExistingToolchain(_)
             | UninstallingToolchain(_)
             | UninstalledToolchain(_)
+            | HeldToolchain(_)
+            | UnheldToolchain(_)
+            | SkippingHeldToolchain(_)
             | UpgradingMetadata(_, _)
             | MetadataUpgradeNotNeeded(_) => NotificationLevel::Info,
             NonFatalError(_) => NotificationLevel::Error,
-            UpgradeRemovesToolchains | DuplicateToolchainFile { .. } => NotificationLevel::Warn,
+            UpgradeRemovesToolchains
+            | DuplicateToolchainFile { .. }
+            | HeldToolchainNotModified(_) => NotificationLevel::Warn,
         }
     }
 }
@@ -101,6 +116,15 @@ impl<'a> Display for Notification<'a> {
             ),
             SetProfile(name) => write!(f, "profile set to '{name}'"),
             SetSelfUpdate(mode) => write!(f, "auto-self-update mode set to '{mode}'"),
+            SetAutoInstallComponents(mode) => {
+                write!(f, "auto-install-components mode set to '{mode}'")
+            }
+            SetReleaseNotes(true) => write!(f, "release notes will be shown on update"),
+            SetReleaseNotes(false) => write!(f, "release notes will not be shown on update"),
+            SetSelfUpdateVersion(Some(req)) => {
+                write!(f, "self-updates will only move to rustup versions matching '{req}'")
+            }
+            SetSelfUpdateVersion(None) => write!(f, "self-updates will move to any rustup version"),
             LookingForToolchain(name) => write!(f, "looking for installed toolchain '{name}'"),
             ToolchainDirectory(path) => write!(f, "toolchain directory: '{}'", path.display()),
             UpdatingToolchain(name) => write!(f, "updating existing install for '{name}'"),
@@ -109,6 +133,13 @@ impl<'a> Display for Notification<'a> {
             UsingExistingToolchain(name) => write!(f, "using existing install for '{name}'"),
             UninstallingToolchain(name) => write!(f, "uninstalling toolchain '{name}'"),
             UninstalledToolchain(name) => write!(f, "toolchain '{name}' uninstalled"),
+            HeldToolchain(name) => write!(f, "toolchain '{name}' held"),
+            UnheldToolchain(name) => write!(f, "toolchain '{name}' no longer held"),
+            SkippingHeldToolchain(name) => write!(f, "skipping held toolchain '{name}'"),
+            HeldToolchainNotModified(name) => write!(
+                f,
+                "toolchain '{name}' is held, so the components and targets it is missing are not installed"
+            ),
             UpdateHashMatches => write!(f, "toolchain is already up to date"),
             UpgradingMetadata(from_ver, to_ver) => write!(
                 f,
@@ -153,6 +153,7 @@ impl home::env::Env for Process {
 
 #[derive(Clone, Debug)]
 pub struct OsProcess {
+    pub(self) stdin_is_a_tty: bool,
     pub(self) stderr_is_a_tty: bool,
     pub(self) stdout_is_a_tty: bool,
 }
@@ -160,6 +161,7 @@ pub struct OsProcess {
 impl OsProcess {
     pub fn new() -> Self {
         OsProcess {
+            stdin_is_a_tty: io::stdin().is_terminal(),
             stderr_is_a_tty: io::stderr().is_terminal(),
             stdout_is_a_tty: io::stdout().is_terminal(),
         }
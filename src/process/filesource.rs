@@ -6,6 +6,9 @@ use crate::process::Process;
 /// Stand-in for std::io::Stdin
 pub trait Stdin {
     fn lock(&self) -> Box<dyn StdinLock + '_>;
+
+    /// Query whether a TTY is present, so that the user can be prompted.
+    fn is_a_tty(&self, process: &Process) -> bool;
 }
 
 /// Stand-in for std::io::StdinLock
@@ -19,6 +22,14 @@ impl Stdin for io::Stdin {
     fn lock(&self) -> Box<dyn StdinLock + '_> {
         Box::new(io::Stdin::lock(self))
     }
+
+    fn is_a_tty(&self, process: &Process) -> bool {
+        match process {
+            crate::process::Process::OsProcess(p) => p.stdin_is_a_tty,
+            #[cfg(feature = "test")]
+            crate::process::Process::TestProcess(_) => unreachable!(),
+        }
+    }
 }
 
 // -------------- stdout -------------------------------
@@ -128,6 +139,10 @@ mod test_support {
                 inner: self.0.lock().unwrap_or_else(|e| e.into_inner()),
             })
         }
+
+        fn is_a_tty(&self, _: &Process) -> bool {
+            false
+        }
     }
 
     // ----------------------- test support for writers ------------------
@@ -1,14 +1,17 @@
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
+use crate::cli::proxy_mode::AutoInstallMode;
 use crate::cli::self_update::SelfUpdateMode;
-use crate::dist::Profile;
+use crate::dist::{CustomProfile, Profile, ResolvedProfile};
 use crate::errors::*;
 use crate::notifications::*;
 use crate::utils::utils;
@@ -87,12 +90,30 @@ pub struct Settings {
     pub default_toolchain: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub profile: Option<Profile>,
+    /// The name of the profile in `profiles` selected instead of `profile`,
+    /// which then holds its base. Versions of rustup that predate custom
+    /// profiles ignore this key and use the base.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, CustomProfile>,
     #[serde(default)]
     pub overrides: BTreeMap<String, String>,
+    /// Toolchains which are not to be updated
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub held_toolchains: BTreeSet<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pgp_keys: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auto_self_update: Option<SelfUpdateMode>,
+    /// The versions of rustup that self-updates may move to, such as `~1.27`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub self_update_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_install_components: Option<AutoInstallMode>,
+    /// Whether updates show the release notes of the new versions
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub release_notes: bool,
 }
 
 impl Settings {
@@ -126,6 +147,40 @@ impl Settings {
         self.overrides.insert(key, toolchain);
     }
 
+    /// Resolves the name of a built-in profile, or of one defined in the
+    /// settings. The built-in profiles take precedence.
+    pub(crate) fn resolve_profile(&self, name: &str) -> Result<ResolvedProfile> {
+        if let Ok(profile) = <Profile as FromStr>::from_str(name) {
+            return Ok(profile.into());
+        }
+        match self.profiles.get(name) {
+            Some(profile) => Ok(ResolvedProfile::custom(name, profile)),
+            None => Err(anyhow!(
+                "unknown profile name: '{}'; valid profile names are: {}",
+                name,
+                Profile::value_variants()
+                    .iter()
+                    .map(|p| p.as_str())
+                    .chain(self.profiles.keys().map(|n| n.as_str()))
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// The profile to install new toolchains with, if any was set.
+    pub(crate) fn profile(&self) -> Result<Option<ResolvedProfile>> {
+        match (&self.custom_profile, self.profile) {
+            (Some(name), _) => self.resolve_profile(name).map(Some),
+            (None, profile) => Ok(profile.map(ResolvedProfile::from)),
+        }
+    }
+
+    pub(crate) fn set_profile(&mut self, profile: &ResolvedProfile) {
+        self.profile = Some(profile.base);
+        self.custom_profile =
+            (profile.name() != profile.base.as_str()).then(|| profile.name().to_owned());
+    }
+
     pub(crate) fn dir_override(
         &self,
         dir: &Path,
@@ -228,6 +283,49 @@ mod tests {
         assert_eq!(settings.profile, Some(Profile::Default));
     }
 
+    #[test]
+    fn resolve_custom_profile() {
+        let mut settings = Settings::parse(
+            r#"version = "12"
+profile = "minimal"
+custom_profile = "team"
+
+[profiles.team]
+components = ["clippy", "rustfmt"]
+
+[profiles.docs]
+base = "default"
+"#,
+        )
+        .unwrap();
+
+        let team = settings.resolve_profile("team").unwrap();
+        assert_eq!(team.name(), "team");
+        assert_eq!(team.base, Profile::Minimal);
+        assert_eq!(team.components, ["clippy", "rustfmt"]);
+        assert_eq!(
+            settings.resolve_profile("docs").unwrap().base,
+            Profile::Default
+        );
+        assert_eq!(
+            settings.resolve_profile("m").unwrap(),
+            Profile::Minimal.into()
+        );
+        assert_eq!(
+            settings.resolve_profile("tema").unwrap_err().to_string(),
+            "unknown profile name: 'tema'; valid profile names are: minimal, default, complete, docs, team"
+        );
+        assert_eq!(settings.profile().unwrap(), Some(team));
+
+        let docs = settings.resolve_profile("docs").unwrap();
+        settings.set_profile(&docs);
+        assert_eq!(settings.profile, Some(Profile::Default));
+        assert_eq!(settings.custom_profile.as_deref(), Some("docs"));
+        settings.set_profile(&Profile::Complete.into());
+        assert_eq!(settings.profile, Some(Profile::Complete));
+        assert_eq!(settings.custom_profile, None);
+    }
+
     const BASIC: &str = r#"version = "12"
 default_toolchain = "stable-aarch64-apple-darwin"
 profile = "default"
@@ -17,6 +17,7 @@ use std::{
 };
 
 use enum_map::{enum_map, Enum, EnumMap};
+use ring::signature::{Ed25519KeyPair, KeyPair};
 use tempfile::TempDir;
 use url::Url;
 
@@ -341,9 +342,10 @@ impl SelfUpdateTestContext {
         let mut dest_file = fs::OpenOptions::new()
             .append(true)
             .create(true)
-            .open(dist_exe)
+            .open(&dist_exe)
             .unwrap();
         writeln!(dest_file).unwrap();
+        sign_rustup_init(&dist_exe);
 
         Self {
             config: cx.config,
@@ -410,9 +412,10 @@ impl CliTestContext {
         let mut dest_file = fs::OpenOptions::new()
             .append(true)
             .create(true)
-            .open(dist_exe)
+            .open(&dist_exe)
             .unwrap();
         writeln!(dest_file).unwrap();
+        sign_rustup_init(&dist_exe);
 
         self.config.rustup_update_root = Some(root_url);
         UpdateServerGuard {
@@ -498,6 +501,25 @@ fn create_local_update_server(self_dist: &Path, exedir: &Path, version: &str) ->
     root_url
 }
 
+/// The key the mock update server signs `rustup-init` with, in place of the
+/// release signing key.
+fn signing_key() -> Ed25519KeyPair {
+    Ed25519KeyPair::from_seed_unchecked(&[42; 32]).unwrap()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Writes the signature of the `rustup-init` at `path` next to it, as the
+/// update server does.
+pub fn sign_rustup_init(path: &Path) {
+    let signature = signing_key().sign(&fs::read(path).unwrap());
+    let mut sig_path = path.as_os_str().to_owned();
+    sig_path.push(".sig");
+    fs::write(sig_path, to_hex(signature.as_ref())).unwrap();
+}
+
 pub fn output_release_file(dist_dir: &Path, schema: &str, version: &str) {
     let contents = format!(
         r#"
@@ -579,6 +601,20 @@ impl Config {
             "/bogus-config-file.toml",
         );
 
+        // Self-updates from the mock update server are signed with a test key
+        cmd.env(
+            "RUSTUP_OVERRIDE_SIGNING_KEY",
+            to_hex(signing_key().public_key().as_ref()),
+        );
+
+        // Toolchains updated concurrently interleave their output, which most
+        // tests compare exactly
+        cmd.env("RUSTUP_CONCURRENT_UPDATES", "1");
+
+        // Likewise for the multi-arch C libraries and binfmt_misc interpreters
+        // that decide whether toolchains for other hosts can run
+        cmd.env("RUSTUP_OVERRIDE_HOST_ROOT", "/bogus-host-root");
+
         if let Some(root) = self.rustup_update_root.as_ref() {
             cmd.env("RUSTUP_UPDATE_ROOT", root);
         }
@@ -937,7 +973,7 @@ where
             no_self_update = true;
         } else if arg.as_ref() == "self" {
             self_cmd = true;
-        } else if arg.as_ref() == "run" {
+        } else if arg.as_ref() == "run" || arg.as_ref() == "shell" {
             run = true;
         } else if arg.as_ref() == "--version" {
             version = true;
@@ -1265,7 +1301,7 @@ fn build_mock_channel(
     let std = build_mock_std_installer(&host_triple);
     let rustc = build_mock_rustc_installer(&host_triple, version, version_hash);
     let cargo = build_mock_cargo_installer(version, version_hash);
-    let rust_docs = build_mock_rust_doc_installer();
+    let rust_docs = build_mock_rust_doc_installer(version, date);
     let rust = build_combined_installer(&[&std, &rustc, &cargo, &rust_docs]);
     let cross_std1 = build_mock_cross_std_installer(CROSS_ARCH1, date);
     let cross_std2 = build_mock_cross_std_installer(CROSS_ARCH2, date);
@@ -1302,7 +1338,7 @@ fn build_mock_channel(
         let std = build_mock_std_installer(MULTI_ARCH1);
         let rustc = build_mock_rustc_installer(MULTI_ARCH1, version, version_hash);
         let cargo = build_mock_cargo_installer(version, version_hash);
-        let rust_docs = build_mock_rust_doc_installer();
+        let rust_docs = build_mock_rust_doc_installer(version, date);
         let rust = build_combined_installer(&[&std, &rustc, &cargo, &rust_docs]);
 
         let triple = MULTI_ARCH1.to_string();
@@ -1531,11 +1567,15 @@ fn build_mock_rls_installer(
     }
 }
 
-fn build_mock_rust_doc_installer() -> MockInstallerBuilder {
+fn build_mock_rust_doc_installer(version: &str, date: &str) -> MockInstallerBuilder {
     let mut files: Vec<MockFile> = topical_doc_data::unique_paths()
         .map(|x| MockFile::new(x, b""))
         .collect();
     files.insert(0, MockFile::new("share/doc/rust/html/index.html", b""));
+    files.push(MockFile::new(
+        "share/doc/rust/html/releases.md",
+        mock_release_notes(version, date).as_bytes(),
+    ));
     MockInstallerBuilder {
         components: vec![MockComponentBuilder {
             name: "rust-docs".to_string(),
@@ -1544,6 +1584,24 @@ fn build_mock_rust_doc_installer() -> MockInstallerBuilder {
     }
 }
 
+/// Release notes with a section for `version` and each earlier minor version.
+fn mock_release_notes(version: &str, date: &str) -> String {
+    let minor = version
+        .split('.')
+        .nth(1)
+        .and_then(|minor| minor.parse::<u32>().ok())
+        .unwrap_or_default();
+    let mut notes = String::from("% Rust Release Notes\n\n");
+    for minor in (0..=minor).rev() {
+        let heading = format!("Version 1.{minor}.0 ({date})");
+        notes += &format!(
+            "{heading}\n{}\n\n- Mock release notes for 1.{minor}.0\n\n",
+            "=".repeat(heading.len())
+        );
+    }
+    notes
+}
+
 fn build_mock_rust_analysis_installer(trip: &str) -> MockInstallerBuilder {
     MockInstallerBuilder {
         components: vec![MockComponentBuilder {
@@ -13,7 +13,7 @@ use url::Url;
 use crate::dist::{
     manifest::{
         Component, CompressionKind, HashedBinary, Manifest, ManifestVersion, Package,
-        PackageTargets, Renamed, TargetedPackage,
+        PackageTargets, Patch, PatchFormat, Renamed, TargetedPackage,
     },
     Profile, TargetTriple,
 };
@@ -133,21 +133,108 @@ pub enum MockManifestVersion {
 impl MockDistServer {
     #[tracing::instrument(level = "trace", skip_all)]
     pub fn write(&self, vs: &[MockManifestVersion], enable_xz: bool, enable_zst: bool) {
+        self.write_(vs, enable_xz, enable_zst, false);
+    }
+
+    /// Like `write`, but also generates zstd patches from the archives of
+    /// each channel to those of the next release of the same channel, and
+    /// lists them in its v2 manifest.
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub fn write_with_patches(
+        &self,
+        vs: &[MockManifestVersion],
+        enable_xz: bool,
+        enable_zst: bool,
+    ) {
+        self.write_(vs, enable_xz, enable_zst, true);
+    }
+
+    fn write_(&self, vs: &[MockManifestVersion], enable_xz: bool, enable_zst: bool, patches: bool) {
         fs::create_dir_all(&self.path).unwrap();
 
+        let mut previous: HashMap<&str, (&MockChannel, HashMap<MockComponent, MockHashes>)> =
+            HashMap::new();
         for channel in self.channels.iter() {
             let mut hashes = HashMap::new();
             for package in &channel.packages {
                 let new_hashes = self.build_package(channel, package, enable_xz, enable_zst);
                 hashes.extend(new_hashes);
             }
+            let patches = match previous.get(channel.name.as_str()) {
+                Some((prev_channel, prev_hashes)) if patches => {
+                    self.build_patches(prev_channel, prev_hashes, channel, &hashes)
+                }
+                _ => HashMap::new(),
+            };
             for v in vs {
                 match *v {
                     MockManifestVersion::V1 => self.write_manifest_v1(channel),
-                    MockManifestVersion::V2 => self.write_manifest_v2(channel, &hashes),
+                    MockManifestVersion::V2 => self.write_manifest_v2(channel, &hashes, &patches),
                 }
             }
+            previous.insert(&channel.name, (channel, hashes));
+        }
+    }
+
+    /// Creates patches from the archives of `prev_channel` to those of
+    /// `channel`, returning them by component.
+    fn build_patches(
+        &self,
+        prev_channel: &MockChannel,
+        prev_hashes: &HashMap<MockComponent, MockHashes>,
+        channel: &MockChannel,
+        hashes: &HashMap<MockComponent, MockHashes>,
+    ) -> HashMap<MockComponent, Vec<Patch>> {
+        let mut patches = HashMap::new();
+        for (component, hashes) in hashes {
+            let Some(prev_hashes) = prev_hashes.get(component) else {
+                continue;
+            };
+            let installer_name = if component.target != "*" {
+                format!("{}-{}-{}", component.name, channel.name, component.target)
+            } else {
+                format!("{}-{}", component.name, channel.name)
+            };
+
+            let formats = [
+                (".tar.gz", Some(&prev_hashes.gz), Some(&hashes.gz)),
+                (".tar.xz", prev_hashes.xz.as_ref(), hashes.xz.as_ref()),
+                (".tar.zst", prev_hashes.zst.as_ref(), hashes.zst.as_ref()),
+            ];
+            for (format, from, to) in formats {
+                let (Some(from), Some(to)) = (from, to) else {
+                    continue;
+                };
+                let archive = format!("{installer_name}{format}");
+                let dist_dir = self.path.join("dist");
+                let old = fs::read(dist_dir.join(&prev_channel.date).join(&archive)).unwrap();
+                let new = fs::read(dist_dir.join(&channel.date).join(&archive)).unwrap();
+
+                let patch_path = dist_dir
+                    .join(&channel.date)
+                    .join(format!("{archive}.from-{}.patch.zst", prev_channel.date));
+                let mut encoder = zstd::stream::write::Encoder::with_ref_prefix(
+                    File::create(&patch_path).unwrap(),
+                    0,
+                    &old,
+                )
+                .unwrap();
+                encoder.write_all(&new).unwrap();
+                encoder.finish().unwrap();
+
+                patches
+                    .entry(component.clone())
+                    .or_insert_with(Vec::new)
+                    .push(Patch {
+                        from: from.clone(),
+                        to: to.clone(),
+                        format: PatchFormat::Zstd,
+                        url: format!("file://{}", patch_path.to_string_lossy()),
+                        hash: calc_hash(&patch_path),
+                    });
+            }
         }
+        patches
     }
 
     #[tracing::instrument(level = "trace", skip_all)]
@@ -312,6 +399,7 @@ impl MockDistServer {
         &self,
         channel: &MockChannel,
         hashes: &HashMap<MockComponent, MockHashes>,
+        patches: &HashMap<MockComponent, Vec<Patch>>,
     ) {
         let mut manifest = Manifest {
             manifest_version: ManifestVersion::V2,
@@ -331,6 +419,7 @@ impl MockDistServer {
                 let mut tpkg = TargetedPackage {
                     bins: Vec::new(),
                     components: Vec::new(),
+                    patches: Vec::new(),
                 };
 
                 let package_file_name = if target.target != "*" {
@@ -358,6 +447,7 @@ impl MockDistServer {
                         url: url.clone(),
                         hash: hash.gz,
                         compression: CompressionKind::GZip,
+                        size: archive_size(&path),
                     });
 
                     if let Some(xz_hash) = hash.xz {
@@ -365,6 +455,7 @@ impl MockDistServer {
                             url: url.replace(".tar.gz", ".tar.xz"),
                             hash: xz_hash,
                             compression: CompressionKind::XZ,
+                            size: archive_size(&path.with_extension("xz")),
                         });
                     }
 
@@ -373,8 +464,11 @@ impl MockDistServer {
                             url: url.replace(".tar.gz", ".tar.zst"),
                             hash: zst_hash,
                             compression: CompressionKind::ZStd,
+                            size: archive_size(&path.with_extension("zst")),
                         });
                     }
+
+                    tpkg.patches = patches.get(&component).cloned().unwrap_or_default();
                 }
 
                 // [pkg.*.target.*.components.*] and [pkg.*.target.*.extensions.*]
@@ -493,6 +587,10 @@ pub fn calc_hash(src: &Path) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+fn archive_size(src: &Path) -> Option<u64> {
+    fs::metadata(src).ok().map(|m| m.len())
+}
+
 pub fn create_hash(src: &Path, dst: &Path) -> String {
     let hex = calc_hash(src);
     let src_file = src.file_name().unwrap();
@@ -17,8 +17,10 @@ use wait_timeout::ChildExt;
 
 use crate::{
     config::{ActiveReason, Cfg, InstalledPath},
-    dist::PartialToolchainDesc,
-    env_var, install,
+    dist::{prefix::InstallPrefix, PartialToolchainDesc},
+    env_var,
+    history::{Action, Change, Snapshot},
+    install,
     notifications::Notification,
     utils::{raw::open_dir_following_links, utils},
     RustupError,
@@ -54,10 +56,17 @@ impl<'a> Toolchain<'a> {
                 if install_if_missing =>
             {
                 Ok(
-                    DistributableToolchain::install(cfg, &desc, &[], &[], cfg.get_profile()?, true)
-                        .await?
-                        .1
-                        .toolchain,
+                    DistributableToolchain::install(
+                        cfg,
+                        &desc,
+                        &[],
+                        &[],
+                        &cfg.get_profile()?,
+                        true,
+                    )
+                    .await?
+                    .1
+                    .toolchain,
                 )
             }
             Err(e) => Err(e.into()),
@@ -173,6 +182,38 @@ impl<'a> Toolchain<'a> {
         cmd.env("RUSTUP_HOME", &self.cfg.rustup_dir);
     }
 
+    /// The environment `set_env` gives the commands run from this toolchain,
+    /// less the recursion counter. With `bin`, the toolchain's own `bin`
+    /// directory comes first in `PATH`, so its tools are found without going
+    /// through the proxies.
+    pub(crate) fn env(&self, bin: bool) -> Vec<(String, OsString)> {
+        let mut cmd = Command::new("");
+        self.set_env(&mut cmd);
+
+        if bin {
+            let path = cmd
+                .get_envs()
+                .find(|(name, _)| *name == "PATH")
+                .and_then(|(_, value)| value.map(OsStr::to_owned))
+                .or_else(|| self.cfg.process.var_os("PATH"));
+            let bin_dir = self.path.join("bin");
+            let mut parts = vec![bin_dir.clone()];
+            if let Some(path) = &path {
+                parts.extend(env::split_paths(path).filter(|p| *p != bin_dir));
+            }
+            if let Ok(path) = env::join_paths(parts) {
+                cmd.env("PATH", path);
+            }
+        }
+
+        cmd.get_envs()
+            .filter(|(name, _)| *name != "RUST_RECURSION_COUNT")
+            .filter_map(|(name, value)| {
+                Some((name.to_string_lossy().into_owned(), value?.to_owned()))
+            })
+            .collect()
+    }
+
     /// Apply the appropriate LD path for a command being run from a toolchain.
     fn set_ldpath(&self, cmd: &mut Command) {
         #[cfg_attr(not(target_os = "macos"), allow(unused_mut))]
@@ -432,6 +473,12 @@ impl<'a> Toolchain<'a> {
             LocalToolchainName::Named(t) => t,
             LocalToolchainName::Path(_) => bail!("Cannot remove a path based toolchain"),
         };
+        let snapshot = match &name {
+            ToolchainName::Official(_) if path.exists() => {
+                Snapshot::take(&InstallPrefix::from(path.clone()))
+            }
+            _ => None,
+        };
         let fs_modified = match Self::exists(cfg, &(&name).into())? {
             true => {
                 (cfg.notify_handler)(Notification::UninstallingToolchain(&name));
@@ -472,6 +519,9 @@ impl<'a> Toolchain<'a> {
 
         if !path.is_symlink() && !path.exists() && fs_modified {
             (cfg.notify_handler)(Notification::UninstalledToolchain(&name));
+            cfg.history.record(
+                Change::new(Action::Uninstall, Some(name.to_string())).snapshot(snapshot),
+            )?;
         }
         Ok(())
     }
@@ -10,13 +10,16 @@ use crate::{
     component_for_bin,
     config::Cfg,
     dist::{
+        self,
         config::Config,
         manifest::{Component, ComponentStatus, Manifest},
-        manifestation::{Changes, Manifestation},
+        manifestation::{self, Changes, Manifestation, UpdatePlan},
         prefix::InstallPrefix,
-        DistOptions, PartialToolchainDesc, Profile, ToolchainDesc,
+        DistOptions, PartialToolchainDesc, ResolvedProfile, ToolchainDesc,
     },
+    history::{Action, Change, Entry, Snapshot},
     install::{InstallMethod, UpdateStatus},
+    utils::utils,
     RustupError,
 };
 
@@ -48,10 +51,133 @@ impl<'a> DistributableToolchain<'a> {
         &self.desc
     }
 
-    pub(crate) async fn add_component(&self, mut component: Component) -> anyhow::Result<()> {
+    pub(crate) async fn add_component(&self, component: Component) -> anyhow::Result<()> {
         // TODO: take multiple components?
         let manifestation = self.get_manifestation()?;
         let manifest = self.get_manifest()?;
+        let component = self.component_to_add(component, &manifestation, &manifest)?;
+
+        let changes = Changes {
+            explicit_add_components: vec![component],
+            remove_components: vec![],
+        };
+
+        let notify_handler =
+            &|n: crate::dist::Notification<'_>| (self.toolchain.cfg.notify_handler)(n.into());
+        let download_cfg = self.toolchain.cfg.download_cfg(&notify_handler);
+
+        manifestation
+            .update(
+                &manifest,
+                changes,
+                false,
+                &download_cfg,
+                &self.desc.manifest_name(),
+                false,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Computes the changes adding `components` would make, without making
+    /// them.
+    pub(crate) async fn plan_add_components(
+        &self,
+        components: Vec<Component>,
+    ) -> anyhow::Result<UpdatePlan> {
+        let manifestation = self.get_manifestation()?;
+        let manifest = self.get_manifest()?;
+        let explicit_add_components = components
+            .into_iter()
+            .map(|c| self.component_to_add(c, &manifestation, &manifest))
+            .collect::<anyhow::Result<_>>()?;
+
+        let changes = Changes {
+            explicit_add_components,
+            remove_components: vec![],
+        };
+
+        let notify_handler =
+            &|n: crate::dist::Notification<'_>| (self.toolchain.cfg.notify_handler)(n.into());
+        let download_cfg = self.toolchain.cfg.download_cfg(&notify_handler);
+
+        manifestation
+            .plan(
+                &manifest,
+                changes,
+                false,
+                &download_cfg,
+                &self.desc.manifest_name(),
+            )
+            .await
+    }
+
+    /// Brings the components of the toolchain in line with `profile`, in one
+    /// transaction: the components of the profile it lacks are installed and,
+    /// with `prune`, the ones it has beyond the profile are removed.
+    /// Components for other targets are left alone, as they are managed with
+    /// `rustup target`.
+    pub(crate) async fn set_profile(
+        &self,
+        profile: &ResolvedProfile,
+        prune: bool,
+    ) -> anyhow::Result<manifestation::UpdateStatus> {
+        let manifestation = self.get_manifestation()?;
+        let config = manifestation.read_config()?.unwrap_or_default();
+        let manifest = self.get_manifest()?;
+
+        let mut wanted = manifest.get_profile_components(profile.base, &self.desc.target)?;
+        for name in &profile.components {
+            let component = Component::new(name.clone(), Some(self.desc.target.clone()), false);
+            wanted.push(self.component_to_add(component, &manifestation, &manifest)?);
+        }
+
+        let explicit_add_components = wanted
+            .iter()
+            .filter(|c| !c.contained_within(&config.components))
+            .cloned()
+            .collect();
+        let remove_components = match prune {
+            true => config
+                .components
+                .iter()
+                .filter(|c| c.target.is_none() || c.target.as_ref() == Some(&self.desc.target))
+                .filter(|c| !c.contained_within(&wanted))
+                .cloned()
+                .collect(),
+            false => vec![],
+        };
+
+        let changes = Changes {
+            explicit_add_components,
+            remove_components,
+        };
+
+        let notify_handler =
+            &|n: crate::dist::Notification<'_>| (self.toolchain.cfg.notify_handler)(n.into());
+        let download_cfg = self.toolchain.cfg.download_cfg(&notify_handler);
+
+        manifestation
+            .update(
+                &manifest,
+                changes,
+                false,
+                &download_cfg,
+                &self.desc.manifest_name(),
+                false,
+            )
+            .await
+    }
+
+    /// Resolves a component to add to how `manifest` names it, failing if
+    /// it doesn't list it.
+    fn component_to_add(
+        &self,
+        mut component: Component,
+        manifestation: &Manifestation,
+        manifest: &Manifest,
+    ) -> anyhow::Result<Component> {
         // Rename the component if necessary.
         if let Some(c) = manifest.rename_component(&component) {
             component = c;
@@ -74,7 +200,7 @@ impl<'a> DistributableToolchain<'a> {
             } else {
                 let config = manifestation.read_config()?.unwrap_or_default();
                 let suggestion =
-                    self.get_component_suggestion(&component, &config, &manifest, false);
+                    self.get_component_suggestion(&component, &config, manifest, false);
                 // Check if the target is supported.
                 if !targ_pkg
                     .components
@@ -90,34 +216,14 @@ impl<'a> DistributableToolchain<'a> {
                 }
                 return Err(RustupError::UnknownComponent {
                     desc: self.desc.clone(),
-                    component: component.description(&manifest),
+                    component: component.description(manifest),
                     suggestion,
                 }
                 .into());
             }
         }
 
-        let changes = Changes {
-            explicit_add_components: vec![component],
-            remove_components: vec![],
-        };
-
-        let notify_handler =
-            &|n: crate::dist::Notification<'_>| (self.toolchain.cfg.notify_handler)(n.into());
-        let download_cfg = self.toolchain.cfg.download_cfg(&notify_handler);
-
-        manifestation
-            .update(
-                &manifest,
-                changes,
-                false,
-                &download_cfg,
-                &self.desc.manifest_name(),
-                false,
-            )
-            .await?;
-
-        Ok(())
+        Ok(component)
     }
 
     pub(crate) fn components(&self) -> anyhow::Result<Vec<ComponentStatus>> {
@@ -331,7 +437,7 @@ impl<'a> DistributableToolchain<'a> {
         toolchain: &ToolchainDesc,
         components: &[&str],
         targets: &[&str],
-        profile: Profile,
+        profile: &ResolvedProfile,
         force: bool,
     ) -> anyhow::Result<(UpdateStatus, DistributableToolchain<'a>)> {
         let hash_path = cfg.get_hash_file(toolchain, true)?;
@@ -360,7 +466,7 @@ impl<'a> DistributableToolchain<'a> {
         &mut self,
         components: &[&str],
         targets: &[&str],
-        profile: Profile,
+        profile: &ResolvedProfile,
     ) -> anyhow::Result<UpdateStatus> {
         self.update_extra(components, targets, profile, true, false)
             .await
@@ -372,24 +478,11 @@ impl<'a> DistributableToolchain<'a> {
         &mut self,
         components: &[&str],
         targets: &[&str],
-        profile: Profile,
+        profile: &ResolvedProfile,
         force: bool,
         allow_downgrade: bool,
     ) -> anyhow::Result<UpdateStatus> {
-        let old_date_version =
-            // Ignore a missing manifest: we can't report the old version
-            // correctly, and it probably indicates an incomplete install, so do
-            // not report an old rustc version either.
-            self.get_manifest()
-                .map(|m| {
-                    (
-                        m.date,
-                        // should rustc_version be a free function on a trait?
-                        // note that prev_version can be junk if the rustc component is missing ...
-                        self.toolchain.rustc_version(),
-                    )
-                })
-                .ok();
+        let old_date_version = self.old_date_version();
 
         let cfg = self.toolchain.cfg;
         let hash_path = cfg.get_hash_file(&self.desc, true)?;
@@ -412,6 +505,144 @@ impl<'a> DistributableToolchain<'a> {
         .await
     }
 
+    /// Computes the changes installing or updating `toolchain` would make,
+    /// without making them. Returns `None` if it is up to date.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn plan_update(
+        cfg: &'a Cfg<'a>,
+        toolchain: &ToolchainDesc,
+        components: &[&str],
+        targets: &[&str],
+        profile: &ResolvedProfile,
+        force: bool,
+        allow_downgrade: bool,
+    ) -> anyhow::Result<Option<UpdatePlan>> {
+        let old_date_version = match Self::new(cfg, toolchain.clone()) {
+            Ok(distributable) => Some(distributable.old_date_version()),
+            Err(RustupError::ToolchainNotInstalled(_)) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let hash_path = cfg.get_hash_file(toolchain, false)?;
+        let notify_handler = |n: crate::dist::Notification<'_>| (cfg.notify_handler)(n.into());
+
+        let opts = DistOptions {
+            cfg,
+            toolchain,
+            profile,
+            update_hash: Some(&hash_path),
+            dl_cfg: cfg.download_cfg(&notify_handler),
+            force,
+            allow_downgrade,
+            exists: old_date_version.is_some(),
+            old_date_version: old_date_version.flatten(),
+            components,
+            targets,
+        };
+        let prefix = InstallPrefix::from(cfg.toolchain_path(&toolchain.into()));
+        dist::plan_from_dist(&prefix, &opts).await
+    }
+
+    /// Reinstalls `toolchain` as it was before the change recorded in
+    /// `entry`: from the same manifest, and with the same components.
+    pub(crate) async fn rollback(
+        cfg: &'a Cfg<'a>,
+        toolchain: &ToolchainDesc,
+        entry: &Entry,
+    ) -> anyhow::Result<manifestation::UpdateStatus> {
+        let (manifest, config) = cfg.history.snapshot(entry)?;
+        cfg.ensure_toolchains_dir()?;
+        let prefix = InstallPrefix::from(cfg.toolchain_path(&toolchain.into()));
+        let snapshot = Snapshot::take(&prefix);
+        let manifestation = Manifestation::open(prefix, toolchain.target.clone())?;
+
+        let installed = manifestation
+            .read_config()?
+            .map(|c| c.components)
+            .unwrap_or_default();
+        let changes = Changes {
+            explicit_add_components: config
+                .components
+                .iter()
+                .filter(|c| !installed.contains(c))
+                .cloned()
+                .collect(),
+            remove_components: installed
+                .into_iter()
+                .filter(|c| !config.components.contains(c))
+                .collect(),
+        };
+
+        let notify_handler = |n: crate::dist::Notification<'_>| (cfg.notify_handler)(n.into());
+        let download_cfg = cfg.download_cfg(&notify_handler);
+        let status = manifestation
+            .update(
+                &manifest,
+                changes,
+                false,
+                &download_cfg,
+                &toolchain.manifest_name(),
+                false,
+            )
+            .await?;
+
+        // The channel's current manifest has to be checked again by the next
+        // update, even if it was the one installed before the rollback.
+        utils::ensure_file_removed("update hash", &cfg.get_hash_file(toolchain, false)?)?;
+
+        cfg.history.record(
+            Change::new(Action::Rollback, Some(toolchain.to_string()))
+                .snapshot(snapshot)
+                .restored(entry.id),
+        )?;
+        Ok(status)
+    }
+
+    /// The date of the installed manifest and the version of rustc, as
+    /// needed to update the toolchain.
+    fn old_date_version(&self) -> Option<(String, String)> {
+        // Ignore a missing manifest: we can't report the old version
+        // correctly, and it probably indicates an incomplete install, so do
+        // not report an old rustc version either.
+        self.get_manifest()
+            .map(|m| {
+                (
+                    m.date,
+                    // should rustc_version be a free function on a trait?
+                    // note that prev_version can be junk if the rustc component is missing ...
+                    self.toolchain.rustc_version(),
+                )
+            })
+            .ok()
+    }
+
+    /// The component that provides `binary`, if the binary is missing from the
+    /// toolchain because that component is available but not installed.
+    pub(crate) fn missing_component_for_bin(
+        &self,
+        binary: &str,
+    ) -> anyhow::Result<Option<Component>> {
+        let Some(component_name) = component_for_bin(binary) else {
+            return Ok(None);
+        };
+        if utils::is_file(self.toolchain.binary_file(binary)) {
+            return Ok(None);
+        }
+
+        let manifestation = self.get_manifestation()?;
+        let config = manifestation.read_config()?.unwrap_or_default();
+        let manifest = self.get_manifest()?;
+        let component = manifest
+            .query_components(&self.desc, &config)?
+            .into_iter()
+            .find(|cs| {
+                cs.component.short_name(&manifest) == component_name
+                    && cs.available
+                    && !cs.installed
+            })
+            .map(|cs| cs.component);
+        Ok(component)
+    }
+
     pub fn recursion_error(&self, binary_lossy: String) -> Result<Infallible, anyhow::Error> {
         let prefix = InstallPrefix::from(self.toolchain.path());
         let manifestation = Manifestation::open(prefix, self.desc.target.clone())?;
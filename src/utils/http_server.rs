@@ -0,0 +1,212 @@
+//! The little HTTP/1.1 that `rustup serve-cache` and `rustup doc --serve`
+//! speak: `GET` and `HEAD` requests, answered on a connection that is then
+//! closed.
+
+use std::io;
+
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The largest request head we are willing to read.
+const MAX_REQUEST_HEAD_LEN: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Method {
+    Get,
+    Head,
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Request {
+    pub(crate) method: Method,
+    pub(crate) path: String,
+    /// The start of a `Range: bytes=<start>-` header, as sent by clients
+    /// resuming a download.
+    pub(crate) range_start: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    Ok,
+    PartialContent,
+    Found,
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    RangeNotSatisfiable,
+    BadGateway,
+}
+
+impl Status {
+    fn line(self) -> &'static str {
+        match self {
+            Self::Ok => "200 OK",
+            Self::PartialContent => "206 Partial Content",
+            Self::Found => "302 Found",
+            Self::BadRequest => "400 Bad Request",
+            Self::NotFound => "404 Not Found",
+            Self::MethodNotAllowed => "405 Method Not Allowed",
+            Self::RangeNotSatisfiable => "416 Range Not Satisfiable",
+            Self::BadGateway => "502 Bad Gateway",
+        }
+    }
+}
+
+/// Reads a request head from `stream`, or the status to reject it with.
+pub(crate) async fn read_request(stream: &mut TcpStream) -> io::Result<Result<Request, Status>> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_REQUEST_HEAD_LEN {
+            return Ok(Err(Status::BadRequest));
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(parse_request(&String::from_utf8_lossy(&head)))
+}
+
+fn parse_request(head: &str) -> Result<Request, Status> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = match request_line.next() {
+        Some("GET") => Method::Get,
+        Some("HEAD") => Method::Head,
+        _ => return Err(Status::MethodNotAllowed),
+    };
+    let target = request_line.next().ok_or(Status::BadRequest)?;
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    // Only serve plain paths below the root, checked once decoded since URLs
+    // resolve `%2e%2e` like `..`.
+    let decoded = percent_decode(path).ok_or(Status::BadRequest)?;
+    if !path.starts_with('/')
+        || decoded.contains('\\')
+        || decoded.split('/').any(|segment| segment == "..")
+    {
+        return Err(Status::BadRequest);
+    }
+
+    let mut range_start = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        if name.trim().eq_ignore_ascii_case("range") {
+            let start = value
+                .trim()
+                .strip_prefix("bytes=")
+                .and_then(|range| range.strip_suffix('-'))
+                .and_then(|start| start.parse().ok())
+                .ok_or(Status::RangeNotSatisfiable)?;
+            range_start = Some(start);
+        }
+    }
+
+    Ok(Request {
+        method,
+        path: path.to_owned(),
+        range_start,
+    })
+}
+
+/// Decodes `%XX` escapes, or returns `None` if they're malformed or don't
+/// decode to UTF-8.
+pub(crate) fn percent_decode(path: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Writes a response head with `headers` besides `Content-Length`.
+pub(crate) async fn write_head(
+    stream: &mut TcpStream,
+    status: Status,
+    content_len: u64,
+    headers: &[(&str, &str)],
+) -> Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {content_len}\r\nConnection: close\r\n",
+        status.line()
+    );
+    for (name, value) in headers {
+        head += &format!("{name}: {value}\r\n");
+    }
+    head += "\r\n";
+    stream
+        .write_all(head.as_bytes())
+        .await
+        .map_err(|e| anyhow!(e).context("failed to write response"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(
+            parse_request("GET /dist/channel-rust-stable.toml?x=1 HTTP/1.1\r\nHost: a\r\n\r\n"),
+            Ok(Request {
+                method: Method::Get,
+                path: "/dist/channel-rust-stable.toml".to_owned(),
+                range_start: None,
+            })
+        );
+        assert_eq!(
+            parse_request("HEAD /dist/a.tar.xz HTTP/1.1\r\nrange: bytes=42-\r\n\r\n"),
+            Ok(Request {
+                method: Method::Head,
+                path: "/dist/a.tar.xz".to_owned(),
+                range_start: Some(42),
+            })
+        );
+        assert_eq!(
+            parse_request("POST /dist HTTP/1.1\r\n\r\n"),
+            Err(Status::MethodNotAllowed)
+        );
+        assert_eq!(
+            parse_request("GET /dist/a.tar.xz HTTP/1.1\r\nRange: bytes=0-10\r\n\r\n"),
+            Err(Status::RangeNotSatisfiable)
+        );
+        assert_eq!(
+            parse_request("GET dist/a HTTP/1.1\r\n\r\n"),
+            Err(Status::BadRequest)
+        );
+        for path in [
+            "/dist/../a",
+            "/dist/%2e%2e/a",
+            "/dist/.%2E/a",
+            "/dist/%5Ca",
+            "/dist/%zz",
+        ] {
+            assert_eq!(
+                parse_request(&format!("GET {path} HTTP/1.1\r\n\r\n")),
+                Err(Status::BadRequest),
+                "{path}"
+            );
+        }
+    }
+}
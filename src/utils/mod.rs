@@ -1,4 +1,5 @@
 //!  Utility functions for Rustup
+pub(crate) mod http_server;
 pub(crate) mod notifications;
 pub mod raw;
 pub(crate) mod units;
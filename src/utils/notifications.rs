@@ -34,6 +34,8 @@ pub enum Notification<'a> {
     Error(String),
     UsingCurl,
     UsingReqwest,
+    /// The download rate is capped to this many bytes per second.
+    UsingDownloadRateLimit(u64),
     /// Renaming encountered a file in use error and is retrying.
     /// The InUse aspect is a heuristic - the OS specifies
     /// Permission denied, but as we work in users home dirs and
@@ -59,7 +61,8 @@ impl<'a> Notification<'a> {
             | DownloadFinished
             | ResumingPartialDownload
             | UsingCurl
-            | UsingReqwest => NotificationLevel::Debug,
+            | UsingReqwest
+            | UsingDownloadRateLimit(_) => NotificationLevel::Debug,
             RenameInUse(_, _) => NotificationLevel::Info,
             NoCanonicalPath(_) => NotificationLevel::Warn,
             Error(_) => NotificationLevel::Error,
@@ -101,6 +104,11 @@ impl<'a> Display for Notification<'a> {
             ResumingPartialDownload => write!(f, "resuming partial download"),
             UsingCurl => write!(f, "downloading with curl"),
             UsingReqwest => write!(f, "downloading with reqwest"),
+            UsingDownloadRateLimit(rate) => write!(
+                f,
+                "limiting download rate to {}",
+                units::Size::new(*rate as usize, units::Unit::B, units::UnitMode::Rate)
+            ),
         }
     }
 }
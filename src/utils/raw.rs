@@ -266,6 +266,86 @@ pub(crate) fn copy_dir(src: &Path, dest: &Path) -> io::Result<()> {
     Ok(())
 }
 
+/// Returns the number of bytes available to the current user on the volume
+/// holding `path`, which must exist.
+#[cfg(not(windows))]
+pub(crate) fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is only read once
+    // `statvfs` has filled it in.
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::useless_conversion)]
+    Ok(u64::from(stat.f_bavail).saturating_mul(u64::from(stat.f_frsize)))
+}
+
+/// Returns the number of bytes available to the current user on the volume
+/// holding `path`, which must exist.
+#[cfg(windows)]
+pub(crate) fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ptr;
+
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path = windows::to_u16s(path)?;
+    let mut available = 0;
+    // SAFETY: `path` is NUL-terminated and the totals we don't need may be null.
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(available)
+}
+
+/// Returns an identifier of the volume holding `path`, which must exist,
+/// equal for all the paths on the same volume.
+#[cfg(not(windows))]
+pub(crate) fn volume_id(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(fs::metadata(path)?.dev())
+}
+
+/// Returns an identifier of the volume holding `path`, which must exist,
+/// equal for all the paths on the same volume.
+#[cfg(windows)]
+pub(crate) fn volume_id(path: &Path) -> io::Result<u64> {
+    use std::mem::MaybeUninit;
+    use std::os::windows::io::AsRawHandle;
+
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+
+    let file = open_dir_following_links(path)?;
+    let mut info = MaybeUninit::<BY_HANDLE_FILE_INFORMATION>::uninit();
+    // SAFETY: `file` stays open during the call, and `info` is only read once
+    // `GetFileInformationByHandle` has filled it in.
+    let info = unsafe {
+        if GetFileInformationByHandle(file.as_raw_handle(), info.as_mut_ptr()) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        info.assume_init()
+    };
+    Ok(u64::from(info.dwVolumeSerialNumber))
+}
+
 #[cfg(not(windows))]
 fn has_cmd(cmd: &str, process: &Process) -> bool {
     let cmd = format!("{}{}", cmd, env::consts::EXE_SUFFIX);
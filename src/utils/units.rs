@@ -75,8 +75,35 @@ impl Display for Size {
     }
 }
 
+/// Parses a byte count such as `512`, `64K`, `2M` or `1G`, where the
+/// suffixes are binary multiples.
+pub(crate) fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last()? {
+        (i, 'k' | 'K') => (&s[..i], 1024),
+        (i, 'm' | 'M') => (&s[..i], 1024 * 1024),
+        (i, 'g' | 'G') => (&s[..i], 1024 * 1024 * 1024),
+        _ => (s, 1),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn parse_size_test() {
+        use crate::utils::units::parse_size;
+
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("64K"), Some(64 * 1024));
+        assert_eq!(parse_size("2m"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("2.5M"), None);
+        assert_eq!(parse_size("-1"), None);
+    }
+
     #[test]
     fn unit_formatter_test() {
         use crate::utils::units::{Size, Unit, UnitMode};
@@ -9,12 +9,14 @@ use anyhow::{anyhow, bail, Context, Result};
 use retry::delay::{jitter, Fibonacci};
 use retry::{retry, OperationResult};
 use sha2::Sha256;
+use tracing::debug;
 use url::Url;
 
 use crate::errors::*;
 use crate::process::Process;
 use crate::utils::notifications::Notification;
 use crate::utils::raw;
+use crate::utils::units;
 
 #[cfg(not(windows))]
 pub(crate) use crate::utils::utils::raw::find_cmd;
@@ -157,20 +159,52 @@ pub(crate) async fn download_file_with_resume(
     notify_handler: &dyn Fn(Notification<'_>),
     process: &Process,
 ) -> Result<()> {
-    use download::DownloadError as DEK;
-    match download_file_(
+    let res = download_file_(
         url,
         path,
         hasher,
         resume_from_partial,
+        None,
+        notify_handler,
+        process,
+    )
+    .await;
+    with_download_context(res, url, path).map(|_| ())
+}
+
+/// Downloads `url` to `path`, unless it hasn't changed since the `cached`
+/// validators were received for it.
+pub(crate) async fn download_file_if_modified(
+    url: &Url,
+    path: &Path,
+    cached: &download::Validators,
+    notify_handler: &dyn Fn(Notification<'_>),
+    process: &Process,
+) -> Result<download::Outcome> {
+    let res = download_file_(
+        url,
+        path,
+        None,
+        false,
+        Some(cached),
         notify_handler,
         process,
     )
-    .await
-    {
-        Ok(_) => Ok(()),
+    .await;
+    with_download_context(res, url, path)
+}
+
+fn with_download_context<T>(res: Result<T>, url: &Url, path: &Path) -> Result<T> {
+    use download::DownloadError as DEK;
+    match res {
+        Ok(value) => Ok(value),
         Err(e) => {
-            if e.downcast_ref::<std::io::Error>().is_some() {
+            if e.downcast_ref::<std::io::Error>().is_some()
+                || matches!(
+                    e.downcast_ref::<RustupError>(),
+                    Some(RustupError::InsufficientDiskSpace { .. })
+                )
+            {
                 return Err(e);
             }
             let is_client_error = match e.downcast_ref::<DEK>() {
@@ -202,11 +236,12 @@ async fn download_file_(
     path: &Path,
     hasher: Option<&mut Sha256>,
     resume_from_partial: bool,
+    cached: Option<&download::Validators>,
     notify_handler: &dyn Fn(Notification<'_>),
     process: &Process,
-) -> Result<()> {
-    use download::download_to_path_with_backend;
-    use download::{Backend, Event, TlsBackend};
+) -> Result<download::Outcome> {
+    use download::{download_to_path_if_modified, download_to_path_with_backend};
+    use download::{Event, Outcome, Validators};
     use sha2::Digest;
     use std::cell::RefCell;
 
@@ -225,6 +260,10 @@ async fn download_file_(
 
         match msg {
             Event::DownloadContentLengthReceived(len) => {
+                // The length includes any partial download being resumed,
+                // which is already on disk.
+                let existing = fs::metadata(path).map_or(0, |m| m.len());
+                ensure_disk_space(path, len.saturating_sub(existing))?;
                 notify_handler(Notification::DownloadContentLengthReceived(len));
             }
             Event::DownloadDataReceived(data) => {
@@ -239,6 +278,47 @@ async fn download_file_(
     };
 
     // Download the file
+    let (backend, notification) = download_backend(process);
+    notify_handler(notification);
+    let limits = download_limits(process)?;
+    if let Some(rate) = limits.max_rate {
+        notify_handler(Notification::UsingDownloadRateLimit(rate));
+    }
+    let res = match cached {
+        Some(cached) => {
+            download_to_path_if_modified(backend, url, path, cached, &limits, Some(callback)).await
+        }
+        None => download_to_path_with_backend(
+            backend,
+            url,
+            path,
+            resume_from_partial,
+            &limits,
+            Some(callback),
+        )
+        .await
+        .map(|()| Outcome::Downloaded(Validators::default())),
+    };
+
+    notify_handler(Notification::DownloadFinished);
+
+    res
+}
+
+/// Returns the size of the file at `url` without downloading it, if the
+/// server reports one.
+pub(crate) async fn content_length(url: &Url, process: &Process) -> Result<Option<u64>> {
+    let (backend, _) = download_backend(process);
+    let limits = download_limits(process)?;
+    download::content_length(backend, url, &limits)
+        .await
+        .with_context(|| format!("failed to query the size of '{url}'"))
+}
+
+/// Selects the download backend from the environment, along with the
+/// notification announcing it.
+fn download_backend(process: &Process) -> (download::Backend, Notification<'static>) {
+    use download::{Backend, TlsBackend};
 
     // Keep the curl env var around for a bit
     let use_curl_backend = process
@@ -247,7 +327,7 @@ async fn download_file_(
     let use_rustls = process
         .var_os("RUSTUP_USE_RUSTLS")
         .map_or(true, |it| it != "0");
-    let (backend, notification) = if use_curl_backend {
+    if use_curl_backend {
         (Backend::Curl, Notification::UsingCurl)
     } else {
         let tls_backend = if use_rustls {
@@ -263,15 +343,72 @@ async fn download_file_(
             }
         };
         (Backend::Reqwest(tls_backend), Notification::UsingReqwest)
+    }
+}
+
+/// Reads the download bandwidth cap and timeouts from the environment,
+/// falling back to the defaults of the `download` crate.
+fn download_limits(process: &Process) -> Result<download::DownloadLimits> {
+    use download::{DownloadLimits, LowSpeedLimit};
+    use std::time::Duration;
+
+    let size_var = |name: &str| -> Result<Option<u64>> {
+        match process.var(name) {
+            Ok(value) => units::parse_size(&value)
+                .map(Some)
+                .ok_or_else(|| anyhow!("invalid size for {name}: '{value}'")),
+            Err(_) => Ok(None),
+        }
+    };
+    let secs_var = |name: &str| -> Result<Option<Duration>> {
+        match process.var(name) {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map(|secs| Some(Duration::from_secs(secs)))
+                .map_err(|_| anyhow!("invalid number of seconds for {name}: '{value}'")),
+            Err(_) => Ok(None),
+        }
     };
-    notify_handler(notification);
-    let res =
-        download_to_path_with_backend(backend, url, path, resume_from_partial, Some(callback))
-            .await;
 
-    notify_handler(Notification::DownloadFinished);
+    let default = DownloadLimits::default();
+    let low_speed_limit = size_var("RUSTUP_LOW_SPEED_LIMIT")?;
+    let low_speed_time = secs_var("RUSTUP_LOW_SPEED_TIME")?;
+    let low_speed = if low_speed_limit.is_none() && low_speed_time.is_none() {
+        default.low_speed
+    } else {
+        let fallback = default.low_speed.unwrap_or_default();
+        let bytes_per_sec = match low_speed_limit {
+            Some(limit) => u32::try_from(limit)
+                .map_err(|_| anyhow!("RUSTUP_LOW_SPEED_LIMIT is too large: '{limit}'"))?,
+            None => fallback.bytes_per_sec,
+        };
+        let time = low_speed_time.unwrap_or(fallback.time);
+        // Zero disables the check, as it does for curl.
+        (bytes_per_sec > 0 && !time.is_zero()).then_some(LowSpeedLimit {
+            bytes_per_sec,
+            time,
+        })
+    };
 
-    res
+    Ok(DownloadLimits {
+        max_rate: size_var("RUSTUP_MAX_DOWNLOAD_RATE")?.filter(|&rate| rate > 0),
+        connect_timeout: secs_var("RUSTUP_CONNECT_TIMEOUT")?.unwrap_or(default.connect_timeout),
+        low_speed,
+        timeout: secs_var("RUSTUP_DOWNLOAD_TIMEOUT")?,
+    })
+}
+
+/// Decodes a string of hex digit pairs, such as `7f454c46`, into bytes.
+/// Returns `None` if it is anything else.
+pub(crate) fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
 }
 
 pub(crate) fn parse_url(url: &str) -> Result<Url> {
@@ -463,6 +600,79 @@ fn set_permissions(path: &Path, perms: fs::Permissions) -> Result<()> {
     })
 }
 
+/// Returns the disk space taken up by `path`, including everything below it
+/// if it is a directory, or zero if it doesn't exist.
+pub(crate) fn disk_usage(path: &Path) -> Result<u64> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read '{}'", path.display()));
+        }
+    };
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut total = 0;
+    for entry in read_dir("directory", path)? {
+        total += disk_usage(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+/// Fails with [`RustupError::InsufficientDiskSpace`] if the volume that holds,
+/// or will hold, `path` has less than `required` bytes available.
+///
+/// Volumes whose free space can't be queried are assumed to be large enough.
+pub(crate) fn ensure_disk_space(path: &Path, required: u64) -> Result<()> {
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        return Ok(());
+    };
+    let available = match raw::available_space(existing) {
+        Ok(available) => available,
+        Err(e) => {
+            debug!(
+                "could not query free disk space of '{}': {e}",
+                existing.display()
+            );
+            return Ok(());
+        }
+    };
+    if available < required {
+        return Err(RustupError::InsufficientDiskSpace {
+            path: path.to_owned(),
+            required,
+            available,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Like [`ensure_disk_space`] for several paths at once, adding up the bytes
+/// required on each volume, as paths often share one.
+pub(crate) fn ensure_disk_space_for(requirements: &[(&Path, u64)]) -> Result<()> {
+    let mut volumes: Vec<(Option<u64>, &Path, u64)> = Vec::new();
+    for &(path, required) in requirements {
+        let volume = path
+            .ancestors()
+            .find(|p| p.exists())
+            .and_then(|p| raw::volume_id(p).ok());
+        match volumes
+            .iter_mut()
+            .find(|(id, ..)| volume.is_some() && *id == volume)
+        {
+            Some((_, _, total)) => *total = total.saturating_add(required),
+            None => volumes.push((volume, path, required)),
+        }
+    }
+    for (_, path, required) in volumes {
+        ensure_disk_space(path, required)?;
+    }
+    Ok(())
+}
+
 pub fn file_size(path: &Path) -> Result<u64> {
     Ok(fs::metadata(path)
         .with_context(|| RustupError::ReadingFile {
@@ -605,6 +815,9 @@ pub(crate) struct FileReaderWithProgress<'a> {
     notify_handler: &'a dyn Fn(Notification<'_>),
     nbytes: u64,
     flen: u64,
+    /// Whether the end of the file has been reported, which is only done once
+    /// so that the tracker can keep count of concurrent reads.
+    finished: bool,
 }
 
 impl<'a> FileReaderWithProgress<'a> {
@@ -633,6 +846,7 @@ impl<'a> FileReaderWithProgress<'a> {
             notify_handler,
             nbytes: 0,
             flen,
+            finished: false,
         })
     }
 }
@@ -645,7 +859,8 @@ impl<'a> io::Read for FileReaderWithProgress<'a> {
                 if nbytes != 0 {
                     (self.notify_handler)(Notification::DownloadDataReceived(&buf[0..nbytes]));
                 }
-                if (nbytes == 0) || (self.flen == self.nbytes) {
+                if !self.finished && (nbytes == 0 || self.flen == self.nbytes) {
+                    self.finished = true;
                     (self.notify_handler)(Notification::DownloadFinished);
                 }
                 Ok(nbytes)
@@ -726,4 +941,49 @@ mod tests {
         assert!(!f_path.exists());
         assert!(ensure_file_removed("f", &f_path).is_ok());
     }
+
+    #[test]
+    fn test_ensure_disk_space() {
+        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        let missing = tempdir.path().join("not/yet/created");
+
+        assert!(ensure_disk_space(&missing, 1).is_ok());
+
+        let err = ensure_disk_space(&missing, u64::MAX).unwrap_err();
+        match err.downcast_ref::<RustupError>() {
+            Some(RustupError::InsufficientDiskSpace { path, required, .. }) => {
+                assert_eq!(*path, missing);
+                assert_eq!(*required, u64::MAX);
+            }
+            _ => panic!("Expected an insufficient disk space error"),
+        }
+    }
+
+    #[test]
+    fn test_ensure_disk_space_for_adds_up_volumes() {
+        let tempdir = tempfile::Builder::new().prefix("rustup").tempdir().unwrap();
+        let (a, b) = (tempdir.path().join("a"), tempdir.path().join("b"));
+        let available = raw::available_space(tempdir.path()).unwrap();
+
+        // Each alone fits, both together don't.
+        let half = available / 2 + 1;
+        assert!(ensure_disk_space_for(&[(&a, half)]).is_ok());
+        let err = ensure_disk_space_for(&[(&a, half), (&b, half)]).unwrap_err();
+        match err.downcast_ref::<RustupError>() {
+            Some(RustupError::InsufficientDiskSpace { path, required, .. }) => {
+                assert_eq!(*path, a);
+                assert_eq!(*required, half * 2);
+            }
+            _ => panic!("Expected an insufficient disk space error"),
+        }
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("7f454C46"), Some(vec![0x7f, 0x45, 0x4c, 0x46]));
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("7f4"), None);
+        assert_eq!(decode_hex("+f"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
 }
@@ -579,3 +579,156 @@ async fn install_non_installable_toolchain() {
         )
         .await;
 }
+
+#[tokio::test]
+async fn install_from_config_file() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    let config = cx.config.current_dir().join("install.toml");
+    raw::write_file(
+        &config,
+        r#"
+default-toolchain = "nightly"
+profile = "minimal"
+components = ["rust-src"]
+toolchains = ["stable"]
+modify-path = false
+"#,
+    )
+    .unwrap();
+
+    cx.config
+        .expect_ok(&["rustup-init", "-y", "--config", &config.to_string_lossy()])
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "toolchain", "list"],
+            for_host!("stable-{0}\nnightly-{0} (active, default)\n"),
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "component", "list"], "rust-src (installed)")
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "component", "list"], "rust-docs (installed)")
+        .await;
+    if cfg!(unix) {
+        assert!(!cx.config.homedir.join(".profile").exists());
+    }
+}
+
+#[tokio::test]
+async fn install_from_config_file_respects_no_update_for_extra_toolchains() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_ok(&["rustup-init", "-y", "--no-modify-path"])
+        .await;
+    let config = cx.config.current_dir().join("install.toml");
+    let config_arg = config.to_string_lossy().into_owned();
+
+    raw::write_file(
+        &config,
+        r#"
+toolchains = ["stable", "nightly"]
+modify-path = false
+update-default-toolchain = false
+"#,
+    )
+    .unwrap();
+    let out = cx
+        .config
+        .run("rustup-init", ["-y", "--config", &config_arg], &[])
+        .await;
+    assert!(out.ok);
+    assert!(out
+        .stderr
+        .contains(for_host!("info: skipping existing toolchain 'stable-{0}'")));
+    assert!(!out.stderr.contains("profile choice will be ignored"));
+    assert!(cx.config.rustupdir.has(for_host!("toolchains/nightly-{0}")));
+
+    raw::write_file(&config, "toolchains = [\"nightly\"]\nmodify-path = false\n").unwrap();
+    let out = cx
+        .config
+        .run("rustup-init", ["-y", "--config", &config_arg], &[])
+        .await;
+    assert!(out.ok);
+    assert!(out
+        .stderr
+        .contains(for_host!("info: updating existing toolchain 'nightly-{0}'")));
+}
+
+#[tokio::test]
+async fn installer_shows_options_from_config_file() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    let config = cx.config.current_dir().join("install.toml");
+    raw::write_file(
+        &config,
+        r#"
+profile = "minimal"
+components = ["rust-src", "rust-analysis"]
+toolchains = ["nightly"]
+modify-path = false
+"#,
+    )
+    .unwrap();
+
+    let out = run_input(
+        &cx.config,
+        &["rustup-init", "--config", &config.to_string_lossy()],
+        "n\n",
+    );
+    assert!(out.ok);
+    assert!(
+        out.stdout.contains(
+            r"
+               profile: minimal
+  modify PATH variable: no
+            components: rust-src, rust-analysis
+      extra toolchains: nightly
+"
+        ),
+        "pattern not found in \"\"\"{}\"\"\"",
+        out.stdout
+    );
+}
+
+#[tokio::test]
+async fn install_from_invalid_config_file() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    let config = cx.config.current_dir().join("install.toml");
+    let config_arg = config.to_string_lossy().into_owned();
+
+    raw::write_file(
+        &config,
+        "default-toolchain = \"stable\"\nmodify-paths = false\n",
+    )
+    .unwrap();
+    cx.config
+        .expect_err(
+            &["rustup-init", "-y", "--config", &config_arg],
+            "unknown field `modify-paths`",
+        )
+        .await;
+
+    raw::write_file(&config, "toolchains = [\"stable\", \"not a toolchain\"]\n").unwrap();
+    cx.config
+        .expect_err(
+            &["rustup-init", "-y", "--config", &config_arg],
+            "Pre-checks for host and toolchain failed",
+        )
+        .await;
+    assert!(!cx.config.cargodir.join("bin").exists());
+
+    cx.config
+        .expect_err(
+            &[
+                "rustup-init",
+                "-y",
+                "--config",
+                &config_arg,
+                "--default-toolchain",
+                "stable",
+            ],
+            "the argument '--config <PATH>' cannot be used with '--default-toolchain <DEFAULT_TOOLCHAIN>'",
+        )
+        .await;
+}
@@ -600,6 +600,50 @@ async fn run_rls_when_not_installed_for_nightly() {
     ).await;
 }
 
+#[tokio::test]
+async fn run_rls_installs_missing_component() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "stable"]).await;
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "set", "auto-install-components", "always"],
+            "auto-install-components mode set to 'always'",
+        )
+        .await;
+    cx.config
+        .expect_stderr_ok(
+            &["rls", "--version"],
+            for_host!(
+                "installing the 'rls' component for the toolchain 'stable-{0}' to provide 'rls'"
+            ),
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "component", "list", "--installed"],
+            for_host!("rls-{0}"),
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn run_rls_without_auto_install() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "stable"]).await;
+    cx.config
+        .expect_ok(&["rustup", "set", "auto-install-components", "never"])
+        .await;
+    cx.config
+        .expect_err(
+            &["rls", "--version"],
+            "To install, run `rustup component add rls`",
+        )
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "component", "list", "--installed"], "rls")
+        .await;
+}
+
 #[tokio::test]
 async fn run_rust_lldb_when_not_in_toolchain() {
     let mut cx = CliTestContext::new(Scenario::UnavailableRls).await;
@@ -1002,6 +1046,198 @@ async fn completion_cargo_unsupported_shell() {
         .await;
 }
 
+#[tokio::test]
+async fn completion_proxies() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "completions", "bash", "proxies"],
+            "complete -o default -F _rustup_proxies rustc rustdoc",
+        )
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "completions", "bash", "proxies"], " cargo ")
+        .await;
+    cx.config
+        .expect_err(
+            &["rustup", "completions", "elvish", "proxies"],
+            "error: proxies does not currently support completions for ",
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn completion_scripts_call_rustup() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        cx.config
+            .expect_stdout_ok(&["rustup", "completions", shell], "rustup __complete")
+            .await;
+    }
+    cx.config
+        .expect_stdout_ok(&["rustup", "completions", "zsh", "cargo"], "_rustup_cargo")
+        .await;
+}
+
+#[tokio::test]
+async fn complete_subcommands_and_flags() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "__complete", "--", "rustup", "comp"],
+            "component\ncompletions\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &[
+                "rustup",
+                "__complete",
+                "--cword",
+                "2",
+                "--",
+                "rustup",
+                "toolchain",
+            ],
+            "install\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &[
+                "rustup",
+                "__complete",
+                "--",
+                "rustup",
+                "toolchain",
+                "install",
+                "--pro",
+            ],
+            "--profile\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &[
+                "rustup",
+                "__complete",
+                "--",
+                "rustup",
+                "set",
+                "profile",
+                "m",
+            ],
+            "minimal\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &[
+                "rustup",
+                "__complete",
+                "--",
+                "rustup",
+                "set",
+                "default-host",
+                "x86_64-unknown-",
+            ],
+            "x86_64-unknown-linux\n",
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn complete_toolchains() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "__complete", "--", "rustup", "default", ""],
+            for_host!("nightly-{0}\n"),
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &[
+                "rustup",
+                "__complete",
+                "--",
+                "rustup",
+                "toolchain",
+                "install",
+                "",
+            ],
+            "stable\nbeta\nnightly\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "__complete", "--", "rustup", "+n"],
+            for_host!("+nightly-{0}\n"),
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "__complete", "--", "cargo", "+"],
+            for_host!("+nightly-{0}\n"),
+        )
+        .await;
+    cx.config
+        .expect_not_stdout_ok(
+            &["rustup", "__complete", "--", "cargo", "build", "+"],
+            "+nightly",
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn complete_components_and_targets() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    let add = [
+        "rustup",
+        "__complete",
+        "--",
+        "rustup",
+        "component",
+        "add",
+        "",
+    ];
+    cx.config.expect_stdout_ok(&add, "rust-src\n").await;
+    cx.config.expect_not_stdout_ok(&add, "cargo\n").await;
+    let remove = [
+        "rustup",
+        "__complete",
+        "--",
+        "rustup",
+        "component",
+        "remove",
+        "",
+    ];
+    cx.config.expect_stdout_ok(&remove, "cargo\n").await;
+    cx.config.expect_not_stdout_ok(&remove, "rust-src\n").await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "__complete", "--", "rustup", "target", "add", ""],
+            clitools::CROSS_ARCH1,
+        )
+        .await;
+    cx.config
+        .expect_not_stdout_ok(
+            &[
+                "rustup",
+                "__complete",
+                "--",
+                "rustup",
+                "target",
+                "remove",
+                "",
+            ],
+            clitools::CROSS_ARCH1,
+        )
+        .await;
+}
+
 #[tokio::test]
 async fn add_remove_component() {
     let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -1063,6 +1299,81 @@ async fn which() {
         .await;
 }
 
+#[tokio::test]
+async fn env() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "install", "stable"])
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "env", "--shell", "bash"],
+            for_host!("export RUSTUP_TOOLCHAIN='nightly-{0}'"),
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "+stable", "env", "--shell", "fish"],
+            for_host!("set -gx RUSTUP_TOOLCHAIN 'stable-{0}'"),
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "env", "--shell", "powershell"],
+            "$Env:RUSTUP_HOME = '",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "env", "--shell", "json"],
+            for_host!(r#"  "RUSTUP_TOOLCHAIN": "nightly-{0}""#),
+        )
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "env"], "RUST_RECURSION_COUNT")
+        .await;
+    #[cfg(not(windows))]
+    {
+        let bin = cx
+            .config
+            .rustupdir
+            .join(for_host!("toolchains/nightly-{0}/bin"));
+        cx.config
+            .expect_stdout_ok(
+                &["rustup", "env", "--bin"],
+                &format!("export PATH='{}:", bin.display()),
+            )
+            .await;
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn shell_installs_and_sets_toolchain() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    // `env` stands in for an interactive shell, printing what it was given.
+    let out = cx
+        .config
+        .run(
+            "rustup",
+            ["shell", "+nightly"],
+            &[("SHELL", "/usr/bin/env")],
+        )
+        .await;
+    assert!(out.ok);
+    assert!(out
+        .stdout
+        .contains(for_host!("RUSTUP_TOOLCHAIN=nightly-{0}\n")));
+    assert!(out
+        .stdout
+        .contains(for_host!("RUSTUP_SHELL_TOOLCHAIN=nightly-{0}\n")));
+    assert!(out
+        .stderr
+        .contains("starting /usr/bin/env with the toolchain"));
+    assert!(cx.config.rustupdir.has(for_host!("toolchains/nightly-{0}")));
+}
+
 #[tokio::test]
 async fn which_asking_uninstalled_toolchain() {
     let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -333,6 +333,74 @@ export PATH="$HOME/apple/bin"
         }
     }
 
+    // The shells that need an env script of their own, with their rcfiles
+    // relative to $HOME and the env script's name.
+    const NON_POSIX_SHELLS: [(&str, &str, &str); 3] = [
+        ("nu", ".config/nushell/env.nu", "env.nu"),
+        ("elvish", ".config/elvish/rc.elv", "env.elv"),
+        ("xonsh", ".xonshrc", "env.xsh"),
+    ];
+
+    #[tokio::test]
+    async fn install_uninstall_non_posix_shell_rcs() {
+        let cx = CliTestContext::new(Scenario::Empty).await;
+        let cargodir = cx.config.cargodir.display();
+        for (shell, rc, env) in NON_POSIX_SHELLS {
+            let rc = cx.config.homedir.join(rc);
+            fs::create_dir_all(rc.parent().unwrap()).unwrap();
+            raw::write_file(&rc, FAKE_RC).unwrap();
+
+            let mut cmd = clitools::cmd(&cx.config, "rustup-init", &INIT_NONE[1..]);
+            cmd.env("SHELL", shell);
+            cmd.env_remove("XDG_CONFIG_HOME");
+            assert!(cmd.output().unwrap().status.success());
+
+            let source = match shell {
+                "elvish" => format!("eval (slurp < '{cargodir}/{env}')\n"),
+                _ => format!("source \"{cargodir}/{env}\"\n"),
+            };
+            let new_rc = fs::read_to_string(&rc).unwrap();
+            assert_eq!(new_rc, FAKE_RC.to_owned() + &source, "{shell}");
+            let script = fs::read_to_string(cx.config.cargodir.join(env)).unwrap();
+            assert!(script.contains(&format!("{cargodir}/bin")), "{shell}");
+
+            let mut cmd = clitools::cmd(&cx.config, "rustup", ["self", "uninstall", "-y"]);
+            cmd.env("SHELL", shell);
+            cmd.env_remove("XDG_CONFIG_HOME");
+            assert!(cmd.output().unwrap().status.success());
+
+            let new_rc = fs::read_to_string(&rc).unwrap();
+            assert_eq!(new_rc, FAKE_RC, "{shell}");
+        }
+    }
+
+    #[tokio::test]
+    async fn install_non_posix_shell_rcs_with_default_cargo_home() {
+        let cx = CliTestContext::new(Scenario::Empty).await;
+        for (shell, rc, source) in [
+            (
+                "nu",
+                ".config/nushell/env.nu",
+                "source $\"($nu.home-path)/.cargo/env.nu\"\n",
+            ),
+            (
+                "elvish",
+                ".config/elvish/rc.elv",
+                "eval (slurp < ~/.cargo/env.elv)\n",
+            ),
+            ("xonsh", ".xonshrc", "source \"$HOME/.cargo/env.xsh\"\n"),
+        ] {
+            let mut cmd = clitools::cmd(&cx.config, "rustup-init", &INIT_NONE[1..]);
+            cmd.env("SHELL", shell);
+            cmd.env_remove("XDG_CONFIG_HOME");
+            cmd.env_remove("CARGO_HOME");
+            assert!(cmd.output().unwrap().status.success());
+
+            let new_rc = fs::read_to_string(cx.config.homedir.join(rc)).unwrap();
+            assert_eq!(new_rc, source, "{shell}");
+        }
+    }
+
     // In the default case we want to write $HOME/.cargo/bin as the path,
     // not the full path.
     #[tokio::test]
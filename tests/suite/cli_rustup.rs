@@ -63,6 +63,54 @@ info: cleaning up downloads & tmp directories
         .await;
 }
 
+#[tokio::test]
+async fn rustup_stable_with_release_notes() {
+    let mut cx = CliTestContext::new(Scenario::None).await;
+
+    {
+        let mut cx = cx.with_dist_dir(Scenario::ArchivesV2_2015_01_01);
+        cx.config
+            .expect_ok(&["rustup", "toolchain", "add", "stable"])
+            .await;
+    }
+
+    let cx = cx.with_dist_dir(Scenario::SimpleV2);
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "set", "release-notes", "enable"],
+            "info: release notes will be shown on update",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "update"], "Mock release notes for 1.1.0")
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "update"], "Mock release notes")
+        .await;
+}
+
+#[tokio::test]
+async fn release_notes() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "stable"]).await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "release-notes"], "Mock release notes for 1.1.0")
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "release-notes"], "1.0.0")
+        .await;
+
+    cx.config
+        .expect_ok(&["rustup", "component", "remove", "rust-docs"])
+        .await;
+    cx.config
+        .expect_err(
+            &["rustup", "release-notes", "stable"],
+            for_host!("error: the release notes of toolchain 'stable-{0}' are not installed"),
+        )
+        .await;
+}
+
 #[tokio::test]
 async fn rustup_stable_quiet() {
     let mut cx = CliTestContext::new(Scenario::None).await;
@@ -201,6 +249,42 @@ info: cleaning up downloads & tmp directories
         .await;
 }
 
+#[tokio::test]
+async fn rustup_all_channels_concurrently() {
+    let mut cx = CliTestContext::new(Scenario::None).await;
+
+    {
+        let mut cx = cx.with_dist_dir(Scenario::ArchivesV2_2015_01_01);
+        cx.config
+            .expect_ok(&["rustup", "toolchain", "add", "stable", "beta", "nightly"])
+            .await;
+    }
+
+    // The output of the updates is interleaved, but not their summary.
+    let cx = cx.with_dist_dir(Scenario::SimpleV2);
+    let out = cx
+        .config
+        .run("rustup", ["update"], &[("RUSTUP_CONCURRENT_UPDATES", "3")])
+        .await;
+    assert!(out.ok);
+    assert_eq!(
+        &out.stdout,
+        for_host!(
+            r"
+   stable-{0} updated - 1.1.0 (hash-stable-1.1.0) (from 1.0.0 (hash-stable-1.0.0))
+     beta-{0} updated - 1.2.0 (hash-beta-1.2.0) (from 1.1.0 (hash-beta-1.1.0))
+  nightly-{0} updated - 1.3.0 (hash-nightly-2) (from 1.2.0 (hash-nightly-1))
+
+"
+        )
+    );
+    for channel in ["stable", "beta", "nightly"] {
+        assert!(out
+            .stderr
+            .contains(&format!("info: syncing channel updates for '{channel}-")));
+    }
+}
+
 #[tokio::test]
 async fn rustup_some_channels_up_to_date() {
     let mut cx = CliTestContext::new(Scenario::None).await;
@@ -2549,6 +2633,129 @@ async fn docs_topical_with_path() {
     }
 }
 
+#[tokio::test]
+async fn docs_search() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "stable"]).await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "doc", "--search", "read_dir"],
+            " 1. fn        std::fs::read_dir\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "doc", "--search", "BYTES"],
+            "struct    std::io::Bytes",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &[
+                "rustup", "doc", "--search", "format", "--open", "1", "--path",
+            ],
+            &mock::topical_doc_data::test_cases()
+                .find(|(topic, _)| *topic == "alloc::format")
+                .unwrap()
+                .1,
+        )
+        .await;
+    cx.config
+        .expect_err(
+            &["rustup", "doc", "--search", "read_dir", "--open", "9"],
+            "error: there is no result 9 for 'read_dir', there are 1",
+        )
+        .await;
+    cx.config
+        .expect_err(
+            &["rustup", "doc", "--search", "no_such_item"],
+            "error: no documentation matches 'no_such_item'",
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn docs_serve() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::process::Stdio;
+
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "stable"]).await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "install", "nightly"])
+        .await;
+
+    let mut cmd = clitools::cmd(
+        &cx.config,
+        "rustup",
+        [
+            "doc",
+            "--serve",
+            "--toolchain",
+            "stable",
+            "--toolchain",
+            "nightly",
+            "std::fs",
+        ],
+    );
+    clitools::env(&cx.config, &mut cmd);
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut urls = Vec::new();
+    for _ in 0..2 {
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        urls.push(line.trim().to_owned());
+    }
+
+    let get = |url: &str| {
+        let (addr, path) = url
+            .strip_prefix("http://")
+            .unwrap()
+            .split_once('/')
+            .unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET /{path} HTTP/1.1\r\nHost: {addr}\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let responses = urls.iter().map(|url| get(url)).collect::<Vec<_>>();
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(
+        urls[0].ends_with(for_host!("/stable-{0}/std/fs/index.html")),
+        "{urls:?}"
+    );
+    assert!(
+        urls[1].ends_with(for_host!("/nightly-{0}/std/fs/index.html")),
+        "{urls:?}"
+    );
+    for response in responses {
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    }
+
+    cx.config
+        .expect_err(
+            &[
+                "rustup",
+                "doc",
+                "--toolchain",
+                "stable",
+                "--toolchain",
+                "nightly",
+            ],
+            "error: only `rustup doc --serve` accepts more than one toolchain",
+        )
+        .await;
+}
+
 #[tokio::test]
 async fn docs_missing() {
     let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -2722,10 +2929,15 @@ async fn warn_on_unmatch_build() {
     cx.config.expect_stderr_ok(
         &["rustup", "toolchain", "install", &format!("nightly-{arch}")],
         &format!(
-            r"warn: toolchain 'nightly-{arch}' may not be able to run on this system.
-warn: If you meant to build software to target that platform, perhaps try `rustup target add {arch}` instead?",
+            r"warn: If you meant to build software to target that platform, perhaps try `rustup target add {arch}` instead?",
         ),
     ).await;
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "toolchain", "install", &format!("nightly-{arch}")],
+            &format!("warn: toolchain 'nightly-{arch}' may not be able to run on this system: "),
+        )
+        .await;
 }
 
 #[tokio::test]
@@ -2735,10 +2947,55 @@ async fn warn_on_unmatch_build_default() {
     cx.config.expect_stderr_ok(
         &["rustup", "default", &format!("nightly-{arch}")],
         &format!(
-            r"warn: toolchain 'nightly-{arch}' may not be able to run on this system.
-warn: If you meant to build software to target that platform, perhaps try `rustup target add {arch}` instead?",
+            r"warn: If you meant to build software to target that platform, perhaps try `rustup target add {arch}` instead?",
         ),
     ).await;
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "default", &format!("nightly-{arch}")],
+            &format!("warn: toolchain 'nightly-{arch}' may not be able to run on this system: "),
+        )
+        .await;
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn dont_warn_on_emulated_build() {
+    let cx = CliTestContext::new(Scenario::MultiHost).await;
+    let arch = clitools::MULTI_ARCH1;
+    let (machine, class) = if arch.starts_with("i686") {
+        ("0300", "01")
+    } else {
+        ("3e00", "02")
+    };
+    let root = cx.config.workdir.borrow().join("host-root");
+    let binfmt = root.join("proc/sys/fs/binfmt_misc");
+    fs::create_dir_all(&binfmt).unwrap();
+    fs::write(binfmt.join("status"), "enabled\n").unwrap();
+    fs::write(
+        binfmt.join("qemu"),
+        format!(
+            "enabled\ninterpreter /usr/bin/qemu\nflags: F\noffset 0\n\
+             magic 7f454c46{class}01010000000000000000000200{machine}\n\
+             mask ffffffffffffff00fffffffffffffffffeffffff\n"
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = clitools::cmd(
+        &cx.config,
+        "rustup",
+        ["toolchain", "install", &format!("nightly-{arch}")],
+    );
+    clitools::env(&cx.config, &mut cmd);
+    cmd.env("RUSTUP_OVERRIDE_HOST_ROOT", &root);
+    let out = cmd.output().unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains(&format!(
+        "info: toolchain 'nightly-{arch}' will run under emulation by /usr/bin/qemu"
+    )));
+    assert!(!stderr.contains("may not be able to run on this system"));
 }
 
 #[tokio::test]
@@ -2759,7 +3016,7 @@ async fn dont_warn_on_partial_build() {
         r"info: syncing channel updates for 'nightly-{triple}'"
     )));
     assert!(!stderr.contains(&format!(
-        r"warn: toolchain 'nightly-{arch}' may not be able to run on this system."
+        r"warn: toolchain 'nightly-{arch}' may not be able to run on this system"
     )));
 }
 
@@ -3,6 +3,7 @@
 use std::env;
 use std::env::consts::EXE_SUFFIX;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::process::Command;
 
@@ -463,6 +464,68 @@ async fn update_updates_rustup_bin() {
     assert_ne!(before_hash, after_hash);
 }
 
+#[tokio::test]
+async fn update_fails_without_signature() {
+    let mut cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect_ok(&["rustup-init", "-y", "--no-modify-path"])
+        .await;
+
+    let trip = this_host_triple();
+    let dist_exe = cx.path().join(format!(
+        "archive/{TEST_VERSION}/{trip}/rustup-init{EXE_SUFFIX}"
+    ));
+    fs::remove_file(format!("{}.sig", dist_exe.display())).unwrap();
+
+    let bin = cx.config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+    let before_hash = calc_hash(&bin);
+
+    cx.config
+        .expect_err(
+            &["rustup", "self", "update"],
+            "could not download the signature of the self-update",
+        )
+        .await;
+    assert_eq!(before_hash, calc_hash(&bin));
+    let setup = cx
+        .config
+        .cargodir
+        .join(format!("bin/rustup-init{EXE_SUFFIX}"));
+    assert!(!setup.exists());
+}
+
+#[tokio::test]
+async fn update_fails_with_bad_signature() {
+    let mut cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect_ok(&["rustup-init", "-y", "--no-modify-path"])
+        .await;
+
+    // Tamper with the binary after it was signed
+    let trip = this_host_triple();
+    let dist_exe = cx.path().join(format!(
+        "archive/{TEST_VERSION}/{trip}/rustup-init{EXE_SUFFIX}"
+    ));
+    let mut dist_file = fs::OpenOptions::new().append(true).open(&dist_exe).unwrap();
+    writeln!(dist_file).unwrap();
+
+    let bin = cx.config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+    let before_hash = calc_hash(&bin);
+
+    cx.config
+        .expect_err(
+            &["rustup", "self", "update"],
+            "refusing to install an unverified rustup",
+        )
+        .await;
+    assert_eq!(before_hash, calc_hash(&bin));
+    let setup = cx
+        .config
+        .cargodir
+        .join(format!("bin/rustup-init{EXE_SUFFIX}"));
+    assert!(!setup.exists());
+}
+
 #[tokio::test]
 async fn update_bad_schema() {
     let mut cx = SelfUpdateTestContext::new(TEST_VERSION).await;
@@ -497,6 +560,134 @@ async fn update_no_change() {
         .await;
 }
 
+#[tokio::test]
+async fn update_to_version() {
+    let version = env!("CARGO_PKG_VERSION");
+    let mut cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect_ok(&["rustup-init", "-y", "--no-modify-path"])
+        .await;
+    // The release file no longer points at the archived version
+    output_release_file(cx.path(), "1", version);
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "self", "update", "--version", TEST_VERSION],
+            "info: downloading self-update",
+        )
+        .await;
+    cx.config
+        .expect_err(
+            &["rustup", "self", "update", "--version", "latest"],
+            "invalid rustup version: 'latest'",
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn update_outside_pinned_version() {
+    let version = env!("CARGO_PKG_VERSION");
+    let mut cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect_ok(&["rustup-init", "-y", "--no-modify-path"])
+        .await;
+    cx.config
+        .expect_ok(&["rustup", "set", "self-update-version", "~1.0"])
+        .await;
+
+    let bin = cx.config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+    let before_hash = calc_hash(&bin);
+
+    cx.config
+        .expect_ok_ex(
+            &["rustup", "self", "update"],
+            &format!("  rustup unchanged - {version}\n\n"),
+            &format!(
+                r"info: checking for self-update
+info: skipping rustup {TEST_VERSION}, which doesn't match the pinned version '~1.0'
+"
+            ),
+        )
+        .await;
+    assert_eq!(before_hash, calc_hash(&bin));
+
+    cx.config
+        .expect_ok(&["rustup", "set", "self-update-version"])
+        .await;
+    cx.config.expect_ok(&["rustup", "self", "update"]).await;
+    assert_ne!(before_hash, calc_hash(&bin));
+}
+
+#[tokio::test]
+async fn update_to_highest_pinned_version() {
+    let mut cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect_ok(&["rustup-init", "-y", "--no-modify-path"])
+        .await;
+    cx.config
+        .expect_ok(&["rustup", "set", "self-update-version", "~1.1"])
+        .await;
+    // Only the release the pin matches is in the archive
+    fs::write(
+        cx.path().join("release-stable.toml"),
+        format!(
+            r#"schema-version = "1"
+version = "1.2.0"
+versions = ["1.0.0", "1.1.0", "{TEST_VERSION}", "1.2.0"]
+"#
+        ),
+    )
+    .unwrap();
+
+    let bin = cx.config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+    let before_hash = calc_hash(&bin);
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "self", "update"],
+            "info: downloading self-update",
+        )
+        .await;
+    assert_ne!(before_hash, calc_hash(&bin));
+}
+
+#[tokio::test]
+async fn set_self_update_version_invalid() {
+    let cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect_err(
+            &["rustup", "set", "self-update-version", "one"],
+            "invalid version requirement: 'one'",
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn rollback_restores_previous_rustup() {
+    let mut cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect_ok(&["rustup-init", "-y", "--no-modify-path"])
+        .await;
+    cx.config
+        .expect_err(
+            &["rustup", "self", "rollback"],
+            "there is no previous version of rustup to roll back to",
+        )
+        .await;
+
+    let bin = cx.config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+    let before_hash = calc_hash(&bin);
+
+    cx.config.expect_ok(&["rustup", "self", "update"]).await;
+    let after_hash = calc_hash(&bin);
+    assert_ne!(before_hash, after_hash);
+
+    cx.config.expect_ok(&["rustup", "self", "rollback"]).await;
+    assert_eq!(before_hash, calc_hash(&bin));
+
+    // Rolling back again returns to the updated rustup
+    cx.config.expect_ok(&["rustup", "self", "rollback"]).await;
+    assert_eq!(after_hash, calc_hash(&bin));
+}
+
 #[tokio::test]
 async fn rustup_self_updates_trivial() {
     let mut cx = SelfUpdateTestContext::new(TEST_VERSION).await;
@@ -113,6 +113,125 @@ async fn install_with_profile() {
     cx.config.expect_component_not_executable("cargo").await;
 }
 
+#[tokio::test]
+async fn install_with_custom_profile() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_ok(&["rustup", "set", "profile", "minimal"])
+        .await;
+    let settings = cx.config.rustupdir.join("settings.toml");
+    let mut contents = fs::read_to_string(&settings).unwrap();
+    contents.push_str(
+        "\n[profiles.team]\nbase = \"minimal\"\ncomponents = [\"cargo\", \"rust-src\"]\n",
+    );
+    fs::write(&settings, contents).unwrap();
+
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "set", "profile", "team"],
+            "profile set to 'team'",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "show", "profile"], "team")
+        .await;
+    // Older versions of rustup only know the base
+    let contents = fs::read_to_string(&settings).unwrap();
+    assert!(contents.contains("profile = \"minimal\"\ncustom_profile = \"team\"\n"));
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+
+    cx.config.expect_component_executable("rustc").await;
+    cx.config.expect_component_executable("cargo").await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "component", "list", "--installed"], "rust-src")
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "component", "list", "--installed"], "rust-docs")
+        .await;
+}
+
+#[tokio::test]
+async fn custom_profile_errors() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_err(
+            &["rustup", "set", "profile", "team"],
+            "unknown profile name: 'team'; valid profile names are: minimal, default, complete",
+        )
+        .await;
+
+    let settings = cx.config.rustupdir.join("settings.toml");
+    let mut contents = fs::read_to_string(&settings).unwrap();
+    contents.push_str("\n[profiles.team]\ncomponents = [\"bogus\"]\n");
+    fs::write(&settings, contents).unwrap();
+    cx.config
+        .expect_err(
+            &[
+                "rustup",
+                "toolchain",
+                "install",
+                "--profile",
+                "team",
+                "nightly",
+            ],
+            "component 'bogus' of profile 'team' is not in the manifest",
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn toolchain_set_profile() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_ok(&[
+            "rustup",
+            "toolchain",
+            "install",
+            "--profile",
+            "minimal",
+            "nightly",
+        ])
+        .await;
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    cx.config.expect_component_not_executable("cargo").await;
+
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "toolchain", "set-profile", "nightly", "default"],
+            for_host!("toolchain 'nightly-{0}' now has the components of profile 'default'"),
+        )
+        .await;
+    cx.config.expect_component_executable("cargo").await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "component", "list", "--installed"], "rust-docs")
+        .await;
+
+    // Without `--prune`, moving to a smaller profile keeps everything.
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "toolchain", "set-profile", "nightly", "minimal"],
+            for_host!("toolchain 'nightly-{0}' already has the components of profile 'minimal'"),
+        )
+        .await;
+    cx.config.expect_component_executable("cargo").await;
+
+    cx.config
+        .expect_ok(&[
+            "rustup",
+            "toolchain",
+            "set-profile",
+            "nightly",
+            "minimal",
+            "--prune",
+        ])
+        .await;
+    cx.config.expect_component_executable("rustc").await;
+    cx.config.expect_component_not_executable("cargo").await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "component", "list", "--installed"], "rust-docs")
+        .await;
+}
+
 #[tokio::test]
 async fn default_existing_toolchain() {
     let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -140,6 +259,224 @@ async fn update_channel() {
         .await;
 }
 
+#[tokio::test]
+async fn update_channel_dry_run() {
+    let mut cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    set_current_dist_date(&cx.config, "2015-01-01");
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    set_current_dist_date(&cx.config, "2015-01-02");
+    cx.config
+        .expect_ok_contains(
+            &["rustup", "update", "nightly", "--dry-run"],
+            for_host!("  upgrade rustc-{0} 1.2.0 (hash-nightly-1) -> 1.3.0 (hash-nightly-2)"),
+            "",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "update", "--dry-run"], "total download size: ")
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1")
+        .await;
+}
+
+#[tokio::test]
+async fn toolchain_install_dry_run() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "toolchain", "install", "nightly", "--dry-run"],
+            for_host!("  add     rustc-{0} 1.3.0 (hash-nightly-2)"),
+        )
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "toolchain", "list"], "nightly")
+        .await;
+    assert!(!cx.config.rustupdir.has(for_host!("toolchains/nightly-{0}")));
+}
+
+#[tokio::test]
+async fn rollback_undoes_update() {
+    let mut cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    set_current_dist_date(&cx.config, "2015-01-01");
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    set_current_dist_date(&cx.config, "2015-01-02");
+    cx.config.expect_ok(&["rustup", "update", "nightly"]).await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2")
+        .await;
+
+    cx.config
+        .expect_ok_contains(
+            &["rustup", "toolchain", "rollback", "nightly"],
+            "",
+            for_host!("info: rolled back 'nightly-{0}' to its 2015-01-01 release"),
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1")
+        .await;
+
+    // The next update isn't skipped because the channel was already checked
+    cx.config.expect_ok(&["rustup", "update", "nightly"]).await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2")
+        .await;
+}
+
+#[tokio::test]
+async fn rollback_twice_goes_further_back() {
+    let mut cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    set_current_dist_date(&cx.config, "2015-01-01");
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    set_current_dist_date(&cx.config, "2015-01-02");
+    cx.config.expect_ok(&["rustup", "update", "nightly"]).await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "rollback", "nightly"])
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1")
+        .await;
+
+    // Nothing is recorded before the update the first rollback undid, so
+    // the second one doesn't bring the rolled back release again.
+    cx.config
+        .expect_err(
+            &["rustup", "toolchain", "rollback", "nightly"],
+            for_host!("no earlier state of 'nightly-{0}' is recorded in its history"),
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1")
+        .await;
+}
+
+#[tokio::test]
+async fn rollback_reinstalls_uninstalled_toolchain() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "install", "nightly"])
+        .await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "uninstall", "nightly"])
+        .await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "rollback", "nightly"])
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "toolchain", "list"], "nightly")
+        .await;
+}
+
+#[tokio::test]
+async fn rollback_without_history() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "install", "nightly"])
+        .await;
+    cx.config
+        .expect_err(
+            &["rustup", "toolchain", "rollback", "nightly"],
+            for_host!("no earlier state of 'nightly-{0}' is recorded in its history"),
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn history_records_changes() {
+    let mut cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    set_current_dist_date(&cx.config, "2015-01-01");
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    set_current_dist_date(&cx.config, "2015-01-02");
+    cx.config.expect_ok(&["rustup", "update", "nightly"]).await;
+    cx.config
+        .expect_ok(&["rustup", "override", "set", "stable"])
+        .await;
+
+    let out = cx.config.run("rustup", ["history"], &[]).await;
+    assert!(out.ok);
+    let lines: Vec<_> = out.stdout.lines().collect();
+    assert_eq!(lines.len(), 5, "{}", out.stdout);
+    assert!(lines[0].contains(for_host!("install    nightly-{0}")));
+    assert!(lines[1].contains(for_host!("default    nightly-{0} (was none)")));
+    assert!(lines[2].contains(for_host!(
+        "update     nightly-{0} (was 2015-01-01, with cargo-{0}, rust-docs-{0}, rust-std-{0}, rustc-{0})"
+    )));
+    assert!(lines[3].contains(for_host!("install    stable-{0}")));
+    assert!(lines[4].contains(for_host!("override   stable-{0} for '")));
+}
+
+#[tokio::test]
+async fn update_skips_held_toolchain() {
+    let mut cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    set_current_dist_date(&cx.config, "2015-01-01");
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "toolchain", "hold", "nightly"],
+            for_host!("toolchain 'nightly-{0}' held"),
+        )
+        .await;
+    set_current_dist_date(&cx.config, "2015-01-02");
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "update"],
+            for_host!("skipping held toolchain 'nightly-{0}'"),
+        )
+        .await;
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "update", "nightly"],
+            for_host!("skipping held toolchain 'nightly-{0}'"),
+        )
+        .await;
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "update", "--dry-run"],
+            for_host!("skipping held toolchain 'nightly-{0}'"),
+        )
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "update", "nightly", "--dry-run"], "upgrade")
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-1")
+        .await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "install", "nightly", "--force"])
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2")
+        .await;
+}
+
+#[tokio::test]
+async fn unhold_lets_update_proceed() {
+    let mut cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    set_current_dist_date(&cx.config, "2015-01-01");
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    cx.config
+        .expect_ok(&["rustup", "toolchain", "hold", "nightly"])
+        .await;
+    cx.config
+        .expect_stdout_ok(&["rustup", "toolchain", "list"], "(active, default, held)")
+        .await;
+    cx.config
+        .expect_stderr_ok(
+            &["rustup", "toolchain", "unhold", "nightly"],
+            for_host!("toolchain 'nightly-{0}' no longer held"),
+        )
+        .await;
+    cx.config
+        .expect_not_stdout_ok(&["rustup", "toolchain", "list"], "held")
+        .await;
+    set_current_dist_date(&cx.config, "2015-01-02");
+    cx.config.expect_ok(&["rustup", "update"]).await;
+    cx.config
+        .expect_stdout_ok(&["rustc", "--version"], "hash-nightly-2")
+        .await;
+}
+
 #[tokio::test]
 async fn list_toolchains() {
     let mut cx = CliTestContext::new(Scenario::ArchivesV2).await;
@@ -839,6 +1176,57 @@ async fn list_installed_targets() {
         .await;
 }
 
+#[tokio::test]
+async fn target_info() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    cx.config
+        .expect_ok(&["rustup", "target", "add", clitools::CROSS_ARCH2])
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "target", "info", clitools::CROSS_ARCH2],
+            "target: arm-linux-androideabi
+  arch: arm
+  os: linux
+  env: android
+  abi: eabi
+tier: 2
+",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "target", "info", clitools::CROSS_ARCH2],
+            "rust-std: installed\ncomponents: rust-std (installed)\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "target", "info", clitools::CROSS_ARCH1],
+            "rust-std: available\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "target", "info", &this_host_triple()],
+            "tier: 1 (with host tools)\n",
+        )
+        .await;
+    cx.config
+        .expect_stdout_ok(
+            &["rustup", "target", "info", "riscv32imc-unknown-none-elf"],
+            "rust-std: not distributed",
+        )
+        .await;
+    cx.config
+        .expect_err(
+            &["rustup", "target", "info", "x86_64-unknown-linux-gnux"],
+            "invalid target triple: 'x86_64-unknown-linux-gnux'; did you mean 'x86_64-unknown-linux-gnu'?",
+        )
+        .await;
+}
+
 #[tokio::test]
 async fn add_target1() {
     let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -854,6 +1242,30 @@ async fn add_target1() {
     assert!(cx.config.rustupdir.has(path));
 }
 
+#[tokio::test]
+async fn add_target_dry_run() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config.expect_ok(&["rustup", "default", "nightly"]).await;
+    cx.config
+        .expect_stdout_ok(
+            &[
+                "rustup",
+                "target",
+                "add",
+                clitools::CROSS_ARCH1,
+                "--dry-run",
+            ],
+            &format!("  add     rust-std-{}", clitools::CROSS_ARCH1),
+        )
+        .await;
+    let path = format!(
+        "toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+        this_host_triple(),
+        clitools::CROSS_ARCH1
+    );
+    assert!(!cx.config.rustupdir.has(path));
+}
+
 #[tokio::test]
 async fn add_target2() {
     let mut cx = CliTestContext::new(Scenario::SimpleV2).await;